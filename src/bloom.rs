@@ -0,0 +1,62 @@
+//! Bloom filter of the public keys already reported, so `--dedup` can skip a
+//! key that is derived again. It never forgets a key it has seen, and only
+//! wrongly takes a new key for a seen one at `DEDUP_FALSE_POSITIVE_RATE` when
+//! it holds its expected capacity.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Chance that a new key is taken for one already reported, at capacity
+pub const DEDUP_FALSE_POSITIVE_RATE: f64 = 1e-9;
+
+/// Bloom filter over 32 byte public keys, shared by the mining threads
+pub struct BloomFilter {
+    words: Vec<AtomicU64>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// Size the filter for `capacity` keys at `false_positive_rate`
+    pub fn with_capacity(capacity: usize, false_positive_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(capacity.max(1) as f64) * false_positive_rate.ln() / (ln2 * ln2)).ceil();
+        let words = (bits as usize).div_ceil(64).max(1);
+        let hashes = ((words * 64) as f64 / capacity.max(1) as f64 * ln2).round() as u32;
+        Self {
+            words: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            hashes: hashes.clamp(1, 64),
+        }
+    }
+
+    /// Size of the filter in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.words.len() * 8
+    }
+
+    /// Bits set for `key`. Public keys are uniformly distributed, so two
+    /// halves of the key bytes serve as the (double) hashes directly.
+    fn bit_indexes(&self, key: &[u8; 32]) -> impl Iterator<Item = usize> + '_ {
+        let half = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().unwrap());
+        let h1 = half(&key[..8]) ^ half(&key[16..24]);
+        let h2 = (half(&key[8..16]) ^ half(&key[24..])) | 1;
+        let bits = self.words.len() as u64 * 64;
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize)
+    }
+
+    /// Add `key`, returning whether it was new
+    pub fn insert(&self, key: &[u8; 32]) -> bool {
+        let mut new = false;
+        for bit in self.bit_indexes(key) {
+            let mask = 1 << (bit % 64);
+            if self.words[bit / 64].fetch_or(mask, Ordering::Relaxed) & mask == 0 {
+                new = true;
+            }
+        }
+        new
+    }
+
+    /// Whether `key` was probably added before
+    pub fn contains(&self, key: &[u8; 32]) -> bool {
+        self.bit_indexes(key)
+            .all(|bit| self.words[bit / 64].load(Ordering::Relaxed) & (1 << (bit % 64)) != 0)
+    }
+}
@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use nostr::prelude::*;
+use qrcode::{Color, QrCode};
+
+/// Pixels per QR module
+pub const MODULE_PX: u32 = 8;
+/// Light modules around each QR code, as required by the QR spec
+pub const QUIET_ZONE: u32 = 4;
+/// Pixels per font dot of the labels
+pub const GLYPH_PX: u32 = 4;
+/// Space between and around the card elements
+pub const PADDING: u32 = 16;
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const LABEL_HEIGHT: u32 = GLYPH_HEIGHT * GLYPH_PX;
+
+const PUBLIC_LABEL: &str = "NPUB (PUBLIC)";
+const SECRET_LABEL: &str = "NSEC (SECRET)";
+
+/// Rows of a 5x7 glyph, most significant of the 5 bits on the left. Only the
+/// characters used by the card labels are defined.
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'N' => [0x11, 0x19, 0x15, 0x13, 0x11, 0x11, 0x11],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0; 7],
+    }
+}
+
+/// Grayscale image with a white background
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![255; (width * height) as usize],
+        }
+    }
+
+    fn fill(&mut self, x: u32, y: u32, size: u32) {
+        for row in y..y + size {
+            let start = (row * self.width + x) as usize;
+            self.pixels[start..start + size as usize].fill(0);
+        }
+    }
+
+    fn draw_qr(&mut self, code: &QrCode, x: u32, y: u32) {
+        let width = code.width() as u32;
+        for (i, color) in code.to_colors().iter().enumerate() {
+            if *color == Color::Dark {
+                let i = i as u32;
+                let module_x = x + (QUIET_ZONE + i % width) * MODULE_PX;
+                let module_y = y + (QUIET_ZONE + i / width) * MODULE_PX;
+                self.fill(module_x, module_y, MODULE_PX);
+            }
+        }
+    }
+
+    fn draw_label(&mut self, label: &str, x: u32, y: u32) {
+        for (i, c) in label.chars().enumerate() {
+            let glyph_x = x + i as u32 * (GLYPH_WIDTH + 1) * GLYPH_PX;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                        self.fill(
+                            glyph_x + col * GLYPH_PX,
+                            y + row as u32 * GLYPH_PX,
+                            GLYPH_PX,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn label_width(label: &str) -> u32 {
+    label.chars().count() as u32 * (GLYPH_WIDTH + 1) * GLYPH_PX
+}
+
+fn qr_size(code: &QrCode) -> u32 {
+    (code.width() as u32 + 2 * QUIET_ZONE) * MODULE_PX
+}
+
+/// Render a printable card with the labelled npub QR code and, if
+/// `include_secret` is set, the nsec QR code next to it. Returns the
+/// grayscale pixels along with the image width and height.
+pub fn render_backup_card(keys: &Keys, include_secret: bool) -> Result<(Vec<u8>, u32, u32)> {
+    let mut panels = vec![(PUBLIC_LABEL, QrCode::new(keys.public_key().to_bech32()?)?)];
+    if include_secret {
+        panels.push((SECRET_LABEL, QrCode::new(keys.secret_key()?.to_bech32()?)?));
+    }
+
+    let panel_width = panels
+        .iter()
+        .map(|(label, code)| qr_size(code).max(label_width(label)))
+        .max()
+        .unwrap_or(0);
+    let qr_height = panels
+        .iter()
+        .map(|(_, code)| qr_size(code))
+        .max()
+        .unwrap_or(0);
+    let panel_count = panels.len() as u32;
+    let width = PADDING + panel_count * (panel_width + PADDING);
+    let height = PADDING + LABEL_HEIGHT + qr_height + PADDING;
+
+    let mut canvas = Canvas::new(width, height);
+    for (i, (label, code)) in panels.iter().enumerate() {
+        let panel_x = PADDING + i as u32 * (panel_width + PADDING);
+        canvas.draw_label(label, panel_x, PADDING);
+        canvas.draw_qr(code, panel_x, PADDING + LABEL_HEIGHT);
+    }
+
+    Ok((canvas.pixels, canvas.width, canvas.height))
+}
+
+/// Path of the card of the `index`th key found in a run, counted from 1. The
+/// first card goes to `path` itself, later ones get the index before the
+/// extension, e.g. card-2.png, so no card overwrites another.
+pub fn backup_card_path(path: &str, index: u64) -> String {
+    if index <= 1 {
+        return path.to_string();
+    }
+    let path = std::path::Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}-{index}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{index}"),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Write the backup card of the keys to a PNG file
+pub fn write_backup_card(keys: &Keys, include_secret: bool, path: &str) -> Result<()> {
+    let (pixels, width, height) = render_backup_card(keys, include_secret)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+    Ok(())
+}
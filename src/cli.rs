@@ -1,6 +1,225 @@
-use clap::Parser;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use nostr::prelude::{Metadata, SecretKey, Url};
 use regex::Regex;
 
+/// Format used to print the found keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable multi-line report
+    Plain,
+    /// Bare hex secret key on a single line, as read by `nak` and `nostr-tool`
+    Nak,
+    /// One JSON object per line, flushed after every match
+    Jsonl,
+    /// `NOSTR_*=...` shell variable assignments, to `eval` or save as a `.env`
+    Env,
+    /// A single `npub=... nsec=... diff=... iter=...` line per match, to tail
+    Compact,
+    /// A header row, then one comma separated row per match, for spreadsheets
+    Csv,
+}
+
+impl OutputFormat {
+    /// Whether stdout is reserved for key output, in which case status lines
+    /// go to stderr so the output can be piped to other tools
+    pub fn is_machine_readable(self) -> bool {
+        self != OutputFormat::Plain
+    }
+}
+
+/// Encoding of the secret key in the reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SecretFormat {
+    /// Hexadecimal secret key
+    Hex,
+    /// Bech32 `nsec` secret key
+    Nsec,
+    /// Both of them
+    Both,
+}
+
+/// Bech32 entity whose encoding is matched against the vanity targets
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Hrp {
+    /// Public key
+    Npub,
+    /// Secret key
+    Nsec,
+}
+
+impl Hrp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Hrp::Npub => "npub",
+            Hrp::Nsec => "nsec",
+        }
+    }
+}
+
+/// How the QR code of a secret is drawn in the terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QrStyle {
+    /// A half block for every two modules
+    Blocks,
+    /// A quadrant block for every four modules, with --qr-compact
+    Compact,
+    /// `#` and spaces, for terminals without the block characters
+    Ascii,
+}
+
+/// How the difficulty bar moves after a key reaching it is found
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DifficultyBar {
+    /// Raised to every find, so only ever rarer keys are reported
+    OpenEnded,
+    /// Kept at the difficulty, so every key reaching it is reported
+    Fixed,
+}
+
+/// Source of the randomness the secret keys are drawn from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RngKind {
+    /// The thread local generator of `rand`, seeded from the system
+    Thread,
+    /// The system's generator directly, for every key
+    Os,
+    /// ChaCha20, seeded with `--seed` for reproducible runs
+    Chacha,
+}
+
+/// Matching strategy used by the miner
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    /// Leading zero bits of the public key
+    Difficulty,
+    /// Hexadecimal vanity prefix
+    Hex,
+    /// Bech32 (npub) vanity prefixes and/or suffixes
+    Npub,
+}
+
+/// How a run ended, reported as the process exit code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// At least one matching key was found
+    Found = 0,
+    /// The run could not go on, e.g. the coordinator could not be reached
+    Failed = 1,
+    /// The arguments were rejected, the same code clap uses for usage errors
+    InvalidArguments = 2,
+    /// `--timeout` or `--max-iterations` was reached without a match
+    NoMatch = 3,
+    /// Stopped with Ctrl+C before anything was found
+    Interrupted = 130,
+}
+
+impl Outcome {
+    /// Outcome of a run once all the workers stopped
+    pub fn of_run(found_any: bool, interrupted: bool) -> Self {
+        if found_any {
+            Outcome::Found
+        } else if interrupted {
+            Outcome::Interrupted
+        } else {
+            Outcome::NoMatch
+        }
+    }
+}
+
+impl From<Outcome> for std::process::ExitCode {
+    fn from(outcome: Outcome) -> Self {
+        std::process::ExitCode::from(outcome as u8)
+    }
+}
+
+/// Tools that run instead of the miner
+#[derive(Subcommand)]
+pub enum Command {
+    /// Inspect the keys saved with --keystore
+    Keystore {
+        #[command(subcommand)]
+        action: KeystoreCommand,
+    },
+    /// Check that an nsec and an npub are the same key, e.g. for a backup
+    Verify {
+        /// Secret key, as an nsec or hex
+        #[arg(long = "nsec")]
+        nsec: String,
+        /// Public key, as an npub or hex
+        #[arg(long = "npub")]
+        npub: String,
+    },
+    /// Check BIP-39 mnemonics
+    Mnemonic {
+        #[command(subcommand)]
+        action: MnemonicCommand,
+    },
+    /// Print the JSON Schema of the key matches of --format jsonl
+    Schema,
+    /// Print how many keys a target takes to find and, given a rate, how
+    /// long, without mining or benchmarking
+    #[command(group(
+        ArgGroup::new("target")
+            .required(true)
+            .multiple(true)
+            .args(["prefix", "suffix", "hex", "bits"])
+    ))]
+    Estimate {
+        /// npub prefix, without the npub1
+        #[arg(long = "prefix", default_value = "")]
+        prefix: String,
+        /// npub suffix
+        #[arg(long = "suffix", default_value = "")]
+        suffix: String,
+        /// Hexadecimal prefix of the public key
+        #[arg(long = "hex", default_value = "", conflicts_with_all = ["prefix", "suffix"])]
+        hex: String,
+        /// Leading zero bits of the public key
+        #[arg(long = "bits", conflicts_with_all = ["prefix", "suffix", "hex"])]
+        bits: Option<u8>,
+        /// Keys per second of all cores together, e.g. from a previous run
+        #[arg(long = "rate")]
+        rate: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MnemonicCommand {
+    /// Check that a typed seed phrase is a valid BIP-39 mnemonic, and in
+    /// which language, without printing it or any key
+    Validate {
+        /// The words of the mnemonic, quoted or not
+        #[arg(required = true, num_args = 1..)]
+        words: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum KeystoreCommand {
+    /// List the keys of a keystore, without decrypting them
+    List {
+        /// Keystore file
+        path: PathBuf,
+    },
+    /// Decrypt a key of a keystore and print its nsec
+    Export {
+        /// Keystore file
+        path: PathBuf,
+        /// npub of the key, or its number in the list
+        entry: String,
+        #[arg(
+            long = "keystore-password",
+            env = "RANA_KEYSTORE_PASSWORD",
+            hide_env_values = true,
+            help = "Password the keystore secrets are encrypted with"
+        )]
+        password: String,
+    },
+}
+
 #[derive(Parser)]
 #[command(
     name = "Rana",
@@ -15,9 +234,13 @@ use regex::Regex;
 
 {all-args}{after-help}
 ",
-    version
+    version,
+    group(ArgGroup::new("sequential").args(["start_from", "resume_scalar"]))
 )]
 pub struct CLIArgs {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(
         short,
         long,
@@ -25,6 +248,33 @@ pub struct CLIArgs {
         help = "Enter the number of starting bits that should be 0."
     )]
     pub difficulty: u8,
+    #[arg(
+        long = "min-zero-bytes",
+        required = false,
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u8).range(0..=31),
+        conflicts_with_all = ["difficulty", "target_time"],
+        help = "Enter the number of starting bytes that should be 0, up to
+31. Same as a difficulty of 8 bits per byte."
+    )]
+    pub min_zero_bytes: u8,
+    #[arg(
+        long = "nip13-difficulty",
+        required = false,
+        conflicts_with_all = [
+            "difficulty",
+            "min_zero_bytes",
+            "target_time",
+            "vanity_prefix",
+            "vanity_npub_prefixes_raw_input",
+            "vanity_npub_suffixes_raw_input",
+            "npub_fuzzy",
+            "stdin_prefixes"
+        ],
+        help = "Same as --difficulty, in NIP-13 terms: the proof of work of
+the public key itself, not of the events it signs"
+    )]
+    pub nip13_difficulty: Option<u8>,
     #[arg(
         short,
         long = "vanity",
@@ -54,14 +304,47 @@ in npub format (Bech32 encoding). Specify multiple vanity
 targets as a comma-separated list."
     )]
     pub vanity_npub_suffixes_raw_input: String,
+    #[arg(
+        long = "npub-anchored-suffix",
+        required = false,
+        default_value_t = false,
+        requires = "vanity_npub_suffixes_raw_input",
+        help = "Match the --vanity-n-suffix against the end of the key data
+instead of the end of the npub, which is its checksum"
+    )]
+    pub npub_anchored_suffix: bool,
+    #[arg(
+        long = "brand",
+        required = false,
+        conflicts_with_all = [
+            "vanity_prefix",
+            "vanity_npub_prefixes_raw_input",
+            "npub_fuzzy",
+            "stdin_prefixes"
+        ],
+        help = "Mine for npub prefixes that read like this word. Letters
+bech32 lacks (b, i, o and 1) are replaced by lookalikes, other
+characters are dropped and the derived prefixes are listed"
+    )]
+    pub brand: Option<String>,
     #[arg(
         short = 'c',
         long = "cores",
         default_value_t = num_cpus::get(),
-        help = "Number of processor cores to use"
+        help = "Number of processor cores to use. More than the machine has
+are allowed, with a warning"
     )]
     pub num_cores: usize,
 
+    #[arg(
+        long = "cpu-count-report",
+        required = false,
+        default_value_t = false,
+        help = "Report the detected logical, physical and available cores,
+and how many rana mines with, at startup"
+    )]
+    pub cpu_count_report: bool,
+
     #[arg(
         short = 'r',
         long = "restore",
@@ -80,6 +363,49 @@ targets as a comma-separated list."
     )]
     pub word_count: usize,
 
+    #[arg(
+        long = "rng",
+        value_enum,
+        default_value_t = RngKind::Thread,
+        help = "Randomness the secret keys are drawn from: `thread` is the
+thread local generator, `os` asks the system for every key and
+`chacha` can be seeded with --seed. Each core gets its own"
+    )]
+    pub rng: RngKind,
+
+    #[arg(
+        long = "seed",
+        required = false,
+        help = "Seed of --rng chacha, to get the same keys on every run.
+Anyone who knows it can derive them, so only use it for tests"
+    )]
+    pub seed: Option<u64>,
+
+    #[arg(
+        long = "just-generate",
+        required = false,
+        default_value_t = false,
+        conflicts_with_all = [
+            "difficulty",
+            "min_zero_bytes",
+            "nip13_difficulty",
+            "vanity_prefix",
+            "vanity_npub_prefixes_raw_input",
+            "vanity_npub_suffixes_raw_input",
+            "brand",
+            "npub_fuzzy",
+            "stdin_prefixes",
+            "target_time",
+            "tiers",
+            "below",
+            "seed_from_mnemonic_file",
+            "pubkey_only"
+        ],
+        help = "Generate and print a single key without any target, then
+exit. With -g it is derived from a new mnemonic"
+    )]
+    pub just_generate: bool,
+
     #[arg(
         short = 'p',
         long = "passphrase",
@@ -89,6 +415,33 @@ targets as a comma-separated list."
     )]
     pub mnemonic_passphrase: String,
 
+    #[arg(
+        long = "seed-from-mnemonic-file",
+        required = false,
+        conflicts_with_all = [
+            "mnemonic",
+            "word_count",
+            "start_from",
+            "resume_scalar",
+            "stdin_prefixes",
+            "npub_fuzzy"
+        ],
+        help = "Instead of mining random keys, sweep the NIP-06 accounts of
+each mnemonic of this file, one per line, for the vanity npub
+prefixes and suffixes. Lines starting with # are skipped"
+    )]
+    pub seed_from_mnemonic_file: Option<PathBuf>,
+
+    #[arg(
+        long = "accounts",
+        required = false,
+        default_value_t = 100,
+        requires = "seed_from_mnemonic_file",
+        help = "Number of NIP-06 accounts swept for each mnemonic of
+--seed-from-mnemonic-file, from account 0"
+    )]
+    pub accounts: u32,
+
     #[arg(
         short,
         long = "qr",
@@ -97,74 +450,1288 @@ targets as a comma-separated list."
         help = "Print QR code of the private key"
     )]
     pub qr: bool,
-}
 
-pub fn check_args(
-    difficulty: u8,
-    vanity_prefix: &str,
-    vanity_npub_prefixes: &Vec<String>,
-    vanity_npub_suffixes: &Vec<String>,
-    num_cores: usize,
-) {
-    // Check the public key requirements
-    let mut requirements_count: u8 = 0;
-    if difficulty > 0 {
-        requirements_count += 1;
-    }
-    if !vanity_prefix.is_empty() {
-        requirements_count += 1;
-    }
-    if !vanity_npub_prefixes.is_empty() || !vanity_npub_suffixes.is_empty() {
-        requirements_count += 1;
-    }
+    #[arg(
+        long = "qr-compact",
+        required = false,
+        default_value_t = false,
+        requires = "qr",
+        help = "Draw the QR code with quadrant blocks, half as wide, for
+small terminals"
+    )]
+    pub qr_compact: bool,
 
-    if requirements_count > 1 {
-        panic!("You can cannot specify more than one requirement. You should choose between difficulty or any of the vanity formats.");
-    }
+    #[arg(
+        long = "qr-ascii",
+        required = false,
+        num_args = 0..=1,
+        default_missing_value = "true",
+        requires = "qr",
+        conflicts_with = "qr_compact",
+        help = "Draw the QR code with # and spaces, for terminals without
+block characters. The default on Windows, where --qr-ascii=false
+draws blocks instead"
+    )]
+    pub qr_ascii: Option<bool>,
 
-    if vanity_prefix.len() > 64 {
-        panic!("The vanity prefix cannot be longer than 64 characters.");
-    }
+    #[arg(
+        short = 'm',
+        long = "mode",
+        value_enum,
+        required = false,
+        help = "Force the matching mode instead of detecting it from the
+other arguments. Errors if the inputs for that mode are missing."
+    )]
+    pub mode: Option<Mode>,
 
-    if !vanity_prefix.is_empty() {
-        // check valid hexa characters
-        let hex_re = Regex::new(r"^([0-9a-f]*)$").unwrap();
-        if !hex_re.is_match(vanity_prefix) {
-            panic!("The vanity prefix can only contain hexadecimal characters.");
-        }
-    }
+    #[arg(
+        long = "match-any",
+        required = false,
+        default_value_t = false,
+        help = "When both npub prefixes and suffixes are given, accept a key
+matching either a prefix or a suffix instead of both"
+    )]
+    pub match_any: bool,
 
-    for vanity_npub_prefix in vanity_npub_prefixes {
-        if !vanity_npub_prefix.is_empty() {
-            let hex_re = Regex::new(r"^([02-9ac-hj-np-z]*)$").unwrap();
-            if !hex_re.is_match(vanity_npub_prefix.as_str()) {
-                panic!("The vanity npub prefix can only contain characters supported by Bech32: 023456789acdefghjklmnpqrstuvwxyz");
-            }
-        }
-        if vanity_npub_prefix.len() > 59 {
-            panic!("The vanity npub prefix cannot be longer than 59 characters.");
-        }
-    }
+    #[arg(
+        long = "hrp",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "npub",
+        help = "Bech32 entity the -n and -s targets are matched against,
+the public npub or the secret nsec. A comma-separated list
+matches under any of them, or all of them with --all-hrps"
+    )]
+    pub hrps: Vec<Hrp>,
 
-    for vanity_npub_suffix in vanity_npub_suffixes {
-        if !vanity_npub_suffix.is_empty() {
-            let hex_re = Regex::new(r"^([02-9ac-hj-np-z]*)$").unwrap();
-            if !hex_re.is_match(vanity_npub_suffix.as_str()) {
-                panic!("The vanity npub suffix can only contain characters supported by Bech32: 023456789acdefghjklmnpqrstuvwxyz");
-            }
-        }
-        if vanity_npub_suffix.len() > 59 {
-            panic!("The vanity npub suffix cannot be longer than 59 characters.");
-        }
-    }
+    #[arg(
+        long = "all-hrps",
+        required = false,
+        default_value_t = false,
+        help = "With several --hrp, only accept a key whose encodings
+under every one of them match"
+    )]
+    pub all_hrps: bool,
 
-    if num_cores == 0 {
-        panic!("There can be no proof of work if one does not do work (-c, --cores must be greater than 0)");
-    } else if num_cores > num_cpus::get() {
-        panic!(
-            "Your processor has {} cores; cannot set -c, --cores to {}",
-            num_cpus::get(),
-            num_cores
-        );
-    }
+    #[arg(
+        long = "npub-fuzzy",
+        required = false,
+        default_value = "",
+        conflicts_with_all = [
+            "difficulty",
+            "min_zero_bytes",
+            "vanity_prefix",
+            "vanity_npub_prefixes_raw_input",
+            "vanity_npub_suffixes_raw_input",
+            "mode",
+            "stdin_prefixes"
+        ],
+        help = "Accept npubs whose prefix is within --max-distance characters
+of this one, far faster than an exact prefix"
+    )]
+    pub npub_fuzzy: String,
+
+    #[arg(
+        long = "max-distance",
+        required = false,
+        default_value_t = 1,
+        requires = "npub_fuzzy",
+        help = "Number of characters of the --npub-fuzzy prefix that may differ"
+    )]
+    pub max_distance: usize,
+
+    #[arg(
+        long = "stdin-prefixes",
+        required = false,
+        default_value_t = false,
+        conflicts_with_all = ["difficulty", "vanity_prefix", "mode"],
+        help = "Read more npub prefixes from stdin, one per line, and add
+them to the search while mining"
+    )]
+    pub stdin_prefixes: bool,
+
+    #[arg(
+        long = "interactive",
+        required = false,
+        default_value_t = false,
+        conflicts_with = "stdin_prefixes",
+        help = "After each match, ask whether to keep mining, save the key to
+--keystore or quit. Disabled when stdin is not a terminal"
+    )]
+    pub interactive: bool,
+
+    #[arg(
+        long = "count-by-prefix",
+        required = false,
+        default_value_t = false,
+        conflicts_with_all = ["difficulty", "vanity_prefix", "npub_fuzzy"],
+        help = "Count the matches of each npub prefix and suffix, and show
+the counts with the progress lines and the final summary"
+    )]
+    pub count_by_prefix: bool,
+
+    #[arg(
+        long = "require-extra-zeros",
+        required = false,
+        default_value_t = 0,
+        conflicts_with_all = ["difficulty", "vanity_prefix"],
+        help = "Only accept vanity npubs whose public key also has at least
+this many leading zero bits, for keys both memorable and rare"
+    )]
+    pub require_extra_zeros: u8,
+
+    #[arg(
+        long = "avoid-confusables",
+        required = false,
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_CONFUSABLES,
+        value_parser = parse_confusables,
+        conflicts_with_all = ["difficulty", "vanity_prefix"],
+        help = "Reject npubs whose displayed prefix has any of these easily
+confused characters, other than the ones of the target itself.
+Defaults to q, g, u, v, s, 5, z, 2, l and 0"
+    )]
+    pub avoid_confusables: Option<String>,
+
+    #[arg(
+        long = "show-address-examples",
+        required = false,
+        default_value_t = false,
+        help = "Show how each found npub looks when clients shorten it, to
+judge whether the vanity part stays visible"
+    )]
+    pub show_address_examples: bool,
+
+    #[arg(
+        long = "debug-bytes",
+        alias = "print-pubkey-bytes",
+        required = false,
+        default_value_t = false,
+        help = "Show the 32 bytes of each found public key in hex, 8 to a
+row, with the leading zero bits counted on them"
+    )]
+    pub debug_bytes: bool,
+
+    #[arg(
+        long = "nprofile-relays",
+        required = false,
+        value_delimiter = ',',
+        value_parser = parse_relay_url,
+        help = "Also show each found key as an nprofile with these relay
+hints, a comma separated list of ws:// or wss:// URLs"
+    )]
+    pub nprofile_relays: Vec<String>,
+
+    #[arg(
+        long = "scientific",
+        required = false,
+        default_value_t = false,
+        conflicts_with = "decimal",
+        help = "Show the iteration count of the stats line as a power of ten"
+    )]
+    pub scientific: bool,
+
+    #[arg(
+        long = "no-stats",
+        required = false,
+        default_value_t = false,
+        help = "Leave out the iterations and rate line printed after every
+match, keeping the keys"
+    )]
+    pub no_stats: bool,
+
+    #[arg(
+        long = "decimal",
+        required = false,
+        default_value_t = false,
+        help = "Show the iteration count of the stats line in decimal with
+thousands separators (default)"
+    )]
+    pub decimal: bool,
+
+    #[arg(
+        long = "list-found",
+        required = false,
+        default_value_t = false,
+        help = "Print a table of every key found when mining stops"
+    )]
+    pub list_found: bool,
+
+    #[arg(
+        long = "show-secrets",
+        required = false,
+        default_value_t = false,
+        requires = "list_found",
+        help = "Add the nsec of each key to the --list-found table"
+    )]
+    pub show_secrets: bool,
+
+    #[arg(
+        long = "keystore",
+        required = false,
+        requires = "keystore_password",
+        conflicts_with = "pubkey_only",
+        help = "Save every found key to this JSON keystore, its secret
+encrypted with --keystore-password (NIP-49). Inspect it with
+`rana keystore list` and `rana keystore export`"
+    )]
+    pub keystore: Option<PathBuf>,
+
+    #[arg(
+        long = "keystore-password",
+        required = false,
+        env = "RANA_KEYSTORE_PASSWORD",
+        hide_env_values = true,
+        help = "Password to encrypt the --keystore secrets with"
+    )]
+    pub keystore_password: Option<String>,
+
+    #[arg(
+        long = "keystore-label",
+        required = false,
+        requires = "keystore",
+        help = "Label of the keys saved to the --keystore, instead of what
+they matched"
+    )]
+    pub keystore_label: Option<String>,
+
+    #[arg(
+        long = "tag",
+        required = false,
+        help = "Free-form label added to every key found by this run, in
+all output formats and the --output file"
+    )]
+    pub tag: Option<String>,
+
+    #[arg(
+        long = "max-rate",
+        required = false,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Cap the combined hashrate of all cores to this many keys per
+second, to mine in the background without pinning the CPU"
+    )]
+    pub max_rate: Option<u64>,
+
+    #[arg(
+        long = "target-rate",
+        required = false,
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["num_cores", "target_time", "no_benchmark"],
+        help = "Benchmark a core and mine with just enough cores to reach
+this many keys per second, saving power when it is enough"
+    )]
+    pub target_rate: Option<u64>,
+
+    #[arg(
+        long = "pubkey-only",
+        required = false,
+        default_value_t = false,
+        conflicts_with_all = ["word_count", "seed_from_mnemonic_file"],
+        help = "Drop the secret of every found key and only report its public
+key, e.g. to study npub distributions. The keys are unusable"
+    )]
+    pub pubkey_only: bool,
+
+    #[arg(
+        long = "benchmark-duration",
+        required = false,
+        default_value = "5",
+        value_parser = parse_duration,
+        help = "How long to benchmark a core for, in seconds or with a unit
+like 1m. Longer is more accurate on busy machines"
+    )]
+    pub benchmark_duration: Duration,
+
+    #[arg(
+        long = "batch",
+        alias = "worker-batch-size",
+        required = false,
+        default_value_t = 64,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Keys each core generates between updates of the shared
+counter and checks for a shutdown. Larger batches synchronize
+less, but stopping and --max-iterations can lag by a batch"
+    )]
+    pub batch: u64,
+
+    #[arg(
+        long = "shuffle-cores",
+        required = false,
+        default_value_t = false,
+        help = "Measure every core while all of them mine, then let faster
+cores count more keys locally before updating the shared
+counter. Helps on CPUs with fast and slow cores"
+    )]
+    pub shuffle_cores: bool,
+
+    #[arg(
+        long = "no-benchmark",
+        required = false,
+        default_value_t = false,
+        conflicts_with = "target_time",
+        help = "Skip benchmarking and start mining right away. The time
+to find a key is then unknown"
+    )]
+    pub no_benchmark: bool,
+
+    #[arg(
+        long = "benchmark-json",
+        required = false,
+        default_value_t = false,
+        conflicts_with = "no_benchmark",
+        help = "Also print the result of the startup benchmark as a JSON
+line with its cores, rate_hps and duration_secs"
+    )]
+    pub benchmark_json: bool,
+
+    #[arg(
+        long = "count-only",
+        required = false,
+        default_value_t = false,
+        conflicts_with_all = ["no_benchmark", "benchmark_json", "timeout", "find_lowest", "interactive"],
+        help = "Mine with the configured targets for --benchmark-duration,
+discard every match and print the rate reached. Measures the
+real cost of the matcher, unlike the startup benchmark"
+    )]
+    pub count_only: bool,
+
+    #[arg(
+        long = "warmup",
+        required = false,
+        default_value = "1",
+        value_parser = parse_duration,
+        help = "How long a core runs the hot loop unmeasured before it is
+benchmarked, so CPU frequency scaling settles first. 0 skips it"
+    )]
+    pub warmup: Duration,
+
+    #[arg(
+        long = "benchmark-compare",
+        required = false,
+        default_value_t = false,
+        help = "Benchmark each matching path (difficulty, hex, npub and
+mnemonic) for --benchmark-duration on one core, print how they
+compare and exit. Prints JSON lines with --format jsonl"
+    )]
+    pub benchmark_compare: bool,
+
+    #[arg(
+        long = "difficulty-histogram",
+        required = false,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Generate this many keys, print how many had each number of
+leading zero bits next to the expected geometric distribution
+and exit. Prints JSON lines with --format jsonl"
+    )]
+    pub difficulty_histogram: Option<u64>,
+
+    #[arg(
+        long = "first-char-distribution",
+        required = false,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Generate this many keys, print how often each character
+comes first after npub1, to see which prefixes can be found,
+and exit. Prints JSON lines with --format jsonl"
+    )]
+    pub first_char_distribution: Option<u64>,
+
+    #[arg(
+        long = "use-nostr-keys",
+        required = false,
+        default_value_t = false,
+        help = "Benchmark with nostr `Keys::generate`, the path the miner uses,
+instead of raw secp256k1 key pairs"
+    )]
+    pub use_nostr_keys: bool,
+
+    #[arg(
+        long = "timeout",
+        required = false,
+        value_parser = parse_duration,
+        help = "Stop mining after this long, in seconds or with a unit like 1m.
+Exits with code 3 when nothing was found"
+    )]
+    pub timeout: Option<Duration>,
+
+    #[arg(
+        long = "max-iterations",
+        required = false,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Stop mining after this many keys were generated. Exits with
+code 3 when nothing was found"
+    )]
+    pub max_iterations: Option<u64>,
+
+    #[arg(
+        long = "print-every",
+        alias = "print-interval-iterations",
+        required = false,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Print a progress line each time this many more keys were
+generated by all cores together"
+    )]
+    pub print_every: Option<u64>,
+
+    #[arg(
+        long = "preview",
+        required = false,
+        default_value_t = false,
+        help = "Print the id and content of the kind-0 profile event for the
+found key, filled in with --name, --about, --picture, --nip05
+and --lud16 (computed locally, nothing is published)"
+    )]
+    pub preview: bool,
+
+    #[arg(
+        long = "relay-publish-dry-run",
+        required = false,
+        default_value_t = false,
+        requires = "preview",
+        conflicts_with = "pubkey_only",
+        help = "Also sign the --preview profile event with the found key and
+print it as the JSON a relay would receive, without sending it"
+    )]
+    pub relay_publish_dry_run: bool,
+
+    #[arg(
+        long = "name",
+        required = false,
+        requires = "preview",
+        help = "Name of the --preview profile"
+    )]
+    pub profile_name: Option<String>,
+
+    #[arg(
+        long = "about",
+        required = false,
+        requires = "preview",
+        help = "About text of the --preview profile"
+    )]
+    pub profile_about: Option<String>,
+
+    #[arg(
+        long = "picture",
+        required = false,
+        requires = "preview",
+        value_parser = parse_url,
+        help = "Picture URL of the --preview profile"
+    )]
+    pub profile_picture: Option<Url>,
+
+    #[arg(
+        long = "nip05",
+        required = false,
+        requires = "preview",
+        value_parser = parse_internet_identifier,
+        help = "NIP-05 identifier (name@domain) of the --preview profile"
+    )]
+    pub profile_nip05: Option<String>,
+
+    #[arg(
+        long = "lud16",
+        required = false,
+        requires = "preview",
+        value_parser = parse_internet_identifier,
+        help = "Lightning address (name@domain) of the --preview profile"
+    )]
+    pub profile_lud16: Option<String>,
+
+    #[arg(
+        short = 'f',
+        long = "format",
+        value_enum,
+        default_value_t = OutputFormat::Plain,
+        help = "Output format of the found keys. `nak` prints only the hex
+secret key so it can be piped to `nak` or `nostr-tool`, `jsonl`
+prints one JSON object per match, `env` prints NOSTR_* shell
+variable assignments, `compact` prints one key=value line per
+match, `csv` prints a header and one row per match; status
+lines then go to stderr"
+    )]
+    pub format: OutputFormat,
+
+    #[arg(
+        long = "secret-format",
+        value_enum,
+        default_value_t = SecretFormat::Both,
+        help = "Encoding of the secret key in the reports of found keys"
+    )]
+    pub secret_format: SecretFormat,
+
+    #[arg(
+        long = "base64",
+        required = false,
+        default_value_t = false,
+        help = "Also output the raw 32 bytes of the public key, and of the
+hex secret key if shown, encoded as base64"
+    )]
+    pub base64: bool,
+
+    #[arg(
+        long = "redact-secret",
+        required = false,
+        default_value_t = false,
+        help = "Replace the secret key and mnemonic with ***redacted*** in the
+terminal output. The --output file still gets the real secret"
+    )]
+    pub redact_secret: bool,
+
+    #[arg(
+        short = 'o',
+        long = "output",
+        required = false,
+        default_value = "",
+        help = "Append every found key, including its secret, to this file"
+    )]
+    pub output: String,
+
+    #[arg(
+        long = "npub-log",
+        alias = "out-npub-file",
+        required = false,
+        help = "Also append the npub of every found key to this file, one per
+line and without any secret, e.g. for a contact list"
+    )]
+    pub npub_log: Option<PathBuf>,
+
+    #[arg(
+        long = "target-time",
+        required = false,
+        value_parser = parse_duration,
+        conflicts_with = "difficulty",
+        help = "Benchmark this machine and pick the highest difficulty
+expected to be found within this time, e.g. 90s, 30m, 1h or 2d"
+    )]
+    pub target_time: Option<Duration>,
+
+    #[arg(
+        long = "target-interval",
+        required = false,
+        value_parser = parse_duration,
+        conflicts_with_all = [
+            "min_zero_bytes",
+            "target_time",
+            "tiers",
+            "below",
+            "find_lowest",
+            "pattern_file",
+            "vanity_prefix",
+            "vanity_npub_prefixes_raw_input",
+            "vanity_npub_suffixes_raw_input",
+            "npub_fuzzy",
+            "stdin_prefixes",
+            "brand"
+        ],
+        help = "Adapt the difficulty so a key is found about this often, in
+seconds or with a unit like 1m: a bit harder after a quicker
+find, a bit easier after an interval without one. Starts from
+--difficulty"
+    )]
+    pub target_interval: Option<Duration>,
+
+    #[arg(
+        long = "tiers",
+        required = false,
+        value_parser = parse_tiers,
+        conflicts_with_all = [
+            "difficulty",
+            "min_zero_bytes",
+            "target_time",
+            "below",
+            "vanity_prefix",
+            "vanity_npub_prefixes_raw_input",
+            "vanity_npub_suffixes_raw_input",
+            "npub_fuzzy",
+            "stdin_prefixes"
+        ],
+        help = "Report every key reaching one of these difficulty tiers, with
+the label of the highest one, e.g. 16:bronze,20:silver,24:gold"
+    )]
+    pub tiers: Option<Tiers>,
+
+    #[arg(
+        long = "tiers-raise-bar",
+        required = false,
+        default_value_t = false,
+        requires = "tiers",
+        help = "Only report keys of the highest tier that have more leading
+zero bits than the best one so far"
+    )]
+    pub tiers_raise_bar: bool,
+
+    #[arg(
+        long = "open-ended",
+        required = false,
+        default_value_t = false,
+        conflicts_with = "fixed",
+        help = "The default difficulty search: after each find only keys with
+more leading zero bits than it are reported, so the keys get
+rarer and rarer"
+    )]
+    pub open_ended: bool,
+
+    #[arg(
+        long = "fixed",
+        alias = "disable-bar-raising",
+        required = false,
+        default_value_t = false,
+        conflicts_with_all = [
+            "tiers",
+            "below",
+            "find_lowest",
+            "pattern_file",
+            "target_interval",
+            "vanity_prefix",
+            "vanity_npub_prefixes_raw_input",
+            "vanity_npub_suffixes_raw_input",
+            "npub_fuzzy",
+            "stdin_prefixes",
+            "brand"
+        ],
+        help = "Report every key with at least the difficulty in leading zero
+bits, instead of raising the bar after each find"
+    )]
+    pub fixed: bool,
+
+    #[arg(
+        long = "report-limit",
+        required = false,
+        default_value_t = 0,
+        help = "Only print keys with at least this many leading zero bits.
+Smaller finds still raise the bar and are counted in the summary"
+    )]
+    pub report_limit: u8,
+
+    #[arg(
+        long = "dedup",
+        required = false,
+        default_value_t = false,
+        help = "Only report each public key once, even when it is derived
+again, as in sweeps or sequential runs. Keeps a bloom filter of
+the reported keys"
+    )]
+    pub dedup: bool,
+
+    #[arg(
+        long = "known-pubkeys",
+        alias = "pubkey-filter-file",
+        required = false,
+        help = "File of public keys already owned, in hex or as npubs, one
+per line. Matches with one of them are skipped"
+    )]
+    pub known_pubkeys: Option<PathBuf>,
+
+    #[arg(
+        long = "dedup-capacity",
+        required = false,
+        default_value_t = 1_000_000,
+        value_parser = clap::value_parser!(u64).range(1..),
+        requires = "dedup",
+        help = "Reported keys the --dedup filter is sized for. Past it, new
+keys are more and more often taken for reported ones"
+    )]
+    pub dedup_capacity: u64,
+
+    #[arg(
+        long = "below",
+        required = false,
+        value_parser = parse_threshold,
+        conflicts_with_all = [
+            "difficulty",
+            "min_zero_bytes",
+            "target_time",
+            "vanity_prefix",
+            "vanity_npub_prefixes_raw_input",
+            "vanity_npub_suffixes_raw_input",
+            "npub_fuzzy",
+            "stdin_prefixes"
+        ],
+        help = "Find public keys whose value is below this hex number. Shorter
+values are padded with zeros, so 0001 accepts keys starting 0000"
+    )]
+    pub below: Option<[u8; 32]>,
+
+    #[arg(
+        long = "find-lowest",
+        required = false,
+        default_value_t = false,
+        requires = "timeout",
+        conflicts_with_all = [
+            "difficulty",
+            "min_zero_bytes",
+            "nip13_difficulty",
+            "target_time",
+            "tiers",
+            "below",
+            "vanity_prefix",
+            "vanity_npub_prefixes_raw_input",
+            "vanity_npub_suffixes_raw_input",
+            "npub_fuzzy",
+            "stdin_prefixes",
+            "brand"
+        ],
+        help = "Mine until --timeout and only print the numerically smallest
+public key found, the one with the most leading zero bits"
+    )]
+    pub find_lowest: bool,
+
+    #[arg(
+        long = "pattern-file",
+        required = false,
+        conflicts_with_all = [
+            "difficulty",
+            "min_zero_bytes",
+            "nip13_difficulty",
+            "target_time",
+            "tiers",
+            "below",
+            "find_lowest",
+            "vanity_prefix",
+            "vanity_npub_prefixes_raw_input",
+            "vanity_npub_suffixes_raw_input",
+            "npub_fuzzy",
+            "stdin_prefixes",
+            "brand",
+            "mode",
+            "seed_from_mnemonic_file"
+        ],
+        help = "Mine for the hex and npub prefixes of this file at once, one
+per line as hex:dead or npub:cafe. Each match tells which one
+it matched"
+    )]
+    pub pattern_file: Option<PathBuf>,
+
+    #[arg(
+        long = "retry-on-weak-key",
+        required = false,
+        default_value_t = false,
+        help = "Check that every generated key is well formed (non-zero
+secret, public key on the curve) and generate a new one if not"
+    )]
+    pub retry_on_weak_key: bool,
+
+    #[arg(
+        long = "require-entropy",
+        alias = "secret-entropy-check",
+        required = false,
+        default_value_t = false,
+        help = "Refuse to report a match whose secret looks degenerate: a
+run of more than 4 equal bytes or fewer than 16 distinct bytes.
+A safety net against misused sequential searches"
+    )]
+    pub require_entropy: bool,
+
+    #[arg(
+        long = "backup-card",
+        required = false,
+        default_value = "",
+        help = "Write a printable PNG card with the npub and nsec QR codes of
+the found key to this path. Later keys of the run get numbered
+cards next to it: card-2.png, card-3.png, ..."
+    )]
+    pub backup_card: String,
+
+    #[arg(
+        long = "backup-card-public-only",
+        required = false,
+        default_value_t = false,
+        help = "Leave the nsec QR code out of the backup card"
+    )]
+    pub backup_card_public_only: bool,
+
+    #[arg(
+        long = "start-from",
+        required = false,
+        conflicts_with = "word_count",
+        value_parser = parse_secret_key,
+        help = "Instead of random keys, search sequential secret keys starting
+from this 64 character hex scalar. Deterministic and resumable,
+but far less private: only use it for vanity keys"
+    )]
+    pub start_from: Option<SecretKey>,
+
+    #[arg(
+        long = "resume-scalar",
+        required = false,
+        conflicts_with_all = ["start_from", "word_count"],
+        value_parser = parse_secret_key,
+        help = "Resume a sequential search from the scalar saved in its
+--checkpoint file, with the same --stride and number of cores"
+    )]
+    pub resume_scalar: Option<SecretKey>,
+
+    #[arg(
+        long = "checkpoint",
+        required = false,
+        requires = "sequential",
+        help = "Save the scalar a --start-from search can be resumed from to
+this file, every 10 seconds and when stopping"
+    )]
+    pub checkpoint: Option<PathBuf>,
+
+    #[arg(
+        long = "stride",
+        required = false,
+        default_value_t = 1,
+        requires = "sequential",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Step between the keys searched with --start-from. Give N
+machines the same stride N and start points start, start+1, ...
+to split the search into disjoint ranges"
+    )]
+    pub stride: u64,
+
+    #[cfg(feature = "metrics")]
+    #[arg(
+        long = "metrics-addr",
+        required = false,
+        default_value = "",
+        help = "Serve Prometheus metrics of the run on this address (e.g.
+127.0.0.1:9898) at /metrics"
+    )]
+    pub metrics_addr: String,
+
+    #[cfg(feature = "profile")]
+    #[arg(
+        long = "profile-cpu",
+        required = false,
+        default_value = "",
+        help = "Sample the mining threads and write a flamegraph SVG to this file"
+    )]
+    pub profile_cpu: String,
+
+    #[cfg(feature = "profile")]
+    #[arg(
+        long = "profile-duration",
+        required = false,
+        default_value = "30s",
+        value_parser = parse_duration,
+        help = "How long --profile-cpu samples for, e.g. 30s or 2m"
+    )]
+    pub profile_duration: Duration,
+
+    #[cfg(feature = "distributed")]
+    #[arg(
+        long = "coordinate",
+        required = false,
+        default_value = "",
+        help = "Run as coordinator on this address (e.g. 0.0.0.0:9735),
+handing out ranges of sequential keys to workers and printing
+the keys they find. Starts from --start-from or a random key"
+    )]
+    pub coordinate: String,
+
+    #[cfg(feature = "distributed")]
+    #[arg(
+        long = "range-size",
+        required = false,
+        default_value_t = 1_000_000,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Number of keys in each range handed out by the coordinator"
+    )]
+    pub range_size: u64,
+
+    #[cfg(feature = "distributed")]
+    #[arg(
+        long = "coordinator",
+        required = false,
+        default_value = "",
+        conflicts_with_all = ["start_from", "resume_scalar", "word_count", "coordinate"],
+        help = "Mine the ranges handed out by the coordinator at this address
+and report found keys back to it"
+    )]
+    pub coordinator: String,
+
+    #[cfg(feature = "distributed")]
+    #[arg(
+        long = "worker-id",
+        required = false,
+        default_value_t = 0,
+        help = "Id this machine reports to the coordinator"
+    )]
+    pub worker_id: u32,
+}
+
+/// Parse a duration given as a number followed by an optional unit
+/// (s, m, h or d). A bare number is taken as seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration '{value}'"))?;
+    let unit_seconds: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => return Err(format!("Invalid duration unit '{unit}', use s, m, h or d")),
+    };
+    let seconds = number
+        .checked_mul(unit_seconds)
+        .ok_or_else(|| format!("Invalid duration '{value}': duration too large"))?;
+    Ok(Duration::from_secs(seconds))
+}
+
+/// A labelled minimum number of leading zero bits
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tier {
+    pub bits: u8,
+    pub label: String,
+}
+
+/// Difficulty tiers, sorted from the lowest to the highest
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tiers(pub Vec<Tier>);
+
+impl Tiers {
+    pub fn lowest(&self) -> u8 {
+        self.0.first().map_or(0, |tier| tier.bits)
+    }
+
+    pub fn highest(&self) -> u8 {
+        self.0.last().map_or(0, |tier| tier.bits)
+    }
+
+    /// Label of the highest tier reached with the given leading zero bits
+    pub fn label_for(&self, bits: u8) -> Option<&str> {
+        self.0
+            .iter()
+            .rev()
+            .find(|tier| bits >= tier.bits)
+            .map(|tier| tier.label.as_str())
+    }
+}
+
+impl std::fmt::Display for Tiers {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let tiers: Vec<String> = self
+            .0
+            .iter()
+            .map(|tier| format!("{}:{}", tier.bits, tier.label))
+            .collect();
+        write!(f, "{}", tiers.join(","))
+    }
+}
+
+/// Parse difficulty tiers given as comma separated `bits:label` pairs
+pub fn parse_tiers(value: &str) -> Result<Tiers, String> {
+    let mut tiers = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (bits, label) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid tier '{entry}', use bits:label like 16:bronze"))?;
+        let bits: u8 = bits
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid number of bits in tier '{entry}'"))?;
+        let label = label.trim();
+        if bits == 0 || label.is_empty() {
+            return Err(format!("Tier '{entry}' needs at least 1 bit and a label"));
+        }
+        tiers.push(Tier {
+            bits,
+            label: label.to_string(),
+        });
+    }
+    if tiers.is_empty() {
+        return Err(String::from("At least one tier is required"));
+    }
+    tiers.sort_by_key(|tier| tier.bits);
+    if tiers.windows(2).any(|pair| pair[0].bits == pair[1].bits) {
+        return Err(String::from(
+            "Two tiers cannot have the same number of bits",
+        ));
+    }
+    Ok(Tiers(tiers))
+}
+
+/// Split a comma separated list of vanity targets, trimming whitespace around
+/// each entry and dropping empty ones. Also returns the raw entries that had
+/// to be trimmed, so they can be reported.
+pub fn split_targets(raw: &str) -> (Vec<String>, Vec<String>) {
+    let mut targets = Vec::new();
+    let mut trimmed = Vec::new();
+    for entry in raw.split(',') {
+        let target = entry.trim();
+        if target != entry {
+            trimmed.push(entry.to_string());
+        }
+        if !target.is_empty() {
+            targets.push(target.to_string());
+        }
+    }
+    (targets, trimmed)
+}
+
+/// Parse the scalar a sequential search starts from, as 64 hex characters
+pub fn parse_secret_key(value: &str) -> Result<SecretKey, String> {
+    value
+        .trim()
+        .parse::<SecretKey>()
+        .map_err(|_| format!("Invalid scalar '{value}', use a valid 64 character hex secret key"))
+}
+
+/// Parse an upper bound for the public key, as up to 64 hex characters.
+/// Shorter values are padded with zeros on the right, like a key prefix.
+pub fn parse_threshold(value: &str) -> Result<[u8; 32], String> {
+    let value = value.trim().trim_start_matches("0x");
+    if value.is_empty() || value.len() > 64 || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "Invalid threshold '{value}', use 1 to 64 hexadecimal characters"
+        ));
+    }
+    let padded = format!("{value:0<64}");
+    let mut threshold = [0_u8; 32];
+    for (i, byte) in threshold.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&padded[i * 2..i * 2 + 2], 16).map_err(|e| e.to_string())?;
+    }
+    if threshold.iter().all(|b| *b == 0) {
+        return Err(format!(
+            "Invalid threshold '{value}', no public key is below zero"
+        ));
+    }
+    Ok(threshold)
+}
+
+/// Parse an http(s) URL of a profile field
+pub fn parse_url(value: &str) -> Result<Url, String> {
+    let url = Url::parse(value).map_err(|e| format!("Invalid URL '{value}': {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("Invalid URL '{value}': use an http or https URL"));
+    }
+    Ok(url)
+}
+
+/// Parse the ws(s) URL of a relay hint, kept as given since NIP-19 encodes
+/// its exact text with a one byte length
+pub fn parse_relay_url(value: &str) -> Result<String, String> {
+    let url = Url::parse(value).map_err(|e| format!("Invalid relay URL '{value}': {e}"))?;
+    if !matches!(url.scheme(), "ws" | "wss") {
+        return Err(format!("Invalid relay URL '{value}': use a ws or wss URL"));
+    }
+    if value.len() > u8::MAX as usize {
+        return Err(format!(
+            "Invalid relay URL '{value}': relay hints can be at most 255 bytes"
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Parse a `name@domain` identifier, as used by NIP-05 and lightning addresses
+pub fn parse_internet_identifier(value: &str) -> Result<String, String> {
+    match value.split_once('@') {
+        Some((name, domain))
+            if !name.is_empty() && domain.contains('.') && !domain.contains('@') =>
+        {
+            Ok(value.to_string())
+        }
+        _ => Err(format!(
+            "Invalid identifier '{value}', use the name@domain form"
+        )),
+    }
+}
+
+impl CLIArgs {
+    /// Leading zero bits asked for with --difficulty, --min-zero-bytes or
+    /// --nip13-difficulty
+    pub fn difficulty_bits(&self) -> u8 {
+        if let Some(bits) = self.nip13_difficulty {
+            bits
+        } else if self.min_zero_bytes > 0 {
+            // whole zero bytes are a shortcut for a difficulty in bits, and
+            // the 31 allowed keep it within a u8
+            self.min_zero_bytes * 8
+        } else {
+            self.difficulty
+        }
+    }
+
+    /// Difficulty bar asked for with --fixed or --open-ended, open-ended when
+    /// neither is given
+    pub fn difficulty_bar(&self) -> DifficultyBar {
+        match (self.fixed, self.open_ended) {
+            (true, false) => DifficultyBar::Fixed,
+            // clap rejects both at once
+            _ => DifficultyBar::OpenEnded,
+        }
+    }
+
+    /// QR style asked for, ASCII by default on Windows whose terminals may
+    /// not draw the block characters
+    pub fn qr_style(&self) -> QrStyle {
+        match self.qr_ascii {
+            Some(true) => QrStyle::Ascii,
+            _ if self.qr_compact => QrStyle::Compact,
+            None if cfg!(windows) => QrStyle::Ascii,
+            _ => QrStyle::Blocks,
+        }
+    }
+
+    /// Kind-0 metadata of the profile given with the `--preview` flags
+    pub fn profile_metadata(&self) -> Metadata {
+        let mut metadata = Metadata::new();
+        if let Some(name) = &self.profile_name {
+            metadata = metadata.name(name);
+        }
+        if let Some(about) = &self.profile_about {
+            metadata = metadata.about(about);
+        }
+        if let Some(picture) = &self.profile_picture {
+            metadata = metadata.picture(picture.clone());
+        }
+        if let Some(nip05) = &self.profile_nip05 {
+            metadata = metadata.nip05(nip05);
+        }
+        if let Some(lud16) = &self.profile_lud16 {
+            metadata = metadata.lud16(lud16);
+        }
+        metadata
+    }
+}
+
+/// Bech32 characters that are easily mistaken for another one at a glance
+pub const DEFAULT_CONFUSABLES: &str = "qguvs5z2l0";
+
+/// Parse an `--avoid-confusables` set, which may only hold bech32 characters
+pub fn parse_confusables(value: &str) -> Result<String, String> {
+    let re = Regex::new(r"^[02-9ac-hj-np-z]+$").unwrap();
+    if !re.is_match(value) {
+        return Err(format!(
+            "Invalid confusable set '{value}', use one or more bech32 characters"
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Characters of an npub that encode the public key: 52 groups of 5 bits for
+/// its 256 bits, the last one padded with 4 zero bits
+const NPUB_KEY_CHARS: usize = 52;
+
+/// The last key character of an npub prefix when it sets any of the 4 zero
+/// padding bits, which no public key can encode. It is the only character
+/// ruled out, the first one can be any of the 32.
+pub fn unpadded_last_npub_key_char(prefix: &str) -> Option<char> {
+    let c = prefix.chars().nth(NPUB_KEY_CHARS - 1)?;
+    let value = crate::utils::BECH32_CHARSET.find(c)?;
+    (value & 0b1111 != 0).then_some(c)
+}
+
+/// Check the targets and the number of cores, with the reason when they are
+/// invalid. Returns a warning when there are more cores than the machine has.
+pub fn check_args(
+    difficulty: u8,
+    vanity_prefix: &str,
+    vanity_npub_prefixes: &Vec<String>,
+    vanity_npub_suffixes: &Vec<String>,
+    num_cores: usize,
+) -> Result<Option<String>, String> {
+    // Check the public key requirements
+    let mut requirements_count: u8 = 0;
+    if difficulty > 0 {
+        requirements_count += 1;
+    }
+    if !vanity_prefix.is_empty() {
+        requirements_count += 1;
+    }
+    if !vanity_npub_prefixes.is_empty() || !vanity_npub_suffixes.is_empty() {
+        requirements_count += 1;
+    }
+
+    if requirements_count > 1 {
+        return Err("You can cannot specify more than one requirement. You should choose between difficulty or any of the vanity formats.".to_string());
+    }
+
+    if vanity_prefix.len() > 64 {
+        return Err("The vanity prefix cannot be longer than 64 characters.".to_string());
+    }
+
+    if !vanity_prefix.is_empty() {
+        // check valid hexa characters
+        let hex_re = Regex::new(r"^([0-9a-f]*)$").unwrap();
+        if !hex_re.is_match(vanity_prefix) {
+            return Err("The vanity prefix can only contain hexadecimal characters.".to_string());
+        }
+    }
+
+    let bech32_re = Regex::new(r"^([02-9ac-hj-np-z]*)$").unwrap();
+
+    for vanity_npub_prefix in vanity_npub_prefixes {
+        if !vanity_npub_prefix.is_empty() && !bech32_re.is_match(vanity_npub_prefix.as_str()) {
+            return Err("The vanity npub prefix can only contain characters supported by Bech32: 023456789acdefghjklmnpqrstuvwxyz".to_string());
+        }
+        if vanity_npub_prefix.len() > 59 {
+            return Err("The vanity npub prefix cannot be longer than 59 characters.".to_string());
+        }
+        if let Some(c) = unpadded_last_npub_key_char(vanity_npub_prefix) {
+            return Err(format!("The vanity npub prefix '{vanity_npub_prefix}' can never be found: no public key has '{c}' as character {NPUB_KEY_CHARS} of its npub."));
+        }
+    }
+
+    for vanity_npub_suffix in vanity_npub_suffixes {
+        if !vanity_npub_suffix.is_empty() && !bech32_re.is_match(vanity_npub_suffix.as_str()) {
+            return Err("The vanity npub suffix can only contain characters supported by Bech32: 023456789acdefghjklmnpqrstuvwxyz".to_string());
+        }
+        if vanity_npub_suffix.len() > 59 {
+            return Err("The vanity npub suffix cannot be longer than 59 characters.".to_string());
+        }
+    }
+
+    if num_cores == 0 {
+        return Err("There can be no proof of work if one does not do work (-c, --cores must be greater than 0)".to_string());
+    }
+    Ok(oversubscription_warning(num_cores, num_cpus::get()))
+}
+
+/// Warning for more mining threads than cores. The threads then take turns
+/// on the cores, which usually lowers the total rate, but it is allowed.
+pub fn oversubscription_warning(num_cores: usize, available: usize) -> Option<String> {
+    (num_cores > available).then(|| {
+        let cores = if available == 1 { "core" } else { "cores" };
+        format!(
+            "WARNING: mining on {num_cores} threads, but this machine has {available} {cores}. The threads will take turns, which usually lowers the hashrate"
+        )
+    })
+}
+
+/// Pick the matching mode, either the one forced with `--mode` or the one
+/// implied by which requirements were provided
+pub fn resolve_mode(
+    mode: Option<Mode>,
+    vanity_prefix: &str,
+    vanity_npub_prefixes: &[String],
+    vanity_npub_suffixes: &[String],
+) -> Result<Mode, String> {
+    let has_hex = !vanity_prefix.is_empty();
+    let has_npub = !vanity_npub_prefixes.is_empty() || !vanity_npub_suffixes.is_empty();
+
+    Ok(match mode {
+        None if has_hex => Mode::Hex,
+        None if has_npub => Mode::Npub,
+        None => Mode::Difficulty,
+        Some(Mode::Difficulty) => {
+            if has_hex || has_npub {
+                return Err(
+                    "--mode difficulty cannot be combined with vanity targets (-v, -n, -s)."
+                        .to_string(),
+                );
+            }
+            Mode::Difficulty
+        }
+        Some(Mode::Hex) => {
+            if !has_hex {
+                return Err("--mode hex requires a vanity prefix (-v, --vanity).".to_string());
+            }
+            Mode::Hex
+        }
+        Some(Mode::Npub) => {
+            if !has_npub {
+                return Err("--mode npub requires at least one npub prefix or suffix (-n, --vanity-n-prefix / -s, --vanity-n-suffix).".to_string());
+            }
+            Mode::Npub
+        }
+    })
 }
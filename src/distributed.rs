@@ -0,0 +1,191 @@
+//! Pool several machines against one target. A coordinator hands out disjoint
+//! ranges of sequential secret keys and collects the keys found by workers,
+//! over a line based TCP protocol:
+//!
+//! - `RANGE <worker id>` is answered with `RANGE <start hex> <count>`
+//! - `FOUND <worker id> <secret hex>` is answered with `OK`
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use nostr::prelude::*;
+
+use crate::scalar::ScalarWalk;
+
+/// Callback run by the coordinator for every key reported by a worker
+pub type FoundCallback = Arc<dyn Fn(u32, &SecretKey) + Send + Sync>;
+
+struct State {
+    start: SecretKey,
+    range_size: u64,
+    next_offset: u64,
+    found: Vec<(u32, SecretKey)>,
+}
+
+/// Hands out ranges of `range_size` keys from `start` and collects found keys
+pub struct Coordinator {
+    listener: TcpListener,
+    state: Arc<Mutex<State>>,
+}
+
+impl Coordinator {
+    pub fn bind(addr: &str, start: SecretKey, range_size: u64) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            state: Arc::new(Mutex::new(State {
+                start,
+                range_size,
+                next_offset: 0,
+                found: Vec::new(),
+            })),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Keys reported so far, with the id of the worker that found them
+    pub fn found(&self) -> Vec<(u32, SecretKey)> {
+        self.state.lock().unwrap().found.clone()
+    }
+
+    /// Accept workers forever, serving each connection on its own thread
+    pub fn serve(&self, on_found: FoundCallback) -> Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let state = self.state.clone();
+            let on_found = on_found.clone();
+            thread::spawn(move || {
+                // a misbehaving worker only loses its own connection
+                let _ = handle_connection(stream, &state, &on_found);
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    state: &Mutex<State>,
+    on_found: &FoundCallback,
+) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["RANGE", _worker_id] => {
+                let (start, count) = {
+                    let mut state = state.lock().unwrap();
+                    let offset = state.next_offset;
+                    state.next_offset = offset.saturating_add(state.range_size);
+                    let (start, _) = ScalarWalk::new(state.start, offset, 1).next().unwrap();
+                    (start, state.range_size)
+                };
+                writeln!(writer, "RANGE {} {count}", start.display_secret())?;
+            }
+            ["FOUND", worker_id, secret] => {
+                let worker_id: u32 = worker_id.parse()?;
+                let secret_key = SecretKey::from_str(secret)?;
+                state.lock().unwrap().found.push((worker_id, secret_key));
+                on_found(worker_id, &secret_key);
+                writeln!(writer, "OK")?;
+            }
+            _ => writeln!(writer, "ERROR unknown command")?,
+        }
+    }
+    Ok(())
+}
+
+/// Connection of a worker to its coordinator
+pub struct WorkerClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    worker_id: u32,
+}
+
+impl WorkerClient {
+    pub fn connect(addr: &str, worker_id: u32) -> Result<Self> {
+        let writer = TcpStream::connect(addr)?;
+        Ok(Self {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+            worker_id,
+        })
+    }
+
+    fn request(&mut self, line: &str) -> Result<String> {
+        writeln!(self.writer, "{line}")?;
+        let mut response = String::new();
+        if self.reader.read_line(&mut response)? == 0 {
+            return Err("The coordinator closed the connection".into());
+        }
+        Ok(response.trim_end().to_string())
+    }
+
+    /// Ask for the next range to search, as its first key and key count
+    pub fn request_range(&mut self) -> Result<(SecretKey, u64)> {
+        let response = self.request(&format!("RANGE {}", self.worker_id))?;
+        match response
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .as_slice()
+        {
+            ["RANGE", start, count] => Ok((SecretKey::from_str(start)?, count.parse()?)),
+            _ => Err(format!("Unexpected coordinator response: {response}").into()),
+        }
+    }
+
+    /// Send a found key to the coordinator
+    pub fn report_found(&mut self, secret_key: &SecretKey) -> Result<()> {
+        let response = self.request(&format!(
+            "FOUND {} {}",
+            self.worker_id,
+            secret_key.display_secret()
+        ))?;
+        if response != "OK" {
+            return Err(format!("Unexpected coordinator response: {response}").into());
+        }
+        Ok(())
+    }
+}
+
+/// Walk over the ranges handed out by a coordinator, asking for a new one
+/// whenever the current range is exhausted. Offsets are relative to the start
+/// of the current range.
+pub struct DistributedWalk {
+    client: WorkerClient,
+    walk: Option<ScalarWalk>,
+    remaining: u64,
+}
+
+impl DistributedWalk {
+    pub fn connect(addr: &str, worker_id: u32) -> Result<Self> {
+        Ok(Self {
+            client: WorkerClient::connect(addr, worker_id)?,
+            walk: None,
+            remaining: 0,
+        })
+    }
+}
+
+impl Iterator for DistributedWalk {
+    type Item = (SecretKey, u128);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            let (start, count) = self.client.request_range().ok()?;
+            if count == 0 {
+                return None;
+            }
+            self.walk = Some(ScalarWalk::new(start, 0, 1));
+            self.remaining = count;
+        }
+        self.remaining -= 1;
+        self.walk.as_mut()?.next()
+    }
+}
@@ -0,0 +1,226 @@
+//! Keep found keys in a JSON keystore, each secret encrypted with a password
+//! as a NIP-49 `ncryptsec`:
+//!
+//! `ncryptsec` is the bech32 encoding of version (`0x02`), log2 of the scrypt
+//! rounds, a 16 byte salt, a 24 byte nonce, a key security byte and the
+//! XChaCha20-Poly1305 encrypted secret key, authenticated with the key
+//! security byte as associated data.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use bech32::{FromBase32, ToBase32, Variant};
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+use nostr::prelude::*;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::cli::KeystoreCommand;
+
+/// Log2 of the scrypt rounds used for the keystore, as recommended by NIP-49
+pub const NCRYPTSEC_LOG_N: u8 = 16;
+
+const NCRYPTSEC_HRP: &str = "ncryptsec";
+const NCRYPTSEC_VERSION: u8 = 0x02;
+/// The client does not track whether the key was ever handled insecurely
+const KEY_SECURITY_UNKNOWN: u8 = 0x02;
+
+fn symmetric_key(password: &str, salt: &[u8], log_n: u8) -> Result<[u8; 32]> {
+    let password: String = password.nfkc().collect();
+    let params = scrypt::Params::new(log_n, 8, 1, 32).map_err(|e| e.to_string())?;
+    let mut key = [0_u8; 32];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key).map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+/// Encrypt a secret key with a password into a NIP-49 `ncryptsec`
+pub fn encrypt_secret_key(secret_key: &SecretKey, password: &str, log_n: u8) -> Result<String> {
+    let mut rng = rand::thread_rng();
+    let salt: [u8; 16] = rand::Rng::gen(&mut rng);
+    let nonce: [u8; 24] = rand::Rng::gen(&mut rng);
+
+    let cipher = XChaCha20Poly1305::new(&symmetric_key(password, &salt, log_n)?.into());
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &secret_key.secret_bytes(),
+                aad: &[KEY_SECURITY_UNKNOWN],
+            },
+        )
+        .map_err(|_| "Could not encrypt the secret key")?;
+
+    let mut payload = vec![NCRYPTSEC_VERSION, log_n];
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&nonce);
+    payload.push(KEY_SECURITY_UNKNOWN);
+    payload.extend_from_slice(&ciphertext);
+    Ok(bech32::encode(
+        NCRYPTSEC_HRP,
+        payload.to_base32(),
+        Variant::Bech32,
+    )?)
+}
+
+/// Decrypt a NIP-49 `ncryptsec` with its password
+pub fn decrypt_secret_key(ncryptsec: &str, password: &str) -> Result<SecretKey> {
+    let (hrp, data, _) = bech32::decode(ncryptsec)?;
+    let payload = Vec::<u8>::from_base32(&data)?;
+    if hrp != NCRYPTSEC_HRP || payload.len() != 91 || payload[0] != NCRYPTSEC_VERSION {
+        return Err("Not a version 2 ncryptsec".into());
+    }
+    let log_n = payload[1];
+    let (salt, nonce) = (&payload[2..18], &payload[18..42]);
+    let key_security = payload[42];
+
+    let cipher = XChaCha20Poly1305::new(&symmetric_key(password, salt, log_n)?.into());
+    let secret = cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: &payload[43..],
+                aad: &[key_security],
+            },
+        )
+        .map_err(|_| "Wrong password or corrupted ncryptsec")?;
+    Ok(SecretKey::from_slice(&secret)?)
+}
+
+/// A key of the keystore, with its secret encrypted
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeystoreEntry {
+    pub npub: String,
+    pub label: String,
+    /// Unix timestamp of when the key was added
+    pub created_at: u64,
+    pub ncryptsec: String,
+}
+
+/// Keys stored in a keystore file
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Keystore {
+    pub entries: Vec<KeystoreEntry>,
+}
+
+impl Keystore {
+    /// Read a keystore file. A missing file is an empty keystore.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Write the keystore to a temporary file next to `path` and rename it
+    /// over the old one, so a crash mid-write never leaves a truncated file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Encrypt the secret of `keys` and add it as a new entry
+    pub fn add(&mut self, keys: &Keys, label: &str, password: &str, log_n: u8) -> Result<()> {
+        self.entries.push(KeystoreEntry {
+            npub: keys.public_key().to_bech32()?,
+            label: label.to_string(),
+            created_at: Timestamp::now().as_u64(),
+            ncryptsec: encrypt_secret_key(&keys.secret_key()?, password, log_n)?,
+        });
+        Ok(())
+    }
+
+    /// Find an entry by its npub, or by its position in the listing
+    pub fn find(&self, entry: &str) -> Option<&KeystoreEntry> {
+        match entry.parse::<usize>() {
+            Ok(index) => self.entries.get(index.checked_sub(1)?),
+            Err(_) => self.entries.iter().find(|e| e.npub == entry),
+        }
+    }
+
+    /// One line per entry: its position, npub, creation time and label
+    pub fn format_list(&self) -> String {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                format!(
+                    "{:>3}  {}  {:>10}  {}",
+                    i + 1,
+                    e.npub,
+                    e.created_at,
+                    e.label
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Saves the keys found while mining to a keystore file
+pub struct KeystoreWriter {
+    path: PathBuf,
+    password: String,
+    label: Option<String>,
+    keystore: Mutex<Keystore>,
+}
+
+impl KeystoreWriter {
+    /// Load the keystore at `path`, so a corrupt file is reported before any
+    /// mining. Keys are labelled with `label` if given.
+    pub fn open(path: &Path, password: &str, label: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            password: password.to_string(),
+            label: label.map(str::to_string),
+            keystore: Mutex::new(Keystore::load(path)?),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add a key, labelled with what it matched unless a label was given,
+    /// and write the keystore back
+    pub fn save_key(&self, keys: &Keys, matched: &str) -> Result<()> {
+        let label = self.label.as_deref().unwrap_or(matched);
+        let mut keystore = self.keystore.lock().unwrap();
+        keystore.add(keys, label, &self.password, NCRYPTSEC_LOG_N)?;
+        keystore.save(&self.path)
+    }
+}
+
+/// Run a `rana keystore` subcommand
+pub fn handle_keystore(action: &KeystoreCommand) -> Result<()> {
+    match action {
+        KeystoreCommand::List { path } => {
+            let keystore = Keystore::load(path)?;
+            if keystore.entries.is_empty() {
+                println!("{} holds no keys", path.display());
+            } else {
+                println!("{}", keystore.format_list());
+            }
+        }
+        KeystoreCommand::Export {
+            path,
+            entry,
+            password,
+        } => {
+            let keystore = Keystore::load(path)?;
+            let found = keystore
+                .find(entry)
+                .ok_or_else(|| format!("No key '{entry}' in {}", path.display()))?;
+            let secret_key = decrypt_secret_key(&found.ncryptsec, password)?;
+            println!("{}", secret_key.to_bech32()?);
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,18 @@
+pub mod bloom;
+pub mod card;
 pub mod cli;
+#[cfg(feature = "distributed")]
+pub mod distributed;
+pub mod keystore;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod mnemonic;
-pub mod tests;
+pub mod patterns;
+#[cfg(feature = "profile")]
+pub mod profile;
+pub mod scalar;
+pub mod schema;
 pub mod utils;
+
+#[cfg(test)]
+mod tests;
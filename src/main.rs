@@ -1,242 +1,1785 @@
-use std::cmp::max;
-use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
-use std::sync::Arc;
+use std::cmp::min;
+use std::collections::HashSet;
+use std::io::IsTerminal;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
 use bip39::Mnemonic;
 use clap::Parser;
 use nostr::prelude::*;
+use rana::bloom::{BloomFilter, DEDUP_FALSE_POSITIVE_RATE};
+use rana::card::{backup_card_path, write_backup_card};
 use rana::cli::*;
-use rana::mnemonic::handle_mnemonic;
-use rana::utils::{benchmark_cores, get_leading_zero_bits, print_keys, print_qr};
+#[cfg(feature = "distributed")]
+use rana::distributed::*;
+use rana::keystore::*;
+use rana::mnemonic::{
+    handle_mnemonic, nip06_path, read_mnemonics, sweep_accounts, validate_mnemonic,
+};
+use rana::patterns::{read_patterns, Patterns};
+use rana::scalar::*;
+use rana::schema::key_match_schema;
+use rana::status;
+use rana::utils::*;
 
 const DIFFICULTY_DEFAULT: u8 = 10;
 
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
     // Parse CLI arguments
     let parsed_args = CLIArgs::parse();
 
     // Handle mnemonic part if arguments is set
     if !parsed_args.mnemonic.is_empty() {
-        handle_mnemonic(&parsed_args);
+        return Ok(match handle_mnemonic(&parsed_args) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{e}");
+                Outcome::InvalidArguments.into()
+            }
+        });
     }
 
-    let mut difficulty: u8 = parsed_args.difficulty;
-    let vanity_prefix: String = parsed_args.vanity_prefix;
-    let mut vanity_npub_prefixes: Vec<String> = Vec::new();
-    let mut vanity_npub_suffixes: Vec<String> = Vec::new();
-    let num_cores: usize = parsed_args.num_cores;
-    let qr: bool = parsed_args.qr;
+    if let Some(Command::Keystore { action }) = &parsed_args.command {
+        handle_keystore(action)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(Command::Verify { nsec, npub }) = &parsed_args.command {
+        return Ok(match verify_key_pair(nsec, npub) {
+            Ok(true) => {
+                println!("PASS: the nsec is the secret key of {}", npub.trim());
+                Outcome::Found.into()
+            }
+            Ok(false) => {
+                let derived = Keys::from_sk_str(nsec.trim())?.public_key().to_bech32()?;
+                println!(
+                    "FAIL: the nsec is the secret key of {derived}, not {}",
+                    npub.trim()
+                );
+                Outcome::NoMatch.into()
+            }
+            Err(e) => {
+                eprintln!("FAIL: {e}");
+                Outcome::InvalidArguments.into()
+            }
+        });
+    }
+
+    if let Some(Command::Mnemonic {
+        action: MnemonicCommand::Validate { words },
+    }) = &parsed_args.command
+    {
+        return Ok(match validate_mnemonic(&words.join(" ")) {
+            Ok((word_count, language)) => {
+                println!("PASS: a valid BIP-39 mnemonic of {word_count} words in {language}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                println!("FAIL: {e}");
+                Outcome::NoMatch.into()
+            }
+        });
+    }
+
+    if parsed_args.seed.is_some() && parsed_args.rng != RngKind::Chacha {
+        eprintln!("--seed can only be used with --rng chacha.");
+        return Ok(Outcome::InvalidArguments.into());
+    }
+
+    if let Some(Command::Schema) = &parsed_args.command {
+        println!("{}", serde_json::to_string_pretty(&key_match_schema())?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(Command::Estimate {
+        prefix,
+        suffix,
+        hex,
+        bits,
+        rate,
+    }) = &parsed_args.command
+    {
+        let to_targets = |target: &str| (!target.is_empty()).then(|| target.to_string());
+        let prefixes: Vec<String> = to_targets(prefix).into_iter().collect();
+        let suffixes: Vec<String> = to_targets(suffix).into_iter().collect();
+        if let Err(e) = check_args(0, hex, &prefixes, &suffixes, 1) {
+            eprintln!("{e}");
+            return Ok(Outcome::InvalidArguments.into());
+        }
+        let pow_difficulty = match bits {
+            Some(bits) => *bits,
+            None if !hex.is_empty() => min(hex.len() * 4, u8::MAX as usize) as u8,
+            None => npub_pow_difficulty(&prefixes, &suffixes, false),
+        };
+        println!("{}", format_estimate(pow_difficulty, *rate));
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if parsed_args.benchmark_compare {
+        let format = parsed_args.format;
+        status!(
+            format,
+            "Benchmarking each matching path on a single core for {} seconds...",
+            parsed_args.benchmark_duration.as_secs_f64()
+        );
+        let rates = benchmark_paths(parsed_args.benchmark_duration);
+        if format == OutputFormat::Jsonl {
+            for rate in &rates {
+                println!("{}", serde_json::to_string(rate)?);
+            }
+        } else {
+            println!("{}", format_path_rates(&rates));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(iterations) = parsed_args.difficulty_histogram {
+        let format = parsed_args.format;
+        status!(
+            format,
+            "Counting the leading zero bits of {} keys on {} cores...",
+            group_thousands(iterations),
+            parsed_args.num_cores
+        );
+        let bins = histogram_bins(&mine_difficulty_histogram(
+            iterations,
+            parsed_args.num_cores,
+        ));
+        if format == OutputFormat::Jsonl {
+            for bin in &bins {
+                println!("{}", serde_json::to_string(bin)?);
+            }
+        } else {
+            println!("{}", format_histogram(&bins));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(iterations) = parsed_args.first_char_distribution {
+        let format = parsed_args.format;
+        status!(
+            format,
+            "Counting the first npub characters of {} keys on {} cores...",
+            group_thousands(iterations),
+            parsed_args.num_cores
+        );
+        let bins = first_char_bins(&mine_first_char_distribution(
+            iterations,
+            parsed_args.num_cores,
+        ));
+        if format == OutputFormat::Jsonl {
+            for bin in &bins {
+                println!("{}", serde_json::to_string(bin)?);
+            }
+        } else {
+            println!("{}", format_first_char_distribution(&bins));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if parsed_args.just_generate {
+        return generate_one(&parsed_args).map(|outcome| outcome.into());
+    }
 
-    for vanity_npub_pre in parsed_args.vanity_npub_prefixes_raw_input.split(',') {
-        if !vanity_npub_pre.is_empty() {
-            vanity_npub_prefixes.push(vanity_npub_pre.to_string())
+    #[cfg(feature = "distributed")]
+    if !parsed_args.coordinate.is_empty() {
+        return coordinate(&parsed_args).map(|_| ExitCode::SUCCESS);
+    }
+
+    let min_zero_bytes: usize = parsed_args.min_zero_bytes as usize;
+    let mut difficulty: u8 = parsed_args.difficulty_bits();
+    let profile_metadata = Arc::new(parsed_args.profile_metadata());
+    let vanity_prefix: String = parsed_args.vanity_prefix.clone();
+    let mut num_cores: usize = parsed_args.num_cores;
+    let qr: bool = parsed_args.qr;
+    let qr_style: QrStyle = parsed_args.qr_style();
+    let match_any: bool = parsed_args.match_any;
+    let npub_matches = npub_matcher(parsed_args.npub_anchored_suffix);
+    let mut hrps: Vec<Hrp> = Vec::new();
+    for hrp in &parsed_args.hrps {
+        if !hrps.contains(hrp) {
+            hrps.push(*hrp);
         }
     }
-    for vanity_npub_post in parsed_args.vanity_npub_suffixes_raw_input.split(',') {
-        if !vanity_npub_post.is_empty() {
-            vanity_npub_suffixes.push(vanity_npub_post.to_string())
+    let hrps = Arc::new(hrps);
+    let all_hrps: bool = parsed_args.all_hrps;
+    let scientific: bool = parsed_args.scientific;
+    let no_stats: bool = parsed_args.no_stats;
+    let hrp_names: String = hrps
+        .iter()
+        .map(|hrp| hrp.as_str())
+        .collect::<Vec<_>>()
+        .join("/");
+    let hrp_name: &str = &hrp_names;
+    let preview: bool = parsed_args.preview;
+    let relay_publish_dry_run: bool = parsed_args.relay_publish_dry_run;
+    let format: OutputFormat = parsed_args.format;
+    let redact_secret: bool = parsed_args.redact_secret;
+    let output = Arc::new(parsed_args.output.clone());
+    let npub_log = Arc::new(parsed_args.npub_log.clone());
+    let retry_on_weak_key: bool = parsed_args.retry_on_weak_key;
+    let backup_card = Arc::new(parsed_args.backup_card.clone());
+    let pubkey_only: bool = parsed_args.pubkey_only;
+    let max_rate: Option<u64> = parsed_args.max_rate;
+    let below: Option<[u8; 32]> = parsed_args.below;
+    let find_lowest: bool = parsed_args.find_lowest;
+    let count_only: bool = parsed_args.count_only;
+    let difficulty_bar: DifficultyBar = parsed_args.difficulty_bar();
+    let adaptive: Option<Arc<AdaptiveDifficulty>> = parsed_args
+        .target_interval
+        .map(|interval| Arc::new(AdaptiveDifficulty::new(interval)));
+    let tiers = Arc::new(parsed_args.tiers.clone());
+    let tiers_raise_bar: bool = parsed_args.tiers_raise_bar;
+    let backup_card_secret: bool =
+        !parsed_args.backup_card_public_only && !redact_secret && !pubkey_only;
+
+    let (mut vanity_npub_prefixes, trimmed_prefixes) =
+        split_targets(&parsed_args.vanity_npub_prefixes_raw_input);
+    let (vanity_npub_suffixes, trimmed_suffixes) =
+        split_targets(&parsed_args.vanity_npub_suffixes_raw_input);
+    for entry in trimmed_prefixes.iter().chain(&trimmed_suffixes) {
+        status!(
+            format,
+            "WARNING: removed the whitespace around the vanity target '{entry}'"
+        );
+    }
+
+    if let Some(word) = &parsed_args.brand {
+        vanity_npub_prefixes = brand_prefixes(word);
+        if !vanity_npub_prefixes.is_empty() {
+            status!(
+                format,
+                "Prefixes derived from the brand '{word}': {}",
+                vanity_npub_prefixes.join(", ")
+            );
         }
     }
 
+    let npub_fuzzy = Arc::new(parsed_args.npub_fuzzy.clone());
+    let max_distance: usize = parsed_args.max_distance;
+    let extra_zeros: u8 = parsed_args.require_extra_zeros;
+    let report_limit: u8 = parsed_args.report_limit;
+    let avoid_confusables = Arc::new(parsed_args.avoid_confusables.clone());
+    let patterns: Option<Arc<Patterns>> = match &parsed_args.pattern_file {
+        Some(path) => {
+            let (patterns, rejected) =
+                read_patterns(std::io::BufReader::new(std::fs::File::open(path)?));
+            for (line, error) in rejected {
+                status!(
+                    format,
+                    "WARNING: skipped line {line} of {}: {error}",
+                    path.display()
+                );
+            }
+            Some(Arc::new(patterns))
+        }
+        None => None,
+    };
+    let known_pubkeys: Option<Arc<HashSet<XOnlyPublicKey>>> = match &parsed_args.known_pubkeys {
+        Some(path) => {
+            let (known, rejected) =
+                read_known_pubkeys(std::io::BufReader::new(std::fs::File::open(path)?));
+            for (line, error) in rejected {
+                status!(
+                    format,
+                    "WARNING: skipped line {line} of {}: {error}",
+                    path.display()
+                );
+            }
+            status!(
+                format,
+                "Skipping matches with the {} public keys of {}",
+                known.len(),
+                path.display()
+            );
+            Some(Arc::new(known))
+        }
+        None => None,
+    };
+
     //-- Calculate pow difficulty and initialize
-    check_args(
-        difficulty,
-        vanity_prefix.as_str(),
-        &vanity_npub_prefixes,
-        &vanity_npub_suffixes,
-        num_cores,
-    );
+    // a failed check is printed and becomes the invalid arguments exit code
+    let validated = (|| -> Result<Mode, String> {
+        if patterns
+            .as_ref()
+            .is_some_and(|patterns| patterns.is_empty())
+        {
+            return Err("The --pattern-file has no valid hex: or npub: patterns.".to_string());
+        }
+        if parsed_args.brand.is_some() && vanity_npub_prefixes.is_empty() {
+            return Err(
+                "The --brand word has no characters that can be written in bech32.".to_string(),
+            );
+        }
+        if let Some(warning) = check_args(
+            difficulty,
+            vanity_prefix.as_str(),
+            &vanity_npub_prefixes,
+            &vanity_npub_suffixes,
+            num_cores,
+        )? {
+            status!(format, "{warning}");
+        }
+
+        if !npub_fuzzy.is_empty() {
+            check_args(0, "", &vec![npub_fuzzy.to_string()], &Vec::new(), num_cores)?;
+            if max_distance >= npub_fuzzy.len() {
+                return Err(
+                    "The --max-distance must be smaller than the length of the --npub-fuzzy prefix."
+                        .to_string(),
+                );
+            }
+        }
+
+        if parsed_args.stride.checked_mul(num_cores as u64).is_none() {
+            return Err(
+                "The --stride multiplied by the number of cores must fit in 64 bits.".to_string(),
+            );
+        }
+
+        // prefixes read from stdin and fuzzy prefixes can only be bech32 targets
+        let mode = if parsed_args.stdin_prefixes || !npub_fuzzy.is_empty() {
+            Mode::Npub
+        } else {
+            resolve_mode(
+                parsed_args.mode,
+                vanity_prefix.as_str(),
+                &vanity_npub_prefixes,
+                &vanity_npub_suffixes,
+            )?
+        };
+
+        if parsed_args.target_time.is_some() && mode != Mode::Difficulty {
+            return Err("--target-time can only be used when mining for a difficulty.".to_string());
+        }
+        if parsed_args.avoid_confusables.is_some() && mode != Mode::Npub {
+            return Err(
+                "--avoid-confusables can only be used when mining for an npub.".to_string(),
+            );
+        }
+        if parsed_args.count_by_prefix && mode != Mode::Npub {
+            return Err("--count-by-prefix can only be used when mining for an npub.".to_string());
+        }
+        if hrps.len() > 1 && (!npub_fuzzy.is_empty() || parsed_args.avoid_confusables.is_some()) {
+            return Err(
+                "--npub-fuzzy and --avoid-confusables can only be used with a single --hrp."
+                    .to_string(),
+            );
+        }
+        if parsed_args.require_extra_zeros > 0 && mode != Mode::Npub {
+            return Err(
+                "--require-extra-zeros can only be used when mining for an npub.".to_string(),
+            );
+        }
+        if parsed_args.report_limit > 0 && mode != Mode::Difficulty {
+            return Err(
+                "--report-limit can only be used when mining for a difficulty.".to_string(),
+            );
+        }
+        if let Some(interval) = parsed_args.target_interval {
+            if interval.is_zero() {
+                return Err("The --target-interval must be at least 1 second.".to_string());
+            }
+            if mode != Mode::Difficulty {
+                return Err(
+                    "--target-interval can only be used when mining for a difficulty.".to_string(),
+                );
+            }
+        }
+        if parsed_args.seed_from_mnemonic_file.is_some() && mode != Mode::Npub {
+            return Err(
+                "--seed-from-mnemonic-file can only be used when searching for an npub."
+                    .to_string(),
+            );
+        }
+        Ok(mode)
+    })();
+    let mode = match validated {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("{e}");
+            return Ok(Outcome::InvalidArguments.into());
+        }
+    };
+
+    if let Some(path) = &parsed_args.seed_from_mnemonic_file {
+        return sweep_mnemonic_file(
+            &parsed_args,
+            path,
+            &vanity_npub_prefixes,
+            &vanity_npub_suffixes,
+            known_pubkeys.as_deref(),
+        )
+        .map(|outcome| outcome.into());
+    }
 
     // initially the same as difficulty
     let mut pow_difficulty: u8 = difficulty;
+    // single core rate once it has been benchmarked
+    let mut core_rate: Option<u64> = None;
 
-    if !vanity_prefix.is_empty() {
-        // set pow difficulty as the length of the prefix translated to bits
-        pow_difficulty = (vanity_prefix.len() * 4) as u8;
-        println!(
-            "Started mining process for vanity hex prefix: '{vanity_prefix}' (estimated pow: {pow_difficulty})"
-        );
-    } else if !vanity_npub_prefixes.is_empty() && !vanity_npub_suffixes.is_empty() {
-        // set pow difficulty as the length of the first prefix + first suffix translated to bits
-        pow_difficulty =
-            ((vanity_npub_prefixes[0].len() * 4) + (vanity_npub_suffixes[0].len() * 4)) as u8;
-        println!(
-            "Started mining process for vanity bech32 prefix[es]: 'npub1{vanity_npub_prefixes:?}' and suffix[es]: '...{vanity_npub_suffixes:?}' (estimated pow: {pow_difficulty})"
-        );
-    } else if !vanity_npub_prefixes.is_empty() {
-        // set pow difficulty as the length of the first prefix translated to bits
-        pow_difficulty = (vanity_npub_prefixes[0].len() * 4) as u8;
-        println!(
-            "Started mining process for vanity bech32 prefix[es]: 'npub1{vanity_npub_prefixes:?}' (estimated pow: {pow_difficulty})"
-        );
-    } else if !vanity_npub_suffixes.is_empty() {
-        // set pow difficulty as the length of the first suffix translated to bits
-        pow_difficulty = (vanity_npub_suffixes[0].len() * 4) as u8;
-        println!(
-            "Started mining process for vanity bech32 suffix[es]: '...{vanity_npub_suffixes:?}' (estimated pow: {pow_difficulty})"
-        );
-    } else {
-        // Defaults to using difficulty
+    match mode {
+        Mode::Hex => {
+            // set pow difficulty as the length of the prefix translated to bits
+            pow_difficulty = (vanity_prefix.len() * 4) as u8;
+            status!(
+                format,
+                "Started mining process for vanity hex prefix: '{vanity_prefix}' (estimated pow: {pow_difficulty})"
+            );
+        }
+        Mode::Npub => {
+            // each bech32 character carries 5 bits, against 4 for a hex character
+            pow_difficulty =
+                npub_pow_difficulty(&vanity_npub_prefixes, &vanity_npub_suffixes, match_any);
+            if !npub_fuzzy.is_empty() {
+                pow_difficulty = fuzzy_pow_difficulty(npub_fuzzy.len(), max_distance);
+                status!(
+                    format,
+                    "Started mining process for vanity bech32 prefix '{hrp_name}1{npub_fuzzy}' with up to {max_distance} differing characters (estimated pow: {pow_difficulty})"
+                );
+            } else if vanity_npub_prefixes.is_empty() && vanity_npub_suffixes.is_empty() {
+                status!(
+                    format,
+                    "Started mining process for vanity bech32 prefix[es] read from stdin"
+                );
+            } else if !vanity_npub_prefixes.is_empty()
+                && !vanity_npub_suffixes.is_empty()
+                && match_any
+            {
+                status!(
+                    format,
+                    "Started mining process for vanity bech32 prefix[es]: '{hrp_name}1{vanity_npub_prefixes:?}' or suffix[es]: '...{vanity_npub_suffixes:?}' (estimated pow: {pow_difficulty})"
+                );
+            } else if !vanity_npub_prefixes.is_empty() && !vanity_npub_suffixes.is_empty() {
+                status!(
+                    format,
+                    "Started mining process for vanity bech32 prefix[es]: '{hrp_name}1{vanity_npub_prefixes:?}' and suffix[es]: '...{vanity_npub_suffixes:?}' (estimated pow: {pow_difficulty})"
+                );
+            } else if !vanity_npub_prefixes.is_empty() {
+                status!(
+                    format,
+                    "Started mining process for vanity bech32 prefix[es]: '{hrp_name}1{vanity_npub_prefixes:?}' (estimated pow: {pow_difficulty})"
+                );
+            } else {
+                status!(
+                    format,
+                    "Started mining process for vanity bech32 suffix[es]: '...{vanity_npub_suffixes:?}' (estimated pow: {pow_difficulty})"
+                );
+            }
+            status!(
+                format,
+                "Note: every {hrp_name} character carries {BECH32_BITS_PER_CHAR} bits, so {hrp_name} vanity is harder than a hex prefix of the same length."
+            );
+            if parsed_args.npub_anchored_suffix {
+                status!(
+                    format,
+                    "Matching the suffix[es] against the end of the key data, before the checksum"
+                );
+            } else {
+                for suffix in vanity_npub_suffixes.iter() {
+                    status!(format, "{}", suffix_checksum_note(suffix, hrp_name));
+                }
+            }
+            if hrps.len() > 1 && all_hrps {
+                // the encodings are independent, so the difficulties add up
+                pow_difficulty = pow_difficulty.saturating_mul(hrps.len() as u8);
+                status!(
+                    format,
+                    "Every match needs the targets under each of {hrp_name} (estimated pow: {pow_difficulty})"
+                );
+            } else if hrps.len() > 1 {
+                // each encoding is another chance, halving the work for two
+                pow_difficulty = pow_difficulty.saturating_sub(hrps.len().ilog2() as u8);
+                status!(
+                    format,
+                    "A match under any of {hrp_name} is enough (estimated pow: {pow_difficulty})"
+                );
+            }
+            if extra_zeros > 0 {
+                pow_difficulty = pow_difficulty.saturating_add(extra_zeros);
+                status!(
+                    format,
+                    "Every match also needs {extra_zeros} leading zero bits (estimated pow: {pow_difficulty})"
+                );
+            }
+        }
+        Mode::Difficulty => {
+            if let Some(target_time) = parsed_args.target_time {
+                let rate = benchmark_core_rate(
+                    format,
+                    parsed_args.use_nostr_keys,
+                    parsed_args.warmup,
+                    parsed_args.benchmark_duration,
+                );
+                difficulty = difficulty_for_target_time(rate * num_cores as u64, target_time);
+                pow_difficulty = difficulty;
+                status!(
+                    format,
+                    "Picked a difficulty of {difficulty} to find a key within about {} seconds",
+                    target_time.as_secs()
+                );
+                core_rate = Some(rate);
+            }
 
-        // if difficulty not indicated, then assume default
-        if difficulty == 0 {
-            difficulty = DIFFICULTY_DEFAULT; // default
-            pow_difficulty = difficulty;
+            // if difficulty not indicated, then assume default. The lowest
+            // key search starts from any key, so the best one is always kept
+            if difficulty == 0 && !find_lowest {
+                difficulty = DIFFICULTY_DEFAULT; // default
+                pow_difficulty = difficulty;
+            }
+
+            if let Some(patterns) = patterns.as_ref() {
+                pow_difficulty = patterns.pow_difficulty();
+                status!(
+                    format,
+                    "Started mining process for {} hex and {} npub patterns (estimated pow: {pow_difficulty})",
+                    patterns.hex.len(),
+                    patterns.npub.len()
+                );
+            } else if find_lowest {
+                status!(
+                    format,
+                    "Started mining process for the lowest public key within {} seconds",
+                    parsed_args.timeout.unwrap_or_default().as_secs_f64()
+                );
+            } else if let Some(tiers) = tiers.as_ref() {
+                pow_difficulty = tiers.lowest();
+                status!(
+                    format,
+                    "Started mining process for the difficulty tiers {tiers} (pow: {pow_difficulty})"
+                );
+            } else if let Some(threshold) = below {
+                // a threshold is about as hard as its leading zero bits
+                pow_difficulty = get_leading_zero_bits(&threshold);
+                status!(
+                    format,
+                    "Started mining process for public keys below {} (estimated pow: {pow_difficulty})",
+                    hex_string(&threshold)
+                );
+            } else if let Some(interval) = parsed_args.target_interval {
+                status!(
+                    format,
+                    "Started mining process with a difficulty of: {difficulty}, adapted for a key about every {} seconds",
+                    interval.as_secs_f64()
+                );
+            } else if min_zero_bytes > 0 {
+                status!(
+                    format,
+                    "Started mining process for {min_zero_bytes} leading zero bytes (pow: {pow_difficulty})"
+                );
+            } else if parsed_args.nip13_difficulty.is_some() {
+                status!(
+                    format,
+                    "Started mining process for a public key with a NIP-13 proof of work of {difficulty} bits (pow: {pow_difficulty})"
+                );
+                status!(
+                    format,
+                    "Note: this is the proof of work of the key itself. Relays requiring NIP-13 check the proof of work of each event id, which is mined separately for every event."
+                );
+            } else if difficulty_bar == DifficultyBar::Fixed {
+                status!(
+                    format,
+                    "Started mining process for every key with a difficulty of at least: {difficulty} (pow: {pow_difficulty})"
+                );
+            } else {
+                status!(
+                    format,
+                    "Started mining process with a difficulty of: {difficulty} (pow: {pow_difficulty})"
+                );
+            }
         }
+    }
 
-        println!(
-            "Started mining process with a difficulty of: {difficulty} (pow: {pow_difficulty})"
+    // just enough cores for the target rate
+    if let Some(target_rate) = parsed_args.target_rate {
+        let rate = core_rate.unwrap_or_else(|| {
+            benchmark_core_rate(
+                format,
+                parsed_args.use_nostr_keys,
+                parsed_args.warmup,
+                parsed_args.benchmark_duration,
+            )
+        });
+        core_rate = Some(rate);
+        num_cores = cores_for_target_rate(rate, target_rate, num_cpus::get());
+        status!(
+            format,
+            "Using {num_cores} of {} cores to reach about {} h/s",
+            num_cpus::get(),
+            group_thousands(target_rate)
         );
     }
 
     // benchmark cores
-    if !vanity_npub_prefixes.is_empty() || !vanity_npub_suffixes.is_empty() {
-        println!("Benchmarking of cores disabled for vanity npub key upon proper calculation.");
+    let benchmarked = if count_only {
+        status!(
+            format,
+            "Counting keys for {} seconds, matches are discarded",
+            parsed_args.benchmark_duration.as_secs_f64()
+        );
+        None
+    } else if mode == Mode::Npub && !parsed_args.benchmark_json {
+        status!(
+            format,
+            "Benchmarking of cores disabled for vanity npub key upon proper calculation."
+        );
+        None
+    } else if find_lowest && !parsed_args.benchmark_json {
+        status!(
+            format,
+            "Benchmarking skipped, there is no target to estimate"
+        );
+        None
+    } else if let Some(rate) = core_rate {
+        print_estimate(Some(rate), num_cores, pow_difficulty, format);
+        Some(rate)
+    } else if parsed_args.no_benchmark {
+        print_estimate(None, num_cores, pow_difficulty, format);
+        None
     } else {
-        benchmark_cores(num_cores, pow_difficulty);
+        Some(benchmark_cores(
+            num_cores,
+            pow_difficulty,
+            format,
+            parsed_args.use_nostr_keys,
+            parsed_args.warmup,
+            parsed_args.benchmark_duration,
+        ))
+    };
+    if let Some(rate) = benchmarked.filter(|_| parsed_args.benchmark_json) {
+        let result = BenchmarkResult::new(rate, num_cores, parsed_args.benchmark_duration);
+        println!("{}", serde_json::to_string(&result)?);
+    }
+
+    #[cfg(feature = "distributed")]
+    let coordinator = Arc::new(parsed_args.coordinator.clone());
+    #[cfg(feature = "distributed")]
+    let worker_id: u32 = parsed_args.worker_id;
+
+    if let Some(resume_scalar) = parsed_args.resume_scalar {
+        status!(
+            format,
+            "Resuming the sequential search from {}",
+            resume_scalar.display_secret()
+        );
+    }
+    let start_from: Option<SecretKey> = parsed_args.resume_scalar.or(parsed_args.start_from);
+    if start_from.is_some() {
+        status!(
+            format,
+            "WARNING: --start-from searches sequential secret keys. Anyone who learns the start and stride can find your key, so only use it for vanity keys, never for security-critical ones."
+        );
+    }
+    if parsed_args.seed.is_some() {
+        status!(
+            format,
+            "WARNING: --seed makes the keys reproducible by anyone who knows it, so only use it for tests, never for keys you keep."
+        );
+    }
+    let stride: u64 = parsed_args.stride;
+    // checked with the other arguments
+    let walk_step: u64 = stride * num_cores as u64;
+    // only tracked when it is saved, to keep the hot loop lean otherwise
+    let checkpoint = parsed_args.checkpoint.clone();
+    let walk_progress: Option<Arc<WalkProgress>> = checkpoint
+        .as_ref()
+        .map(|_| Arc::new(WalkProgress::new(num_cores, stride, walk_step)));
+
+    #[cfg(feature = "profile")]
+    if !parsed_args.profile_cpu.is_empty() {
+        let path = parsed_args.profile_cpu.clone();
+        let duration = parsed_args.profile_duration;
+        status!(
+            format,
+            "Profiling the mining threads for {} seconds...",
+            duration.as_secs()
+        );
+        thread::spawn(move || match rana::profile::profile_cpu(&path, duration) {
+            Ok(()) => status!(format, "CPU profile written to {path}"),
+            Err(e) => status!(format, "Could not write the CPU profile: {e}"),
+        });
     }
 
     // Loop: generate public keys until desired public key is reached
     let now = Instant::now();
 
-    println!("Mining using {num_cores} cores...");
+    if parsed_args.cpu_count_report {
+        status!(
+            format,
+            "{}",
+            format_cpu_topology(&CpuTopology::detect(num_cores))
+        );
+    }
+    status!(format, "Mining using {num_cores} cores...");
+    if let Some(rate) = max_rate {
+        status!(format, "Hashrate capped at {rate} hashes/second");
+    }
 
     // thread safe variables
-    let best_diff = Arc::new(AtomicU8::new(pow_difficulty));
+    // a key needs more leading zero bits than the best difficulty, so start one
+    // bit below the target to accept keys with exactly the given zero bytes
+    let best_diff = Arc::new(AtomicU8::new(if let Some(tiers) = tiers.as_ref() {
+        tiers.highest() - 1
+    } else if min_zero_bytes > 0 {
+        pow_difficulty - 1
+    } else {
+        pow_difficulty
+    }));
     let vanity_ts = Arc::new(vanity_prefix);
-    let vanity_npubs_pre_ts = Arc::new(vanity_npub_prefixes);
+    let target_counts: Option<Arc<TargetCounts>> = parsed_args.count_by_prefix.then(|| {
+        Arc::new(TargetCounts::new(&npub_targets(
+            &vanity_npub_prefixes,
+            &vanity_npub_suffixes,
+            match_any,
+        )))
+    });
+    let vanity_npubs_pre_ts = Arc::new(RwLock::new(vanity_npub_prefixes));
+    if parsed_args.stdin_prefixes {
+        let vanity_npubs_pre_ts = vanity_npubs_pre_ts.clone();
+        thread::spawn(move || {
+            read_prefixes(std::io::stdin().lock(), &vanity_npubs_pre_ts, format);
+            status!(format, "stdin closed, no more npub prefixes will be added");
+        });
+    }
     let vanity_npubs_post_ts = Arc::new(vanity_npub_suffixes);
     let iterations = Arc::new(AtomicU64::new(0));
+    let best_match: Arc<Mutex<Option<KeyMatch>>> = Arc::new(Mutex::new(None));
+    let lowest: Arc<Mutex<Option<KeyMatch>>> = Arc::new(Mutex::new(None));
+    let list_found: bool = parsed_args.list_found;
+    let found: Arc<Mutex<Vec<KeyMatch>>> = Arc::new(Mutex::new(Vec::new()));
+    let matches = Arc::new(AtomicU64::new(0));
+    let backup_cards = Arc::new(AtomicU64::new(0));
+    let unreported = Arc::new(AtomicU64::new(0));
+    let dedup = dedup_filter(&parsed_args);
+    let duplicates = Arc::new(AtomicU64::new(0));
+    let already_known = Arc::new(AtomicU64::new(0));
+    let require_entropy: bool = parsed_args.require_entropy;
+    let low_entropy = Arc::new(AtomicU64::new(0));
+
+    // Ctrl+C stops the workers so the summary can be printed, and a second
+    // one soon after quits without waiting for them
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let shutdown = shutdown.clone();
+        let interrupted = interrupted.clone();
+        let presses = InterruptCounter::default();
+        ctrlc::set_handler(move || {
+            interrupted.store(true, Ordering::Relaxed);
+            shutdown.store(true, Ordering::Relaxed);
+            match presses.press(Instant::now()) {
+                Interrupt::Graceful => status!(
+                    format,
+                    "Stopping... press Ctrl+C again within {} seconds to quit right away",
+                    FORCE_QUIT_WINDOW.as_secs()
+                ),
+                Interrupt::ForceQuit => {
+                    status!(format, "Quitting without waiting for the workers");
+                    std::process::exit(Outcome::Interrupted as i32);
+                }
+            }
+        })
+        .expect("Could not set the Ctrl+C handler");
+    }
+    // loaded up front so a corrupt keystore stops the run before any mining
+    let keystore: Option<Arc<KeystoreWriter>> = match &parsed_args.keystore {
+        Some(path) => Some(Arc::new(KeystoreWriter::open(
+            path,
+            parsed_args.keystore_password.as_deref().unwrap_or_default(),
+            parsed_args.keystore_label.as_deref(),
+        )?)),
+        None => None,
+    };
+    // --count-only mines for as long as a benchmark would
+    let timeout: Option<Duration> = if count_only {
+        Some(parsed_args.benchmark_duration)
+    } else {
+        parsed_args.timeout
+    };
+    let max_iterations: Option<u64> = parsed_args.max_iterations;
+    let print_every: Option<u64> = parsed_args.print_every;
+    let secret_format: SecretFormat = parsed_args.secret_format;
+    let base64: bool = parsed_args.base64;
+    let show_address_examples: bool = parsed_args.show_address_examples;
+    let debug_bytes: bool = parsed_args.debug_bytes;
+    let tag = Arc::new(parsed_args.tag.clone());
+    let nprofile_relays = Arc::new(parsed_args.nprofile_relays.clone());
+    let interactive: bool = parsed_args.interactive && std::io::stdin().is_terminal();
+    if parsed_args.interactive && !interactive {
+        status!(format, "stdin is not a terminal, --interactive is disabled");
+    }
+    // one prompt at a time, and the other cores wait for its answer
+    let prompt_lock = Arc::new(Mutex::new(()));
+    let pause = Arc::new(MiningPause::default());
+
+    #[cfg(feature = "metrics")]
+    if !parsed_args.metrics_addr.is_empty() {
+        let metrics = rana::metrics::Metrics::new(
+            iterations.clone(),
+            matches.clone(),
+            best_match.clone(),
+            now,
+        );
+        let addr = rana::metrics::serve_metrics(&parsed_args.metrics_addr, Arc::new(metrics))?;
+        status!(format, "Serving metrics on http://{addr}/metrics");
+    }
+
+    // keys each core generates before adding them to the shared counter and
+    // checking for a shutdown
+    let batches: Vec<u64> = if parsed_args.shuffle_cores {
+        status!(
+            format,
+            "Measuring each of the {num_cores} cores for {} seconds...",
+            SHUFFLE_BENCHMARK.as_secs_f64()
+        );
+        let rates = benchmark_threads(num_cores, parsed_args.use_nostr_keys, SHUFFLE_BENCHMARK);
+        let batches = batch_sizes(&rates, parsed_args.batch);
+        for (core, (rate, batch)) in rates.iter().zip(&batches).enumerate() {
+            status!(
+                format,
+                "Core {core}: {} h/s, batches of {batch} keys",
+                group_thousands(*rate)
+            );
+        }
+        batches
+    } else {
+        vec![parsed_args.batch; num_cores]
+    };
+
+    // every core gets its own connection, opened before any starts mining so
+    // an unreachable coordinator stops the run right away
+    #[cfg(feature = "distributed")]
+    let mut connections: Vec<Option<DistributedWalk>> = if coordinator.is_empty() {
+        (0..num_cores).map(|_| None).collect()
+    } else {
+        match (0..num_cores)
+            .map(|_| DistributedWalk::connect(&coordinator, worker_id).map(Some))
+            .collect::<Result<_>>()
+        {
+            Ok(connections) => connections,
+            Err(e) => {
+                status!(
+                    format,
+                    "Could not connect to the coordinator at {coordinator}: {e}"
+                );
+                return Ok(Outcome::Failed.into());
+            }
+        }
+    };
 
     // start a thread for each core for calculations
-    for _ in 0..num_cores {
+    let mut workers = Vec::with_capacity(num_cores);
+    for (thread_index, &batch) in batches.iter().enumerate() {
         let best_diff = best_diff.clone();
         let vanity_ts = vanity_ts.clone();
         let vanity_npubs_pre_ts = vanity_npubs_pre_ts.clone();
         let vanity_npubs_post_ts = vanity_npubs_post_ts.clone();
         let passphrase = Arc::new(parsed_args.mnemonic_passphrase.clone());
         let iterations = iterations.clone();
+        let output = output.clone();
+        let npub_log = npub_log.clone();
+        let backup_card = backup_card.clone();
+        let backup_cards = backup_cards.clone();
+        let npub_fuzzy = npub_fuzzy.clone();
+        let avoid_confusables = avoid_confusables.clone();
+        let profile_metadata = profile_metadata.clone();
+        let keystore = keystore.clone();
+        let target_counts = target_counts.clone();
+        let walk_progress = walk_progress.clone();
+        let best_match = best_match.clone();
+        let lowest = lowest.clone();
+        let adaptive = adaptive.clone();
+        let patterns = patterns.clone();
+        let found = found.clone();
+        let matches = matches.clone();
+        let unreported = unreported.clone();
+        let dedup = dedup.clone();
+        let duplicates = duplicates.clone();
+        let known_pubkeys = known_pubkeys.clone();
+        let already_known = already_known.clone();
+        let low_entropy = low_entropy.clone();
+        let nprofile_relays = nprofile_relays.clone();
+        let tag = tag.clone();
+        let hrps = hrps.clone();
+        let tiers = tiers.clone();
+        let shutdown = shutdown.clone();
+        let prompt_lock = prompt_lock.clone();
+        let pause = pause.clone();
+        #[cfg(feature = "distributed")]
+        let coordinator = coordinator.clone();
+        #[cfg(feature = "distributed")]
+        let connection = connections[thread_index].take();
 
-        thread::spawn(move || {
-            let mut keys;
-            let mut mnemonic;
-
-            loop {
-                let mut uses_mnemonic: Option<Mnemonic> = None;
-                iterations.fetch_add(1, Ordering::Relaxed);
+        workers.push(thread::spawn(move || {
+            let secp = Secp256k1::new();
+            // every core gets an equal share of the rate limit
+            let mut throttle = max_rate.map(|rate| Throttle::new(rate as f64 / num_cores as f64));
+            // each thread walks its own interleaved share of the scalar range
+            #[allow(unused_mut)]
+            let mut walk: Option<Box<dyn Iterator<Item = (SecretKey, u128)>>> =
+                start_from.map(|start| {
+                    Box::new(ScalarWalk::new(
+                        start,
+                        thread_index as u64 * stride,
+                        walk_step,
+                    )) as Box<dyn Iterator<Item = (SecretKey, u128)>>
+                });
+            #[allow(unused_mut)]
+            let mut offset_label = "Scalar offset from --start-from";
+            // or the ranges handed out by the coordinator
+            #[cfg(feature = "distributed")]
+            if let Some(connection) = connection {
+                walk = Some(Box::new(connection));
+                offset_label = "Scalar offset into the coordinator range";
+            }
+            let mut rng = new_rng(parsed_args.rng, parsed_args.seed, thread_index as u64);
+            // None once the walk ends, which only a lost coordinator does
+            let mut generate = || -> Option<(Keys, Option<Mnemonic>, Option<u128>)> {
+                if let Some(walk) = walk.as_mut() {
+                    let (secret_key, offset) = walk.next()?;
+                    return Some((Keys::new(secret_key), None, Some(offset)));
+                }
 
                 // Use mnemonics to generate key pair
                 if parsed_args.word_count > 0 {
-                    mnemonic = Keys::generate_mnemonic(parsed_args.word_count)
+                    let mnemonic = generate_mnemonic_with(&mut rng, parsed_args.word_count)
                         .expect("Couldn't not generate mnemonic");
 
-                    keys = Keys::from_mnemonic(mnemonic.to_string(), Some(passphrase.to_string()))
-                        .expect("Error generating keys from mnemonic");
-                    uses_mnemonic = Some(mnemonic);
+                    let keys =
+                        Keys::from_mnemonic(mnemonic.to_string(), Some(passphrase.to_string()))
+                            .expect("Error generating keys from mnemonic");
+                    Some((keys, Some(mnemonic), None))
                 } else {
-                    keys = Keys::generate();
+                    let (secret_key, _) = secp.generate_keypair(&mut rng);
+                    Some((Keys::new(secret_key), None, None))
+                }
+            };
+
+            // a match that is refused is never reported, nor does it raise
+            // the difficulty bar for the keys after it
+            let refused = |keys: &Keys| -> bool {
+                if require_entropy {
+                    let secret = keys.secret_key().map(|sk| sk.secret_bytes());
+                    if secret.is_ok_and(|secret| !has_sufficient_entropy(&secret)) {
+                        low_entropy.fetch_add(1, Ordering::Relaxed);
+                        status!(
+                            format,
+                            "Refused a match of {} as its secret has too little entropy",
+                            keys.public_key()
+                        );
+                        return true;
+                    }
+                }
+                // keys already owned are not found again
+                if known_pubkeys
+                    .as_ref()
+                    .is_some_and(|known| known.contains(&keys.public_key()))
+                {
+                    already_known.fetch_add(1, Ordering::Relaxed);
+                    return true;
+                }
+                // a key derived again is only reported the first time
+                if let Some(dedup) = dedup.as_ref() {
+                    if !dedup.insert(&keys.public_key().serialize()) {
+                        duplicates.fetch_add(1, Ordering::Relaxed);
+                        return true;
+                    }
+                }
+                false
+            };
+
+            // keys counted since the shared counter was last updated, and
+            // its value then
+            let (mut pending, mut counted) = (0_u64, 0_u64);
+            loop {
+                // once per batch, right after the shared counter was updated
+                if pending == 0 && interactive {
+                    pause.wait(&shutdown);
+                }
+                if pending == 0 && shutdown.load(Ordering::Relaxed) {
+                    break;
+                }
+                let generated = if retry_on_weak_key {
+                    generate_until(&mut generate, |generated| {
+                        generated
+                            .as_ref()
+                            .is_none_or(|(keys, _, _)| is_well_formed(&secp, keys))
+                    })
+                    .0
+                } else {
+                    generate()
+                };
+                let Some((keys, uses_mnemonic, scalar_offset)) = generated else {
+                    status!(
+                        format,
+                        "Lost the connection to the coordinator, stopping the search"
+                    );
+                    iterations.fetch_add(pending, Ordering::Relaxed);
+                    shutdown.store(true, Ordering::Relaxed);
+                    break;
+                };
+                // the key is always tested before the loop checks for a shutdown
+                if let (Some(progress), Some(offset)) = (walk_progress.as_ref(), scalar_offset) {
+                    progress.record(thread_index, offset);
+                }
+                pending += 1;
+                let mut iteration = counted + pending;
+                if pending >= batch {
+                    // a plain counter only read for the stats and --max-iterations,
+                    // so Relaxed is enough
+                    let previous = iterations.fetch_add(pending, Ordering::Relaxed);
+                    iteration = previous + pending;
+                    (pending, counted) = (0, iteration);
+                    if max_iterations.is_some_and(|max| iteration >= max) {
+                        shutdown.store(true, Ordering::Relaxed);
+                    }
+                    if let Some(every) = print_every {
+                        let mut crossed = false;
+                        for step in crossed_progress_steps(previous, iteration, every) {
+                            status!(
+                                format,
+                                "Progress: {}",
+                                format_stats(step, now.elapsed().as_secs(), scientific)
+                            );
+                            crossed = true;
+                        }
+                        if let Some(counts) = target_counts.as_ref().filter(|_| crossed) {
+                            status!(format, "{}", format_target_counts(&counts.snapshot()));
+                        }
+                    }
+                }
+                if let Some(throttle) = throttle.as_mut() {
+                    throttle.tick();
                 }
 
                 let mut leading_zeroes: u8 = 0;
                 let mut vanity_npub: String = String::new();
+                let mut below_margin: Option<[u8; 32]> = None;
+                let mut tier: Option<String> = None;
 
                 // check pubkey validity depending on arg settings
                 let mut is_valid_pubkey: bool = false;
 
-                if !vanity_ts.is_empty() {
-                    // hex vanity search
-                    is_valid_pubkey = keys
-                        .public_key()
-                        .to_string()
-                        .starts_with(vanity_ts.as_str());
-                } else if !vanity_npubs_pre_ts.is_empty() || !vanity_npubs_post_ts.is_empty() {
-                    // bech32 vanity search
-                    let bech_key: String = keys.public_key().to_bech32().unwrap();
-
-                    if !vanity_npubs_pre_ts.is_empty() && !vanity_npubs_post_ts.is_empty() {
-                        for cur_vanity_npub_pre in vanity_npubs_pre_ts.iter() {
-                            for cur_vanity_npub_post in vanity_npubs_post_ts.iter() {
-                                is_valid_pubkey = bech_key.starts_with(
-                                    (String::from("npub1") + cur_vanity_npub_pre.as_str()).as_str(),
-                                ) && bech_key
-                                    .ends_with(cur_vanity_npub_post.as_str());
-
-                                if is_valid_pubkey {
-                                    vanity_npub = cur_vanity_npub_pre.clone()
-                                        + "..."
-                                        + cur_vanity_npub_post.clone().as_str();
-                                    break;
-                                }
-                            }
-                            if is_valid_pubkey {
-                                break;
+                match mode {
+                    Mode::Hex => {
+                        // hex vanity search
+                        is_valid_pubkey =
+                            matches_hex_prefix(&keys.public_key(), &vanity_ts) && !refused(&keys);
+                    }
+                    Mode::Npub => {
+                        // bech32 vanity search
+                        if !npub_fuzzy.is_empty() {
+                            let bech_key: String = encode_bech32(&keys, hrps[0]).unwrap();
+                            if let Some((matched, distance)) =
+                                match_npub_fuzzy(&bech_key, &npub_fuzzy, max_distance)
+                            {
+                                is_valid_pubkey = true;
+                                vanity_npub = format!("{matched} (distance {distance})");
                             }
+                        } else if let Some(matched) =
+                            match_hrps(&keys, &hrps, all_hrps, |bech_key| {
+                                npub_matches(
+                                    bech_key,
+                                    &vanity_npubs_pre_ts.read().unwrap(),
+                                    &vanity_npubs_post_ts,
+                                    match_any,
+                                )
+                            })
+                        {
+                            is_valid_pubkey = true;
+                            vanity_npub = matched;
                         }
-                    } else if !vanity_npubs_pre_ts.is_empty() {
-                        for cur_vanity_npub in vanity_npubs_pre_ts.iter() {
-                            is_valid_pubkey = bech_key.starts_with(
-                                (String::from("npub1") + cur_vanity_npub.as_str()).as_str(),
-                            );
 
-                            if is_valid_pubkey {
-                                vanity_npub = cur_vanity_npub.clone();
-                                break;
-                            }
+                        // reject lookalike prefixes after the match
+                        if let Some(confusables) =
+                            avoid_confusables.as_deref().filter(|_| is_valid_pubkey)
+                        {
+                            let target = if npub_fuzzy.is_empty() {
+                                // the prefix part of "prefix...suffix", empty for a suffix
+                                vanity_npub.split("...").next().unwrap_or_default()
+                            } else {
+                                npub_fuzzy.as_str()
+                            };
+                            let bech_key: String = encode_bech32(&keys, hrps[0]).unwrap();
+                            is_valid_pubkey =
+                                find_confusable(&bech_key, target, confusables).is_none();
                         }
-                    } else {
-                        for cur_vanity_npub in vanity_npubs_post_ts.iter() {
-                            is_valid_pubkey = bech_key.ends_with(cur_vanity_npub.as_str());
-
-                            if is_valid_pubkey {
-                                vanity_npub = cur_vanity_npub.clone();
-                                break;
+                        if extra_zeros > 0 && is_valid_pubkey {
+                            match extra_zero_bits(&keys.public_key().serialize(), extra_zeros) {
+                                Some(bits) => leading_zeroes = bits,
+                                None => is_valid_pubkey = false,
                             }
                         }
+                        if is_valid_pubkey && refused(&keys) {
+                            is_valid_pubkey = false;
+                        }
+                        if let Some(counts) = target_counts.as_ref().filter(|_| is_valid_pubkey) {
+                            counts.record(&vanity_npub);
+                        }
                     }
-                } else {
-                    // difficulty search
-                    leading_zeroes = get_leading_zero_bits(&keys.public_key().serialize());
-                    is_valid_pubkey = leading_zeroes > best_diff.load(Ordering::Relaxed);
-                    if is_valid_pubkey {
-                        // update difficulty only if it was set in the first place
-                        if best_diff.load(Ordering::Relaxed) > 0 {
-                            best_diff
-                                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |_| {
-                                    Some(leading_zeroes)
-                                })
-                                .unwrap();
+                    Mode::Difficulty => {
+                        // difficulty search
+                        let serialized = keys.public_key().serialize();
+                        if let Some(patterns) = patterns.as_ref() {
+                            if let Some(matched) = patterns
+                                .find_match(&keys.public_key())
+                                .filter(|_| !refused(&keys))
+                            {
+                                is_valid_pubkey = true;
+                                vanity_npub = matched;
+                            }
+                        } else if find_lowest {
+                            // a smaller key never has fewer leading zero bits
+                            let bits = get_leading_zero_bits(&serialized);
+                            if bits >= best_diff.load(Ordering::Relaxed) && !refused(&keys) {
+                                let kept = if pubkey_only {
+                                    Keys::from_public_key(keys.public_key())
+                                } else {
+                                    keys.clone()
+                                };
+                                let candidate = KeyMatch {
+                                    origin: origin_of(scalar_offset),
+                                    found_at_secs: now.elapsed().as_secs_f64(),
+                                    found_at_iteration: iteration,
+                                    ..KeyMatch::new(&kept, "", bits, None, false).unwrap()
+                                };
+                                if retain_lowest(&lowest, candidate) {
+                                    raise_best(&best_diff, bits);
+                                }
+                            }
+                        } else if let Some(tiers) = tiers.as_ref() {
+                            leading_zeroes = get_leading_zero_bits(&serialized);
+                            if let Some(label) =
+                                tiers.label_for(leading_zeroes).filter(|_| !refused(&keys))
+                            {
+                                // optionally only beat the best key of the highest tier
+                                is_valid_pubkey = !tiers_raise_bar
+                                    || leading_zeroes < tiers.highest()
+                                    || raise_best(&best_diff, leading_zeroes);
+                                tier = Some(label.to_string());
+                            }
+                        } else if let Some(adaptive) = adaptive.as_ref() {
+                            leading_zeroes = get_leading_zero_bits(&serialized);
+                            // only a key that beats the difficulty is screened
+                            is_valid_pubkey = leading_zeroes > best_diff.load(Ordering::Relaxed)
+                                && !refused(&keys)
+                                && adaptive.record(&best_diff, leading_zeroes, now.elapsed());
+                        } else if let Some(threshold) = below.as_ref() {
+                            below_margin =
+                                below_threshold(&serialized, threshold).filter(|_| !refused(&keys));
+                            if below_margin.is_some() {
+                                is_valid_pubkey = true;
+                                leading_zeroes = get_leading_zero_bits(&serialized);
+                            }
+                        } else {
+                            let best = best_diff.load(Ordering::Relaxed);
+                            let bar = match difficulty_bar {
+                                // the bar stays at the difficulty, reached keys included
+                                DifficultyBar::Fixed => pow_difficulty.saturating_sub(1),
+                                DifficultyBar::OpenEnded => best,
+                            };
+                            if let Some(bits) = meets_difficulty(&serialized, min_zero_bytes, bar)
+                                .filter(|_| !refused(&keys))
+                            {
+                                leading_zeroes = bits;
+                                is_valid_pubkey = match difficulty_bar {
+                                    DifficultyBar::Fixed => true,
+                                    // update difficulty only if it was set in the first place
+                                    DifficultyBar::OpenEnded => {
+                                        best == 0 || raise_best(&best_diff, bits)
+                                    }
+                                };
+                            }
                         }
                     }
                 }
 
                 // if one of the required conditions is satisfied
+                // a stopped run reports nothing more, even within a batch
+                if is_valid_pubkey && shutdown.load(Ordering::Relaxed) {
+                    break;
+                }
+                if is_valid_pubkey && count_only {
+                    matches.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
                 if is_valid_pubkey {
-                    println!("==============================================");
-                    print_keys(&keys, vanity_npub, leading_zeroes, uses_mnemonic).unwrap();
-                    let iterations = iterations.load(Ordering::Relaxed);
-                    let iter_string = format!("{iterations}");
-                    let l = iter_string.len();
-                    let f = iter_string.chars().next().unwrap();
-                    println!(
-                        "{} iterations (about {}x10^{} hashes) in {} seconds. Avg rate {} hashes/second",
-                        iterations,
-                        f,
-                        l - 1,
-                        now.elapsed().as_secs(),
-                        iterations / max(1, now.elapsed().as_secs())
-                    );
-                    if qr {
-                        print_qr(keys.secret_key().unwrap()).unwrap();
+                    // vanity matches get their leading zero bits as a bonus stat
+                    if leading_zeroes == 0 {
+                        leading_zeroes = get_leading_zero_bits(&keys.public_key().serialize());
+                    }
+                    // forget the secret before anything gets formatted
+                    let keys = if pubkey_only {
+                        Keys::from_public_key(keys.public_key())
+                    } else {
+                        keys
+                    };
+                    let mnemonic = uses_mnemonic.as_ref();
+                    let mut printed =
+                        KeyMatch::new(&keys, &vanity_npub, leading_zeroes, mnemonic, redact_secret)
+                            .unwrap();
+                    printed.tier = tier;
+                    printed.tag = tag.as_ref().clone();
+                    if !nprofile_relays.is_empty() {
+                        printed.nprofile =
+                            Some(encode_nprofile(&keys.public_key(), &nprofile_relays).unwrap());
+                    }
+                    printed.found_at_secs = now.elapsed().as_secs_f64();
+                    printed.found_at_iteration = iteration;
+                    if scalar_offset.is_some() {
+                        printed.origin = KeyOrigin::Sequential;
+                    }
+                    let unredacted = KeyMatch {
+                        secret_key: keys
+                            .secret_key()
+                            .ok()
+                            .map(|sk| sk.display_secret().to_string()),
+                        nsec: keys.secret_key().ok().and_then(|sk| sk.to_bech32().ok()),
+                        mnemonic: mnemonic.map(|m| m.to_string()),
+                        ..printed.clone()
+                    };
+                    let key_match = printed.clone();
+                    matches.fetch_add(1, Ordering::Relaxed);
+                    // small early improvements only count towards the summary
+                    if !is_reported(leading_zeroes, report_limit) {
+                        retain_best(&best_match, key_match);
+                        unreported.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    if list_found {
+                        found.lock().unwrap().push(key_match.clone());
+                    }
+                    retain_best(&best_match, key_match);
+                    status!(format, "==============================================");
+                    print_key_match(
+                        &printed.clone().with_encodings(secret_format, base64),
+                        &unredacted.with_encodings(secret_format, base64),
+                        format,
+                        &output,
+                    )
+                    .unwrap();
+                    log_npub(&npub_log, &printed.npub, format);
+                    if show_address_examples {
+                        status!(format, "{}", format_address_examples(&printed.npub));
+                    }
+                    if debug_bytes {
+                        status!(
+                            format,
+                            "{}",
+                            format_pubkey_bytes(&keys.public_key().serialize())
+                        );
+                    }
+                    let save_to_keystore = |keystore: &KeystoreWriter| {
+                        let matched = match &printed.vanity {
+                            Some(vanity) => vanity.clone(),
+                            None if mode == Mode::Hex => vanity_ts.to_string(),
+                            None => format!("{} leading zero bits", printed.leading_zero_bits),
+                        };
+                        let path = keystore.path().display();
+                        match keystore.save_key(&keys, &matched) {
+                            Ok(()) => status!(format, "Key saved to {path}"),
+                            Err(e) => {
+                                status!(format, "WARNING: could not save the key to {path}: {e}")
+                            }
+                        }
+                    };
+                    // interactive runs only save the keys picked at the prompt
+                    if let Some(keystore) = keystore.as_ref().filter(|_| !interactive) {
+                        save_to_keystore(keystore);
+                    }
+                    if !no_stats {
+                        status!(
+                            format,
+                            "{}",
+                            format_stats(
+                                iterations.load(Ordering::Relaxed),
+                                now.elapsed().as_secs(),
+                                scientific
+                            )
+                        );
+                    }
+                    #[cfg(feature = "distributed")]
+                    if !coordinator.is_empty() {
+                        if let Err(e) = WorkerClient::connect(&coordinator, worker_id)
+                            .and_then(|mut client| client.report_found(&keys.secret_key()?))
+                        {
+                            status!(format, "Could not report the key to the coordinator: {e}");
+                        }
+                    }
+                    if let Some(offset) = scalar_offset {
+                        status!(format, "{offset_label}: {offset}");
+                    }
+                    if let Some(margin) = below_margin {
+                        status!(format, "Below the threshold by: {}", hex_string(&margin));
+                    }
+                    if preview {
+                        let signed = relay_publish_dry_run
+                            .then(|| sign_profile_event(&keys, &profile_metadata).unwrap());
+                        let (created_at, event_id) = match &signed {
+                            Some(event) => (event.created_at, event.id),
+                            None => {
+                                let created_at = Timestamp::now();
+                                let public_key = keys.public_key();
+                                let event_id =
+                                    profile_preview_id(&public_key, &profile_metadata, created_at)
+                                        .unwrap();
+                                (created_at, event_id)
+                            }
+                        };
+                        status!(
+                            format,
+                            "Profile event id (kind 0, created at {}): {}",
+                            created_at.as_u64(),
+                            event_id.to_hex()
+                        );
+                        status!(
+                            format,
+                            "Profile content: {}",
+                            profile_metadata.as_json().unwrap()
+                        );
+                        if let Some(event) = signed {
+                            status!(format, "Signed profile event, not sent:");
+                            status!(format, "{}", event.as_json().unwrap());
+                        }
+                    }
+                    if !backup_card.is_empty() {
+                        // every match of an open-ended run gets its own card
+                        let index = backup_cards.fetch_add(1, Ordering::Relaxed) + 1;
+                        let path = backup_card_path(&backup_card, index);
+                        match write_backup_card(&keys, backup_card_secret, &path) {
+                            Ok(()) => status!(format, "Backup card written to {path}"),
+                            Err(e) => status!(format, "Could not write the backup card: {e}"),
+                        }
+                    }
+                    if qr && pubkey_only {
+                        status!(
+                            format,
+                            "QR code of the private key not shown, the secret was dropped"
+                        );
+                    } else if qr && redact_secret {
+                        status!(
+                            format,
+                            "QR code of the private key not shown, the secret is redacted"
+                        );
+                    } else if qr {
+                        // the keys are already printed, a QR failure must not lose them
+                        if let Err(e) = print_qr(keys.secret_key().unwrap(), format, qr_style) {
+                            status!(format, "WARNING: could not print the QR code: {e}");
+                        }
+                    }
+                    if interactive {
+                        let _prompt = prompt_lock.lock().unwrap();
+                        // keeping the match resumes all cores where they were
+                        let _paused = pause.pause();
+                        let mut can_save = keystore.is_some();
+                        while !shutdown.load(Ordering::Relaxed) {
+                            let choice = prompt_match_choice(
+                                &mut std::io::stdin().lock(),
+                                &mut std::io::stderr(),
+                                can_save,
+                            )
+                            .unwrap_or(MatchChoice::Stop);
+                            match choice {
+                                MatchChoice::KeepMining => break,
+                                MatchChoice::Save => {
+                                    save_to_keystore(keystore.as_ref().unwrap());
+                                    can_save = false;
+                                }
+                                MatchChoice::Stop => shutdown.store(true, Ordering::Relaxed),
+                            }
+                        }
                     }
                 }
             }
-        });
+            iterations.fetch_add(pending, Ordering::Relaxed);
+        }));
     }
 
-    // put main thread to sleep
-    loop {
-        thread::sleep(Duration::from_secs(3600));
+    let save_checkpoint = || {
+        if let (Some(path), Some(progress), Some(start)) =
+            (checkpoint.as_ref(), walk_progress.as_ref(), start_from)
+        {
+            let resume_from = progress
+                .resume_scalar(start)
+                .expect("The sequential search wrapped around the curve order.");
+            if let Err(e) = write_checkpoint(path, &resume_from) {
+                status!(
+                    format,
+                    "WARNING: could not write the checkpoint to {}: {e}",
+                    path.display()
+                );
+            }
+            return Some(resume_from);
+        }
+        None
+    };
+
+    // wait for Ctrl+C, the timeout or the iteration limit
+    let mut last_checkpoint = Instant::now();
+    while !workers.iter().all(|worker| worker.is_finished()) {
+        if timeout.is_some_and(|timeout| now.elapsed() >= timeout) {
+            shutdown.store(true, Ordering::Relaxed);
+        }
+        if let Some(eased) = adaptive
+            .as_ref()
+            .and_then(|adaptive| adaptive.ease(&best_diff, now.elapsed()))
+        {
+            status!(
+                format,
+                "Nothing found for a while, easing the difficulty to {eased}"
+            );
+        }
+        if last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+            save_checkpoint();
+            last_checkpoint = Instant::now();
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    status!(format, "==============================================");
+    status!(
+        format,
+        "Stopped. {}",
+        format_stats(
+            iterations.load(Ordering::Relaxed),
+            now.elapsed().as_secs(),
+            scientific
+        )
+    );
+    if count_only {
+        let keys = iterations.load(Ordering::Relaxed);
+        status!(
+            format,
+            "Counted {} keys at {} h/s, {} matches discarded",
+            group_thousands(keys),
+            group_thousands((keys as f64 / now.elapsed().as_secs_f64()) as u64),
+            group_thousands(matches.load(Ordering::Relaxed))
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+    if find_lowest {
+        let lowest = lowest.lock().unwrap().take();
+        if let Some(lowest) = lowest.as_ref() {
+            status!(format, "Lowest public key found within the budget:");
+            print_lowest(&parsed_args, lowest)?;
+        }
+        *best_match.lock().unwrap() = lowest;
+    } else {
+        status!(
+            format,
+            "{}",
+            format_summary(best_match.lock().unwrap().as_ref())
+        );
+    }
+    // how much work the best key took, for leading zero bit targets
+    if mode == Mode::Difficulty && patterns.is_none() && below.is_none() {
+        if let Some(best) = best_match.lock().unwrap().as_ref() {
+            if best.found_at_iteration > 0 {
+                status!(
+                    format,
+                    "{}",
+                    format_luck(best.found_at_iteration, best.leading_zero_bits)
+                );
+            }
+        }
+    }
+    let unreported = unreported.load(Ordering::Relaxed);
+    if unreported > 0 {
+        status!(
+            format,
+            "Matches below --report-limit {report_limit}, counted but not printed: {unreported}"
+        );
     }
+    let low_entropy = low_entropy.load(Ordering::Relaxed);
+    if low_entropy > 0 {
+        status!(
+            format,
+            "Matches refused by --require-entropy: {low_entropy}"
+        );
+    }
+    let already_known = already_known.load(Ordering::Relaxed);
+    if already_known > 0 {
+        status!(
+            format,
+            "Matches skipped as already known by --known-pubkeys: {already_known}"
+        );
+    }
+    let duplicates = duplicates.load(Ordering::Relaxed);
+    if duplicates > 0 {
+        status!(
+            format,
+            "Keys found again and skipped by --dedup: {duplicates}"
+        );
+    }
+    if let Some(counts) = target_counts.as_ref() {
+        status!(format, "{}", format_target_counts(&counts.snapshot()));
+    }
+    if let Some(resume_from) = save_checkpoint() {
+        status!(
+            format,
+            "Resume this search with --resume-scalar {}",
+            resume_from.display_secret()
+        );
+    }
+    if list_found {
+        status!(format, "==============================================");
+        status!(
+            format,
+            "{}",
+            format_found_table(&found.lock().unwrap(), parsed_args.show_secrets)
+        );
+    }
+    let found_any = best_match.lock().unwrap().is_some();
+    Ok(Outcome::of_run(found_any, interrupted.load(Ordering::Relaxed)).into())
+}
+
+/// Origin of a randomly drawn key, or of one at an offset of a sequential walk
+fn origin_of(scalar_offset: Option<u128>) -> KeyOrigin {
+    match scalar_offset {
+        Some(_) => KeyOrigin::Sequential,
+        None => KeyOrigin::Random,
+    }
+}
+
+/// Print the key kept by --find-lowest in the requested format
+fn print_lowest(parsed_args: &CLIArgs, lowest: &KeyMatch) -> Result<()> {
+    let keys = match &lowest.secret_key {
+        Some(secret_key) => Keys::from_sk_str(secret_key)?,
+        None => Keys::from_public_key(XOnlyPublicKey::from_str(&lowest.pubkey)?),
+    };
+    let key_match = |redact_secret: bool| -> Result<KeyMatch> {
+        Ok(KeyMatch {
+            tag: parsed_args.tag.clone(),
+            origin: lowest.origin,
+            found_at_secs: lowest.found_at_secs,
+            found_at_iteration: lowest.found_at_iteration,
+            ..KeyMatch::new(&keys, "", lowest.leading_zero_bits, None, redact_secret)?
+        }
+        .with_encodings(parsed_args.secret_format, parsed_args.base64))
+    };
+    print_key_match(
+        &key_match(parsed_args.redact_secret)?,
+        &key_match(false)?,
+        parsed_args.format,
+        &parsed_args.output,
+    )?;
+    log_npub(&parsed_args.npub_log, &lowest.npub, parsed_args.format);
+    Ok(())
+}
+
+/// Append a found npub to the --npub-log, if any. A failure only warns, the
+/// key was printed already.
+fn log_npub(npub_log: &Option<std::path::PathBuf>, npub: &str, format: OutputFormat) {
+    if let Some(path) = npub_log {
+        if let Err(e) = append_npub(path, npub) {
+            status!(
+                format,
+                "WARNING: could not append the npub to {} ({e})",
+                path.display()
+            );
+        }
+    }
+}
+
+/// Generate and print a single key, for --just-generate
+fn generate_one(parsed_args: &CLIArgs) -> Result<Outcome> {
+    let format = parsed_args.format;
+    let mut rng = new_rng(parsed_args.rng, parsed_args.seed, 0);
+    let (keys, mnemonic) = if parsed_args.word_count > 0 {
+        let mnemonic = generate_mnemonic_with(&mut rng, parsed_args.word_count)?;
+        let keys = Keys::from_mnemonic(
+            mnemonic.to_string(),
+            Some(parsed_args.mnemonic_passphrase.clone()),
+        )?;
+        (keys, Some(mnemonic))
+    } else {
+        let (secret_key, _) = Secp256k1::new().generate_keypair(&mut rng);
+        (Keys::new(secret_key), None)
+    };
+    let leading_zeroes = get_leading_zero_bits(&keys.public_key().serialize());
+    let key_match = |redact_secret: bool| -> Result<KeyMatch> {
+        Ok(KeyMatch {
+            tag: parsed_args.tag.clone(),
+            found_at_iteration: 1,
+            ..KeyMatch::new(&keys, "", leading_zeroes, mnemonic.as_ref(), redact_secret)?
+        }
+        .with_encodings(parsed_args.secret_format, parsed_args.base64))
+    };
+    print_key_match(
+        &key_match(parsed_args.redact_secret)?,
+        &key_match(false)?,
+        format,
+        &parsed_args.output,
+    )?;
+    if parsed_args.qr && parsed_args.redact_secret {
+        status!(
+            format,
+            "QR code of the private key not shown, the secret is redacted"
+        );
+    } else if parsed_args.qr {
+        print_qr(keys.secret_key()?, format, parsed_args.qr_style())?;
+    }
+    Ok(Outcome::Found)
+}
+
+/// The filter of the keys already reported, with --dedup
+fn dedup_filter(parsed_args: &CLIArgs) -> Option<Arc<BloomFilter>> {
+    if !parsed_args.dedup {
+        return None;
+    }
+    let filter = BloomFilter::with_capacity(
+        parsed_args.dedup_capacity as usize,
+        DEDUP_FALSE_POSITIVE_RATE,
+    );
+    status!(
+        parsed_args.format,
+        "Skipping keys already reported, in a filter of {} KiB for {} keys",
+        filter.size_bytes().div_ceil(1024),
+        group_thousands(parsed_args.dedup_capacity)
+    );
+    Some(Arc::new(filter))
+}
+
+/// Sweep the accounts of every mnemonic of a file for the vanity npub targets
+fn sweep_mnemonic_file(
+    parsed_args: &CLIArgs,
+    path: &std::path::Path,
+    prefixes: &[String],
+    suffixes: &[String],
+    known_pubkeys: Option<&HashSet<XOnlyPublicKey>>,
+) -> Result<Outcome> {
+    let format = parsed_args.format;
+    let (mnemonics, rejected) = read_mnemonics(std::io::BufReader::new(std::fs::File::open(path)?));
+    for (line, error) in rejected {
+        status!(
+            format,
+            "WARNING: skipped line {line} of {}: {error}",
+            path.display()
+        );
+    }
+    status!(
+        format,
+        "Sweeping {} accounts of {} mnemonics from {}",
+        parsed_args.accounts,
+        mnemonics.len(),
+        path.display()
+    );
+
+    let dedup = dedup_filter(parsed_args);
+    let npub_matches = npub_matcher(parsed_args.npub_anchored_suffix);
+    let mut found_any = false;
+    for (line, mnemonic) in mnemonics {
+        let found = sweep_accounts(
+            &mnemonic,
+            &parsed_args.mnemonic_passphrase,
+            parsed_args.accounts,
+            |keys| {
+                match_hrps(keys, &parsed_args.hrps, parsed_args.all_hrps, |bech_key| {
+                    npub_matches(bech_key, prefixes, suffixes, parsed_args.match_any)
+                })
+            },
+        )?;
+        for (account, keys, matched) in found {
+            let leading_zeroes = get_leading_zero_bits(&keys.public_key().serialize());
+            if parsed_args.require_entropy
+                && !has_sufficient_entropy(&keys.secret_key()?.secret_bytes())
+            {
+                status!(
+                    format,
+                    "Refused mnemonic on line {line}, account {account} as its secret has too little entropy"
+                );
+                continue;
+            }
+            if known_pubkeys.is_some_and(|known| known.contains(&keys.public_key())) {
+                status!(
+                    format,
+                    "Mnemonic on line {line}, account {account} gives a key already known"
+                );
+                continue;
+            }
+            if let Some(dedup) = dedup.as_ref() {
+                if !dedup.insert(&keys.public_key().serialize()) {
+                    status!(
+                        format,
+                        "Mnemonic on line {line}, account {account} gives a key already reported"
+                    );
+                    continue;
+                }
+            }
+            found_any = true;
+            let key_match = KeyMatch {
+                derivation_path: Some(nip06_path(account)),
+                tag: parsed_args.tag.clone(),
+                ..KeyMatch::new(
+                    &keys,
+                    &matched,
+                    leading_zeroes,
+                    Some(&mnemonic),
+                    parsed_args.redact_secret,
+                )?
+            };
+            let unredacted = KeyMatch {
+                derivation_path: Some(nip06_path(account)),
+                tag: parsed_args.tag.clone(),
+                ..KeyMatch::new(&keys, &matched, leading_zeroes, Some(&mnemonic), false)?
+            };
+            status!(format, "==============================================");
+            status!(format, "Mnemonic on line {line}, account {account}:");
+            log_npub(&parsed_args.npub_log, &unredacted.npub, format);
+            print_key_match(
+                &key_match.with_encodings(parsed_args.secret_format, parsed_args.base64),
+                &unredacted.with_encodings(parsed_args.secret_format, parsed_args.base64),
+                format,
+                &parsed_args.output,
+            )?;
+        }
+    }
+    Ok(Outcome::of_run(found_any, false))
+}
+
+/// Serve ranges of sequential keys to the workers and print the keys they find
+#[cfg(feature = "distributed")]
+fn coordinate(parsed_args: &CLIArgs) -> Result<()> {
+    let format = parsed_args.format;
+    let redact_secret = parsed_args.redact_secret;
+    let output = parsed_args.output.clone();
+    let npub_log = parsed_args.npub_log.clone();
+    let start = match parsed_args.start_from {
+        Some(start) => start,
+        None => Keys::generate().secret_key()?,
+    };
+
+    let coordinator = Coordinator::bind(&parsed_args.coordinate, start, parsed_args.range_size)?;
+    status!(
+        format,
+        "Coordinating workers on {}, handing out ranges of {} keys",
+        coordinator.local_addr()?,
+        parsed_args.range_size
+    );
+
+    coordinator.serve(Arc::new(move |worker_id, secret_key| {
+        let keys = Keys::new(*secret_key);
+        let leading_zeroes = get_leading_zero_bits(&keys.public_key().serialize());
+        status!(format, "==============================================");
+        status!(format, "Found by worker {worker_id}:");
+        let key_match = |redact_secret: bool| KeyMatch {
+            origin: KeyOrigin::Sequential,
+            ..KeyMatch::new(&keys, "", leading_zeroes, None, redact_secret).unwrap()
+        };
+        print_key_match(
+            &key_match(redact_secret),
+            &key_match(false),
+            format,
+            &output,
+        )
+        .unwrap();
+        log_npub(&npub_log, &keys.public_key().to_bech32().unwrap(), format);
+    }))
 }
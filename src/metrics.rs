@@ -0,0 +1,136 @@
+//! Prometheus metrics of a mining run, built with the `metrics` feature.
+//! `--metrics-addr` serves them as plain text to any HTTP `GET /metrics`.
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use nostr::prelude::*;
+
+use crate::utils::KeyMatch;
+
+/// The shared state of the run the metrics are read from
+pub struct Metrics {
+    iterations: Arc<AtomicU64>,
+    matches: Arc<AtomicU64>,
+    best_match: Arc<Mutex<Option<KeyMatch>>>,
+    started: Instant,
+    /// Iterations at the previous scrape, to report the current hashrate
+    last_scrape: Mutex<(Instant, u64)>,
+}
+
+impl Metrics {
+    pub fn new(
+        iterations: Arc<AtomicU64>,
+        matches: Arc<AtomicU64>,
+        best_match: Arc<Mutex<Option<KeyMatch>>>,
+        started: Instant,
+    ) -> Self {
+        Self {
+            iterations,
+            matches,
+            best_match,
+            started,
+            last_scrape: Mutex::new((started, 0)),
+        }
+    }
+
+    /// The metrics in the Prometheus text format. The hashrate is measured
+    /// since the previous scrape, or since the start for the first one.
+    pub fn render(&self) -> String {
+        let iterations = self.iterations.load(Ordering::Relaxed);
+        let hashrate = {
+            let mut last_scrape = self.last_scrape.lock().unwrap();
+            let (at, scraped) = *last_scrape;
+            *last_scrape = (Instant::now(), iterations);
+            (iterations - scraped) as f64 / at.elapsed().as_secs_f64().max(f64::EPSILON)
+        };
+        let best_bits = self
+            .best_match
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, |best| best.leading_zero_bits);
+
+        let mut out = String::new();
+        let metrics: [(&str, &str, &str, f64); 5] = [
+            (
+                "rana_iterations_total",
+                "counter",
+                "Keys generated and tested",
+                iterations as f64,
+            ),
+            (
+                "rana_hashrate",
+                "gauge",
+                "Keys tested per second since the previous scrape",
+                hashrate,
+            ),
+            (
+                "rana_best_leading_zero_bits",
+                "gauge",
+                "Leading zero bits of the rarest key found",
+                best_bits as f64,
+            ),
+            (
+                "rana_matches_total",
+                "counter",
+                "Keys found matching the target",
+                self.matches.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "rana_uptime_seconds",
+                "gauge",
+                "Seconds since mining started",
+                self.started.elapsed().as_secs_f64(),
+            ),
+        ];
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            let _ = writeln!(out, "{name} {value}");
+        }
+        out
+    }
+}
+
+/// Serve the metrics on `addr` from a background thread and return the
+/// address it listens on
+pub fn serve_metrics(addr: &str, metrics: Arc<Metrics>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().map_while(|stream| stream.ok()) {
+            // a broken scrape only loses its own response
+            let _ = handle_scrape(stream, &metrics);
+        }
+    });
+    Ok(local_addr)
+}
+
+fn handle_scrape(stream: TcpStream, metrics: &Metrics) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream);
+    reader.read_line(&mut request_line)?;
+    // skip the headers up to the blank line
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let (status, body) = match request_line.split_whitespace().nth(1) {
+        Some("/metrics") | Some("/") => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", String::from("Not found, try /metrics\n")),
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
@@ -1,49 +1,198 @@
-use std::process::exit;
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
 
+use bip39::{Language, Mnemonic};
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey};
+use bitcoin::Network;
 use nostr::prelude::*;
 
 use crate::cli::CLIArgs;
 
-pub fn handle_mnemonic(parsed_args: &CLIArgs) {
-    if parsed_args.word_count > 0 {
-        let mut word_count = parsed_args.word_count;
-        if parsed_args.word_count == 0 || parsed_args.word_count > 24 {
-            word_count = 12;
+/// Word counts of a mnemonic the bip39 crate can read
+pub const WORD_COUNTS: [usize; 3] = [12, 18, 24];
+
+/// Why a mnemonic could not be generated, read or turned into keys
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MnemonicError {
+    /// Not one of the BIP-39 word counts
+    InvalidWordCount(usize),
+    /// A word missing from the word list, or a failed checksum
+    InvalidWords(String),
+    /// The keys could not be derived from the seed
+    Derivation(String),
+}
+
+impl fmt::Display for MnemonicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MnemonicError::InvalidWordCount(count) => {
+                write!(f, "A mnemonic has 12, 18 or 24 words, not {count}")
+            }
+            MnemonicError::InvalidWords(reason) => write!(f, "Invalid mnemonic: {reason}"),
+            MnemonicError::Derivation(reason) => {
+                write!(f, "Could not derive keys from the mnemonic: {reason}")
+            }
         }
-        let mnemonic = Keys::generate_mnemonic(word_count).expect("Couldn't not generate mnemonic");
-        let keys = Keys::from_mnemonic(mnemonic.to_string(), None).expect("");
-
-        println!(
-            "Mnemonic: {}\nPublic key: {}\nPrivate key: {}",
-            mnemonic,
-            keys.public_key()
-                .to_bech32()
-                .expect("Could not get public key bech32 conversion"),
-            keys.secret_key()
-                .expect("Could not get secret key")
-                .to_bech32()
-                .expect("Could not get secret key bech32 conversion")
-        );
-        exit(0)
-    }
-
-    if !parsed_args.mnemonic.is_empty() {
-        let keys = Keys::from_mnemonic(
-            parsed_args.mnemonic.to_string(),
-            Some(parsed_args.mnemonic_passphrase.to_string()),
-        )
-        .expect("Error creating key pair from mnemonic");
-
-        println!(
-            "Public key: {}\nPrivate key: {}",
-            keys.public_key()
-                .to_bech32()
-                .expect("Could not get public key bech32 conversion"),
-            keys.secret_key()
-                .expect("Could not get secret key")
-                .to_bech32()
-                .expect("Could not get secret key bech32 conversion")
-        );
-        exit(0);
     }
 }
+
+impl std::error::Error for MnemonicError {}
+
+fn derivation_error(e: impl fmt::Display) -> MnemonicError {
+    MnemonicError::Derivation(e.to_string())
+}
+
+/// Read a mnemonic, telling a wrong number of words apart from wrong words
+pub fn parse_mnemonic(phrase: &str) -> std::result::Result<Mnemonic, MnemonicError> {
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    if !WORD_COUNTS.contains(&words.len()) {
+        return Err(MnemonicError::InvalidWordCount(words.len()));
+    }
+    Mnemonic::from_str(phrase).map_err(|e| match e {
+        bip39::Error::UnknownWord(index) => {
+            MnemonicError::InvalidWords(format!("unknown word '{}'", words[index]))
+        }
+        e => MnemonicError::InvalidWords(e.to_string()),
+    })
+}
+
+/// What `validate_mnemonic` tells of valid words with a wrong checksum
+const BAD_CHECKSUM: &str = "Every word is valid, but the checksum does not match: a word was mistyped as another one or the words are out of order";
+
+/// Check a typed mnemonic for `rana mnemonic validate`. Returns its word
+/// count and language, or what is wrong with it, down to the first word
+/// that is in no word list.
+pub fn validate_mnemonic(phrase: &str) -> std::result::Result<(usize, Language), String> {
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    if !WORD_COUNTS.contains(&words.len()) {
+        return Err(MnemonicError::InvalidWordCount(words.len()).to_string());
+    }
+    match Mnemonic::parse(phrase) {
+        Ok(mnemonic) => Ok((mnemonic.word_count(), mnemonic.language())),
+        Err(bip39::Error::UnknownWord(index)) => Err(format!(
+            "Word {} of {}, '{}', is not in any BIP-39 word list",
+            index + 1,
+            words.len(),
+            words[index]
+        )),
+        Err(bip39::Error::InvalidChecksum) => Err(BAD_CHECKSUM.to_string()),
+        // words shared by several lists, the checksum usually tells them apart
+        Err(bip39::Error::AmbiguousLanguages(languages)) => {
+            let valid: Vec<Language> = languages
+                .iter()
+                .filter(|language| Mnemonic::parse_in(*language, phrase).is_ok())
+                .collect();
+            match valid.as_slice() {
+                [] => Err(BAD_CHECKSUM.to_string()),
+                [language] => Ok((words.len(), *language)),
+                _ => Err(format!(
+                    "The mnemonic is valid in several languages: {}",
+                    valid
+                        .iter()
+                        .map(|language| language.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )),
+            }
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Generate a new mnemonic of `word_count` words
+pub fn generate_mnemonic(word_count: usize) -> std::result::Result<Mnemonic, MnemonicError> {
+    if !WORD_COUNTS.contains(&word_count) {
+        return Err(MnemonicError::InvalidWordCount(word_count));
+    }
+    Keys::generate_mnemonic(word_count).map_err(derivation_error)
+}
+
+/// Derive the NIP-06 keys of the first account of a mnemonic
+pub fn keys_from_mnemonic(
+    mnemonic: &Mnemonic,
+    passphrase: &str,
+) -> std::result::Result<Keys, MnemonicError> {
+    Keys::from_mnemonic(mnemonic.to_string(), Some(passphrase.to_string()))
+        .map_err(derivation_error)
+}
+
+/// NIP-06 derivation path of the keys of an account
+pub fn nip06_path(account: u32) -> String {
+    format!("m/44'/1237'/{account}'/0/0")
+}
+
+/// Values with the number of the line they were read from
+pub type NumberedLines<T> = Vec<(usize, T)>;
+
+/// Read mnemonics line by line, skipping blank lines and `#` comments.
+/// Returns the valid ones and the rejected ones, with their line numbers.
+pub fn read_mnemonics(reader: impl BufRead) -> (NumberedLines<Mnemonic>, NumberedLines<String>) {
+    let mut mnemonics = Vec::new();
+    let mut rejected = Vec::new();
+    for (index, line) in reader.lines().map_while(|line| line.ok()).enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_mnemonic(line) {
+            Ok(mnemonic) => mnemonics.push((index + 1, mnemonic)),
+            Err(e) => rejected.push((index + 1, e.to_string())),
+        }
+    }
+    (mnemonics, rejected)
+}
+
+/// Derive the NIP-06 keys of the first `accounts` accounts of a mnemonic and
+/// keep the ones accepted by `is_match`, with their account and what matched.
+/// The seed and the common part of the path are only derived once.
+pub fn sweep_accounts(
+    mnemonic: &Mnemonic,
+    passphrase: &str,
+    accounts: u32,
+    is_match: impl Fn(&Keys) -> Option<String>,
+) -> Result<Vec<(u32, Keys, String)>> {
+    let secp = Secp256k1::new();
+    let seed = mnemonic.to_seed(passphrase);
+    let purpose = ExtendedPrivKey::new_master(Network::Bitcoin, &seed)?
+        .derive_priv(&secp, &DerivationPath::from_str("m/44'/1237'")?)?;
+
+    let mut found = Vec::new();
+    for account in 0..accounts {
+        let path = [
+            ChildNumber::from_hardened_idx(account)?,
+            ChildNumber::from_normal_idx(0)?,
+            ChildNumber::from_normal_idx(0)?,
+        ];
+        let keys = Keys::new(purpose.derive_priv(&secp, &path)?.private_key);
+        if let Some(matched) = is_match(&keys) {
+            found.push((account, keys, matched));
+        }
+    }
+    Ok(found)
+}
+
+/// Print the keys of the mnemonic given with --restore and, with -g, of a
+/// newly generated one
+pub fn handle_mnemonic(parsed_args: &CLIArgs) -> std::result::Result<(), MnemonicError> {
+    let bech32 = |keys: &Keys| -> std::result::Result<(String, String), MnemonicError> {
+        let secret_key = keys.secret_key().map_err(derivation_error)?;
+        Ok((
+            keys.public_key().to_bech32().map_err(derivation_error)?,
+            secret_key.to_bech32().map_err(derivation_error)?,
+        ))
+    };
+
+    if parsed_args.word_count > 0 {
+        let mnemonic = generate_mnemonic(parsed_args.word_count)?;
+        let (npub, nsec) = bech32(&keys_from_mnemonic(&mnemonic, "")?)?;
+        println!("Mnemonic: {mnemonic}\nPublic key: {npub}\nPrivate key: {nsec}");
+        return Ok(());
+    }
+
+    let mnemonic = parse_mnemonic(&parsed_args.mnemonic)?;
+    let keys = keys_from_mnemonic(&mnemonic, &parsed_args.mnemonic_passphrase)?;
+    let (npub, nsec) = bech32(&keys)?;
+    println!("Public key: {npub}\nPrivate key: {nsec}");
+    Ok(())
+}
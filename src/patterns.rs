@@ -0,0 +1,119 @@
+//! Targets of a `--pattern-file`, one per line and annotated with their
+//! kind, so hex and npub prefixes are mined for in a single run:
+//!
+//! ```text
+//! # comments and blank lines are skipped
+//! hex:dead
+//! npub:cafe
+//! ```
+
+use std::io::BufRead;
+
+use nostr::prelude::*;
+
+use crate::mnemonic::NumberedLines;
+use crate::utils::{match_npub, matches_hex_prefix, BECH32_BITS_PER_CHAR, BECH32_CHARSET};
+
+/// Hex and npub prefixes mined for together
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Patterns {
+    pub hex: Vec<String>,
+    pub npub: Vec<String>,
+}
+
+impl Patterns {
+    pub fn is_empty(&self) -> bool {
+        self.hex.is_empty() && self.npub.is_empty()
+    }
+
+    /// Parse a `kind:prefix` line into the patterns
+    pub fn add(&mut self, line: &str) -> Result<(), String> {
+        let Some((kind, prefix)) = line.split_once(':') else {
+            return Err(format!(
+                "'{line}' is not of the form hex:prefix or npub:prefix"
+            ));
+        };
+        let prefix = prefix.trim();
+        match kind.trim() {
+            "hex"
+                if (1..=64).contains(&prefix.len())
+                    && prefix.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                self.hex.push(prefix.to_lowercase());
+            }
+            "hex" => {
+                return Err(format!(
+                    "'{prefix}' is not a hex prefix of 1 to 64 characters"
+                ))
+            }
+            "npub"
+                if (1..=59).contains(&prefix.len())
+                    && prefix.chars().all(|c| BECH32_CHARSET.contains(c)) =>
+            {
+                self.npub.push(prefix.to_string());
+            }
+            "npub" => {
+                return Err(format!(
+                    "'{prefix}' is not an npub prefix of 1 to 59 bech32 characters"
+                ))
+            }
+            kind => return Err(format!("unknown pattern kind '{kind}', use hex or npub")),
+        }
+        Ok(())
+    }
+
+    /// The first pattern the key matches, as `hex:prefix` or `npub:prefix`.
+    /// The npub is only encoded when there are npub patterns.
+    pub fn find_match(&self, public_key: &XOnlyPublicKey) -> Option<String> {
+        if let Some(prefix) = self
+            .hex
+            .iter()
+            .find(|prefix| matches_hex_prefix(public_key, prefix))
+        {
+            return Some(format!("hex:{prefix}"));
+        }
+        if self.npub.is_empty() {
+            return None;
+        }
+        let npub = public_key.to_bech32().ok()?;
+        match_npub(&npub, &self.npub, &[], false).map(|prefix| format!("npub:{prefix}"))
+    }
+
+    /// Difficulty of finding a key matching any of the patterns: each hex
+    /// character is 4 bits and each npub character 5, and every pattern
+    /// makes a match more likely
+    pub fn pow_difficulty(&self) -> u8 {
+        let chance: f64 = self
+            .hex
+            .iter()
+            .map(|prefix| prefix.len() * 4)
+            .chain(
+                self.npub
+                    .iter()
+                    .map(|prefix| prefix.len() * BECH32_BITS_PER_CHAR),
+            )
+            .map(|bits| 0.5_f64.powi(bits as i32))
+            .sum();
+        if chance <= 0.0 {
+            return 0;
+        }
+        (-chance.log2()).round().clamp(0.0, u8::MAX as f64) as u8
+    }
+}
+
+/// Read the patterns of a file line by line, skipping blank lines and `#`
+/// comments. Returns them with the rejected lines and their line numbers.
+pub fn read_patterns(reader: impl BufRead) -> (Patterns, NumberedLines<String>) {
+    let mut patterns = Patterns::default();
+    let mut rejected = Vec::new();
+    for (index, line) in reader.lines().map_while(|line| line.ok()).enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Err(e) = patterns.add(line) {
+            rejected.push((index + 1, e));
+        }
+    }
+    (patterns, rejected)
+}
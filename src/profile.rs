@@ -0,0 +1,23 @@
+//! Sampling CPU profiler for contributors, built with the `profile` feature.
+//! Nothing is sampled unless `--profile-cpu` is given.
+
+use std::fs::File;
+use std::thread;
+use std::time::Duration;
+
+use nostr::prelude::*;
+
+/// Samples per second taken from every thread
+pub const PROFILE_FREQUENCY: i32 = 997;
+
+/// Sample all threads for `duration` and write the flamegraph SVG to `path`
+pub fn profile_cpu(path: &str, duration: Duration) -> Result<()> {
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(PROFILE_FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()?;
+    thread::sleep(duration);
+    let report = guard.report().build()?;
+    report.flamegraph(File::create(path)?)?;
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use nostr::prelude::*;
+
+/// Build a scalar from a small integer
+pub fn scalar_from_u64(value: u64) -> Scalar {
+    let mut bytes = [0_u8; 32];
+    bytes[24..].copy_from_slice(&value.to_be_bytes());
+    Scalar::from_be_bytes(bytes).expect("a u64 is always below the curve order")
+}
+
+/// Deterministic walk over secret keys: `start + offset`, then `step` more on
+/// every call, wrapping around the curve order.
+///
+/// Sums landing exactly on the curve order (the invalid zero key) are skipped,
+/// so every key returned is valid. Each item carries its offset from `start`.
+pub struct ScalarWalk {
+    current: Option<SecretKey>,
+    offset: u128,
+    step: u64,
+    tweak: Scalar,
+}
+
+impl ScalarWalk {
+    pub fn new(start: SecretKey, offset: u64, step: u64) -> Self {
+        assert!(step > 0, "The scalar walk step must be greater than 0");
+        let current = if offset == 0 {
+            Some(start)
+        } else {
+            start.add_tweak(&scalar_from_u64(offset)).ok()
+        };
+
+        Self {
+            current,
+            offset: offset as u128,
+            step,
+            tweak: scalar_from_u64(step),
+        }
+    }
+}
+
+impl Iterator for ScalarWalk {
+    type Item = (SecretKey, u128);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let current = self.current;
+            let offset = self.offset;
+
+            self.offset += self.step as u128;
+            self.current = match current {
+                Some(secret_key) => secret_key.add_tweak(&self.tweak).ok(),
+                // stepping from zero lands on the step itself
+                None => SecretKey::from_slice(&self.tweak.to_be_bytes()).ok(),
+            };
+
+            if let Some(secret_key) = current {
+                return Some((secret_key, offset));
+            }
+        }
+    }
+}
+
+/// How often a sequential search saves its `--checkpoint`
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How far each thread of a sequential search got, to resume it later.
+///
+/// Thread `t` of `threads` searches the offsets `t * stride + k * walk_step`
+/// with `walk_step = threads * stride`, so once every thread tested at least
+/// `k` keys all offsets below `k * walk_step` are done and the search can
+/// restart from `start + k * walk_step` with the same layout.
+pub struct WalkProgress {
+    stride: u64,
+    walk_step: u64,
+    tested: Vec<AtomicU64>,
+}
+
+impl WalkProgress {
+    pub fn new(threads: usize, stride: u64, walk_step: u64) -> Self {
+        Self {
+            stride,
+            walk_step,
+            tested: (0..threads).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Record that `thread` tested the key at `offset` from the start
+    pub fn record(&self, thread: usize, offset: u128) {
+        let own = offset - (thread as u128 * self.stride as u128);
+        let tested = (own / self.walk_step as u128 + 1) as u64;
+        // each thread only writes its own slot, read by the checkpoints
+        self.tested[thread].fetch_max(tested, Ordering::Relaxed);
+    }
+
+    /// Offset from the start below which every key was tested
+    pub fn resume_offset(&self) -> u64 {
+        let tested = self
+            .tested
+            .iter()
+            .map(|t| t.load(Ordering::Relaxed))
+            .min()
+            .unwrap_or(0);
+        tested.saturating_mul(self.walk_step)
+    }
+
+    /// Key the search can resume from
+    pub fn resume_scalar(&self, start: SecretKey) -> Option<SecretKey> {
+        ScalarWalk::new(start, self.resume_offset(), 1)
+            .next()
+            .map(|(key, _)| key)
+    }
+}
+
+/// Write the scalar to resume from to `path`, through a temporary file so an
+/// interrupted write never leaves a truncated checkpoint
+pub fn write_checkpoint(path: &Path, resume_from: &SecretKey) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, format!("{}\n", resume_from.display_secret()))?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
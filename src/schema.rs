@@ -0,0 +1,120 @@
+//! JSON Schema of the key matches printed with `--format jsonl`, for tools
+//! that validate rana's output. Its fields and their types come from the
+//! serialization of `KeyMatch`, so the schema follows the struct. The summary
+//! at the end of a run is text on stderr, never JSON, so it has no schema.
+
+use bip39::Mnemonic;
+use nostr::prelude::*;
+use serde_json::{json, Map, Value};
+
+use crate::utils::{KeyMatch, KeyOrigin, KEY_SCHEME, REDACTED};
+
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Key the example matches of the schema are made of, any valid one will do
+const EXAMPLE_SECRET_KEY: &str = "6b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e";
+const EXAMPLE_MNEMONIC: &str =
+    "congress evoke onion donate fantasy soccer project fiction envelope body faith mean";
+
+fn example_keys() -> Keys {
+    Keys::from_sk_str(EXAMPLE_SECRET_KEY).expect("The example secret key is valid")
+}
+
+/// A public-only match, with only the fields every match has
+pub fn minimal_key_match() -> KeyMatch {
+    let public_only = Keys::from_public_key(example_keys().public_key());
+    KeyMatch::new(&public_only, "", 0, None, false).expect("The example key encodes")
+}
+
+/// A match with every optional field set, whose serialization has all the
+/// fields a match can have
+pub fn full_key_match() -> KeyMatch {
+    let mnemonic = Mnemonic::parse(EXAMPLE_MNEMONIC).expect("The example mnemonic is valid");
+    let mut key_match = KeyMatch::new(&example_keys(), "rana", 3, Some(&mnemonic), false)
+        .expect("The example key encodes")
+        .with_base64();
+    key_match.tier = Some("gold".to_string());
+    key_match.tag = Some("example".to_string());
+    key_match.nprofile = Some("nprofile1".to_string());
+    key_match.found_at_secs = 1.5;
+    key_match.found_at_iteration = 1;
+    key_match
+}
+
+fn fields(key_match: KeyMatch) -> Map<String, Value> {
+    match serde_json::to_value(key_match) {
+        Ok(Value::Object(fields)) => fields,
+        _ => unreachable!("A key match serializes to an object"),
+    }
+}
+
+/// What a field of a match holds, `None` for a field not described yet
+pub fn key_match_field_description(field: &str) -> Option<String> {
+    let secret = |description: &str| {
+        Some(format!(
+            "{description}. \"{REDACTED}\" with --redact-secret"
+        ))
+    };
+    let description = match field {
+        "pubkey" => "Public key in hex",
+        "npub" => "Public key as an npub",
+        "scheme" => {
+            "Signature scheme of the key, BIP-340 Schnorr with an x-only secp256k1 public key"
+        }
+        "origin" => "How the secret was produced. Mnemonic keys also have a derivation_path",
+        "pubkey_base64" => "Raw public key bytes in base64, with --base64",
+        "secret_key" => return secret("Secret key in hex"),
+        "secret_key_base64" => return secret("Raw secret key bytes in base64, with --base64"),
+        "nsec" => return secret("Secret key as an nsec"),
+        "mnemonic" => return secret("Mnemonic the key was derived from"),
+        "derivation_path" => "NIP-06 path of the key within its mnemonic",
+        "leading_zero_bits" => "Leading zero bits of the public key",
+        "vanity" => "Vanity target the npub matched",
+        "tier" => "Difficulty tier the key reached, with --tiers",
+        "tag" => "Label given to the run with --tag",
+        "nprofile" => "Public key with relay hints, with --nprofile-relays",
+        "found_at_secs" => "Seconds into the run at which the key was found",
+        "found_at_iteration" => "Keys tried when the key was found",
+        _ => return None,
+    };
+    Some(description.to_string())
+}
+
+/// The schema of one field, from the value it has in an example match
+fn field_schema(field: &str, value: &Value) -> Value {
+    let mut schema = match (field, value) {
+        ("scheme", _) => json!({ "const": KEY_SCHEME }),
+        ("origin", _) => json!({ "enum": KeyOrigin::ALL.map(KeyOrigin::as_str) }),
+        ("leading_zero_bits", _) => json!({ "type": "integer", "minimum": 0, "maximum": u8::MAX }),
+        (_, Value::Number(number)) if number.is_u64() => json!({ "type": "integer", "minimum": 0 }),
+        (_, Value::Number(_)) => json!({ "type": "number", "minimum": 0 }),
+        (_, Value::Bool(_)) => json!({ "type": "boolean" }),
+        _ => json!({ "type": "string" }),
+    };
+    if let Some(description) = key_match_field_description(field) {
+        schema["description"] = description.into();
+    }
+    schema
+}
+
+/// The JSON Schema of a line of `--format jsonl`
+pub fn key_match_schema() -> Value {
+    let properties: Map<String, Value> = fields(full_key_match())
+        .iter()
+        .map(|(field, value)| (field.clone(), field_schema(field, value)))
+        .collect();
+    // the optional fields are left out when unset
+    let required: Vec<String> = fields(minimal_key_match())
+        .into_iter()
+        .map(|(field, _)| field)
+        .collect();
+    json!({
+        "$schema": SCHEMA_DIALECT,
+        "title": "KeyMatch",
+        "description": "A key found by rana. The secret fields are left out with --pubkey-only.",
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false
+    })
+}
@@ -1,10 +1,2569 @@
-#[cfg(test)]
-mod tests {
-    use crate::cli::CLIArgs;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-    #[test]
-    fn cli_tests() {
-        use clap::CommandFactory;
-        CLIArgs::command().debug_assert();
+use bip39::Mnemonic;
+use nostr::prelude::*;
+
+use crate::bloom::*;
+use crate::card::*;
+use crate::cli::*;
+use crate::keystore::*;
+use crate::patterns::*;
+use crate::scalar::*;
+use crate::schema::*;
+use crate::utils::*;
+
+#[test]
+fn cli_tests() {
+    use clap::CommandFactory;
+    CLIArgs::command().debug_assert();
+}
+
+#[test]
+fn target_lists_are_trimmed() {
+    let (targets, trimmed) = split_targets("foo, bar , baz,,  ,qux");
+    assert_eq!(targets, vec!["foo", "bar", "baz", "qux"]);
+    assert_eq!(trimmed, vec![" bar ", " baz", "  "]);
+
+    let (targets, trimmed) = split_targets("rana,h0dl");
+    assert_eq!(targets, vec!["rana", "h0dl"]);
+    assert!(trimmed.is_empty());
+    assert!(split_targets("").0.is_empty());
+}
+
+#[test]
+fn mode_is_detected_from_arguments() {
+    let npubs = vec![String::from("rana")];
+    assert_eq!(resolve_mode(None, "", &[], &[]), Ok(Mode::Difficulty));
+    assert_eq!(resolve_mode(None, "dead", &[], &[]), Ok(Mode::Hex));
+    assert_eq!(resolve_mode(None, "", &npubs, &[]), Ok(Mode::Npub));
+    assert_eq!(resolve_mode(None, "", &[], &npubs), Ok(Mode::Npub));
+}
+
+#[test]
+fn explicit_mode_with_inputs() {
+    let npubs = vec![String::from("rana")];
+    assert_eq!(
+        resolve_mode(Some(Mode::Difficulty), "", &[], &[]),
+        Ok(Mode::Difficulty)
+    );
+    assert_eq!(
+        resolve_mode(Some(Mode::Hex), "dead", &[], &[]),
+        Ok(Mode::Hex)
+    );
+    assert_eq!(
+        resolve_mode(Some(Mode::Npub), "", &npubs, &[]),
+        Ok(Mode::Npub)
+    );
+}
+
+#[test]
+fn npub_mode_without_prefixes() {
+    let error = resolve_mode(Some(Mode::Npub), "", &[], &[]).unwrap_err();
+    assert!(error.contains("--mode npub requires at least one npub prefix or suffix"));
+}
+
+#[test]
+fn hex_mode_without_prefix() {
+    let error = resolve_mode(Some(Mode::Hex), "", &[], &[]).unwrap_err();
+    assert!(error.contains("--mode hex requires a vanity prefix"));
+}
+
+#[test]
+fn difficulty_mode_with_vanity() {
+    let error = resolve_mode(Some(Mode::Difficulty), "dead", &[], &[]).unwrap_err();
+    assert!(error.contains("--mode difficulty cannot be combined with vanity targets"));
+}
+
+#[test]
+fn stats_formatting() {
+    assert_eq!(
+        format_stats(1234, 0, true),
+        "1234 iterations (about 1x10^3 hashes) in 0 seconds. Avg rate 1234 hashes/second"
+    );
+    assert_eq!(
+        format_stats(0, 10, true),
+        "0 iterations (about 0x10^0 hashes) in 10 seconds. Avg rate 0 hashes/second"
+    );
+    assert_eq!(
+        format_stats(1234567, 2, false),
+        "1,234,567 iterations in 2 seconds. Avg rate 617283 hashes/second"
+    );
+}
+
+#[test]
+fn thousands_grouping() {
+    assert_eq!(group_thousands(0), "0");
+    assert_eq!(group_thousands(999), "999");
+    assert_eq!(group_thousands(1000), "1,000");
+    assert_eq!(group_thousands(1234567), "1,234,567");
+    assert_eq!(group_thousands(u64::MAX), "18,446,744,073,709,551,615");
+}
+
+#[test]
+fn stats_formatting_at_the_ceiling() {
+    assert_eq!(
+        format_stats(u64::MAX, u64::MAX, true),
+        "18446744073709551615 iterations (about 1x10^19 hashes) in 18446744073709551615 seconds. Avg rate 1 hashes/second"
+    );
+    assert!(
+        format_stats(u64::MAX, 0, false).ends_with("Avg rate 18446744073709551615 hashes/second")
+    );
+}
+
+#[test]
+fn npub_match_all_requires_prefix_and_suffix() {
+    let prefixes = vec![String::from("rana"), String::from("h0dl")];
+    let suffixes = vec![String::from("end")];
+    assert_eq!(
+        match_npub("npub1h0dlxyzend", &prefixes, &suffixes, false),
+        Some(String::from("h0dl...end"))
+    );
+    assert_eq!(
+        match_npub("npub1ranaxyzabc", &prefixes, &suffixes, false),
+        None
+    );
+    assert_eq!(match_npub("npub1xyzend", &prefixes, &suffixes, false), None);
+}
+
+#[test]
+fn npub_match_any_accepts_prefix_or_suffix() {
+    let prefixes = vec![String::from("rana")];
+    let suffixes = vec![String::from("end")];
+    assert_eq!(
+        match_npub("npub1ranaxyzabc", &prefixes, &suffixes, true),
+        Some(String::from("rana..."))
+    );
+    assert_eq!(
+        match_npub("npub1xyzend", &prefixes, &suffixes, true),
+        Some(String::from("...end"))
+    );
+    assert_eq!(match_npub("npub1xyzabc", &prefixes, &suffixes, true), None);
+}
+
+#[test]
+fn npub_match_single_category() {
+    let targets = vec![String::from("rana")];
+    assert_eq!(
+        match_npub("npub1ranaxyz", &targets, &[], false),
+        Some(String::from("rana"))
+    );
+    assert_eq!(
+        match_npub("npub1xyzrana", &[], &targets, true),
+        Some(String::from("rana"))
+    );
+    assert_eq!(match_npub("npub1xyzrana", &targets, &[], false), None);
+}
+
+const TEST_SECRET_KEY: &str = "6b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e";
+
+#[test]
+fn profile_preview_id_is_stable() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let created_at = Timestamp::from(1_000_000);
+    let first = profile_preview_id(&keys.public_key(), &Metadata::new(), created_at).unwrap();
+    let second = profile_preview_id(&keys.public_key(), &Metadata::new(), created_at).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(
+        first.to_hex(),
+        "ddcdf58d1accc9ae060aba293ead69d65e87611cb945993de391a1a54978a49d"
+    );
+
+    let named = Metadata::new().name("rana");
+    assert_ne!(
+        profile_preview_id(&keys.public_key(), &named, created_at).unwrap(),
+        first
+    );
+}
+
+#[test]
+fn nak_format_is_the_bare_hex_secret() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let output = format_key_match(
+        &KeyMatch::new(&keys, "", 3, None, false).unwrap(),
+        OutputFormat::Nak,
+    )
+    .unwrap();
+    assert_eq!(output.lines().count(), 1);
+    assert_eq!(output, TEST_SECRET_KEY);
+    assert_eq!(output.len(), 64);
+    assert!(output.chars().all(|c| c.is_ascii_hexdigit()));
+}
+
+#[test]
+fn plain_format_lists_all_keys() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let output = format_key_match(
+        &KeyMatch::new(&keys, "", 3, None, false).unwrap(),
+        OutputFormat::Plain,
+    )
+    .unwrap();
+    assert!(output.starts_with("Leading zero bits:         3\n"));
+    assert!(output.contains(TEST_SECRET_KEY));
+    assert!(output.contains("npub18pwr5mkqh82h5sesm0tzsjvfhedaqrjpc56lnj3ek6h8c5sms8xsn5yj00"));
+    assert!(output.contains(&keys.secret_key().unwrap().to_bech32().unwrap()));
+}
+
+#[test]
+fn fuzzy_npub_prefixes() {
+    let npub = "npub18pwr5mkqh82h5sesm0tzsjvfhedaqrjpc56lnj3ek6h8c5sms8xsn5yj00";
+    assert_eq!(
+        match_npub_fuzzy(npub, "8pwr5", 0),
+        Some((String::from("8pwr5"), 0))
+    );
+    assert_eq!(match_npub_fuzzy(npub, "8pwr4", 0), None);
+    assert_eq!(
+        match_npub_fuzzy(npub, "8pwr4", 1),
+        Some((String::from("8pwr5"), 1))
+    );
+    assert_eq!(
+        match_npub_fuzzy(npub, "rpwr5", 1),
+        Some((String::from("8pwr5"), 1))
+    );
+    assert_eq!(match_npub_fuzzy(npub, "rpwr4", 1), None);
+    // longer than the key itself
+    assert_eq!(match_npub_fuzzy("npub1abc", "abcd", 1), None);
+}
+
+#[test]
+fn fuzzy_pow_difficulty_is_below_the_exact_one() {
+    assert_eq!(fuzzy_pow_difficulty(4, 0), 20);
+    // 1 + 4 * 31 = 125 strings around the target, almost 7 bits
+    assert_eq!(fuzzy_pow_difficulty(4, 1), 13);
+    assert!(fuzzy_pow_difficulty(10, 2) < fuzzy_pow_difficulty(10, 1));
+}
+
+#[test]
+fn prefixes_read_from_stdin_join_the_search() {
+    use std::io::Cursor;
+    use std::sync::RwLock;
+
+    let npub = "npub18pwr5mkqh82h5sesm0tzsjvfhedaqrjpc56lnj3ek6h8c5sms8xsn5yj00";
+    let targets = RwLock::new(vec![String::from("rana")]);
+    assert_eq!(match_npub(npub, &targets.read().unwrap(), &[], false), None);
+
+    let input = Cursor::new("h0dl\n\n  8pwr5 \nnot-bech32!\n");
+    let rejected = read_prefixes(input, &targets, OutputFormat::Plain);
+    assert_eq!(rejected, vec![String::from("not-bech32!")]);
+    assert_eq!(*targets.read().unwrap(), vec!["rana", "h0dl", "8pwr5"]);
+    assert_eq!(
+        match_npub(npub, &targets.read().unwrap(), &[], false),
+        Some(String::from("8pwr5"))
+    );
+}
+
+#[test]
+fn raw_secp256k1_and_nostr_keys_agree() {
+    let secp = Secp256k1::new();
+    for secret in [TEST_SECRET_KEY, MAX_SECRET_KEY] {
+        let secret_key = SecretKey::from_str(secret).unwrap();
+        let raw = PublicKey::from_secret_key(&secp, &secret_key)
+            .x_only_public_key()
+            .0;
+        let keys = Keys::new(secret_key);
+        assert_eq!(raw, keys.public_key());
+        assert_eq!(
+            raw.to_bech32().unwrap(),
+            keys.public_key().to_bech32().unwrap()
+        );
+    }
+}
+
+#[test]
+fn bits_map_to_the_highest_tier_reached() {
+    let tiers = parse_tiers("24:gold, 16:bronze,20:silver").unwrap();
+    assert_eq!(tiers.to_string(), "16:bronze,20:silver,24:gold");
+    assert_eq!((tiers.lowest(), tiers.highest()), (16, 24));
+    assert_eq!(tiers.label_for(15), None);
+    assert_eq!(tiers.label_for(16), Some("bronze"));
+    assert_eq!(tiers.label_for(19), Some("bronze"));
+    assert_eq!(tiers.label_for(20), Some("silver"));
+    assert_eq!(tiers.label_for(31), Some("gold"));
+
+    assert!(parse_tiers("").is_err());
+    assert!(parse_tiers("16").is_err());
+    assert!(parse_tiers("0:none").is_err());
+    assert!(parse_tiers("16:bronze,16:again").is_err());
+}
+
+#[test]
+fn tier_labels_are_printed() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let mut key_match = KeyMatch::new(&keys, "", 20, None, false).unwrap();
+    key_match.tier = Some(String::from("silver"));
+    let plain = format_key_match(&key_match, OutputFormat::Plain).unwrap();
+    assert!(plain.contains("Difficulty tier:           silver"));
+    let json = format_key_match(&key_match, OutputFormat::Jsonl).unwrap();
+    assert!(json.contains(r#""tier":"silver""#));
+}
+
+#[test]
+fn keys_are_compared_against_a_threshold() {
+    let threshold = parse_threshold("0001").unwrap();
+    assert_eq!(threshold[..2], [0x00, 0x01]);
+    assert!(threshold[2..].iter().all(|b| *b == 0));
+    assert!(parse_threshold("xyz").is_err());
+    assert!(parse_threshold(&"f".repeat(65)).is_err());
+    assert!(parse_threshold("00").unwrap_err().contains("below zero"));
+    assert!(parse_threshold(&"0".repeat(64)).is_err());
+    // 256 zero bits don't fit the count, which saturates instead
+    assert_eq!(get_leading_zero_bits(&[0_u8; 32]), u8::MAX);
+    assert_eq!(get_leading_zero_bits(&[0_u8; 31]), 248);
+
+    let mut just_below = [0xff_u8; 32];
+    just_below[0] = 0x00;
+    just_below[1] = 0x00;
+    let mut margin = [0_u8; 32];
+    margin[31] = 1;
+    assert_eq!(below_threshold(&just_below, &threshold), Some(margin));
+
+    let mut just_above = [0_u8; 32];
+    just_above[1] = 0x01;
+    just_above[31] = 0x01;
+    assert_eq!(below_threshold(&just_above, &threshold), None);
+    // equal is not below
+    assert_eq!(below_threshold(&threshold, &threshold), None);
+    assert_eq!(
+        hex_string(&below_threshold(&[0; 32], &threshold).unwrap()),
+        hex_string(&threshold)
+    );
+}
+
+#[test]
+fn benchmark_runs_for_the_given_duration() {
+    use std::time::Instant;
+
+    let start = Instant::now();
+    let rate = benchmark_core_rate(
+        OutputFormat::Plain,
+        false,
+        Duration::ZERO,
+        Duration::from_millis(300),
+    );
+    let elapsed = start.elapsed();
+    assert!(rate > 0);
+    assert!(elapsed >= Duration::from_millis(300));
+    assert!(elapsed < Duration::from_secs(BENCHMARK_SECONDS));
+}
+
+#[test]
+fn warmup_runs_before_the_benchmark() {
+    use std::time::Instant;
+
+    let start = Instant::now();
+    let rate = benchmark_core_rate(
+        OutputFormat::Plain,
+        false,
+        Duration::from_millis(200),
+        Duration::from_millis(200),
+    );
+    assert!(rate > 0);
+    assert!(start.elapsed() >= Duration::from_millis(400));
+}
+
+#[test]
+fn warmup_is_skipped_without_a_benchmark() {
+    use std::time::Instant;
+
+    let start = Instant::now();
+    benchmark_core_rate(
+        OutputFormat::Plain,
+        false,
+        Duration::from_secs(WARMUP_SECONDS),
+        Duration::ZERO,
+    );
+    assert!(start.elapsed() < Duration::from_secs(WARMUP_SECONDS));
+}
+
+#[test]
+fn throttled_loops_run_slower() {
+    use std::time::Instant;
+
+    let count_for = |throttle: Option<Throttle>, duration: Duration| {
+        let mut throttle = throttle;
+        let start = Instant::now();
+        let mut iterations = 0_u64;
+        while start.elapsed() < duration {
+            iterations += 1;
+            if let Some(throttle) = throttle.as_mut() {
+                throttle.tick();
+            }
+        }
+        iterations
+    };
+
+    let duration = Duration::from_millis(500);
+    let throttled = count_for(Some(Throttle::new(200.0)), duration);
+    let unthrottled = count_for(None, duration);
+    // 100 iterations expected, within 10%
+    assert!((90..=110).contains(&throttled), "{throttled} iterations");
+    assert!(unthrottled > throttled * 10);
+}
+
+#[test]
+fn the_numerically_smallest_key_is_retained() {
+    use std::sync::Mutex;
+
+    let lowest = Mutex::new(None);
+    let candidates: Vec<KeyMatch> = (0..200)
+        .map(|_| KeyMatch::new(&Keys::generate(), "", 0, None, false).unwrap())
+        .collect();
+    for candidate in &candidates {
+        retain_lowest(&lowest, candidate.clone());
+    }
+    let smallest = candidates.iter().map(|c| &c.pubkey).min().unwrap();
+    assert_eq!(&lowest.lock().unwrap().as_ref().unwrap().pubkey, smallest);
+
+    // the same key again is not an improvement
+    let again = candidates.iter().find(|c| &c.pubkey == smallest).unwrap();
+    assert!(!retain_lowest(&lowest, again.clone()));
+}
+
+#[test]
+fn the_rarest_match_is_retained() {
+    use std::sync::Mutex;
+
+    let best = Mutex::new(None);
+    assert_eq!(format_summary(None), "No key found during this run");
+
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    for (leading_zeroes, kept) in [(5, true), (9, true), (7, false), (9, false)] {
+        let key_match = KeyMatch::new(&keys, "", leading_zeroes, None, false).unwrap();
+        assert_eq!(retain_best(&best, key_match), kept);
+    }
+    let best = best.lock().unwrap();
+    assert_eq!(best.as_ref().unwrap().leading_zero_bits, 9);
+
+    let summary = format_summary(best.as_ref());
+    assert!(summary.contains("Leading zero bits:         9"));
+    assert!(summary.contains("npub18pwr5mkqh82h5sesm0tzsjvfhedaqrjpc56lnj3ek6h8c5sms8xsn5yj00"));
+    assert!(summary.contains(&keys.secret_key().unwrap().to_bech32().unwrap()));
+}
+
+#[test]
+fn found_table_has_a_row_per_match() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let nsec = keys.secret_key().unwrap().to_bech32().unwrap();
+    let matches: Vec<KeyMatch> = [(0, "rana"), (0, "h0dl"), (12, "")]
+        .iter()
+        .map(|(bits, vanity)| KeyMatch::new(&keys, vanity, *bits, None, false).unwrap())
+        .collect();
+
+    let table = format_found_table(&matches, false);
+    // header plus one row per match
+    assert_eq!(table.lines().count(), matches.len() + 1);
+    assert!(table.lines().nth(2).unwrap().contains("h0dl"));
+    assert!(!table.contains(&nsec));
+
+    let table = format_found_table(&matches, true);
+    assert_eq!(table.lines().filter(|l| l.contains(&nsec)).count(), 3);
+    assert_eq!(format_found_table(&[], false).lines().count(), 1);
+}
+
+#[test]
+fn adaptive_difficulty_rises_after_quick_finds() {
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    let interval = Duration::from_secs(10);
+    assert_eq!(adapt_difficulty(8, Duration::from_secs(1), interval), 9);
+    assert_eq!(adapt_difficulty(8, interval, interval), 8);
+    assert_eq!(adapt_difficulty(8, Duration::from_secs(30), interval), 7);
+    assert_eq!(adapt_difficulty(0, Duration::from_secs(30), interval), 0);
+
+    let adaptive = AdaptiveDifficulty::new(interval);
+    let difficulty = AtomicU8::new(8);
+    // too easy keys are no match and leave it alone
+    assert!(!adaptive.record(&difficulty, 8, Duration::from_secs(1)));
+    assert_eq!(difficulty.load(Ordering::Relaxed), 8);
+    for (bits, secs) in [(9, 1), (10, 2), (11, 3)] {
+        assert!(adaptive.record(&difficulty, bits, Duration::from_secs(secs)));
+    }
+    assert_eq!(difficulty.load(Ordering::Relaxed), 11);
+
+    // an interval after the last find it eases, once per interval
+    assert_eq!(adaptive.ease(&difficulty, Duration::from_secs(12)), None);
+    assert_eq!(
+        adaptive.ease(&difficulty, Duration::from_secs(13)),
+        Some(10)
+    );
+    assert_eq!(adaptive.ease(&difficulty, Duration::from_secs(14)), None);
+    // and a find slower than the interval eases it too
+    assert!(adaptive.record(&difficulty, 12, Duration::from_secs(30)));
+    assert_eq!(difficulty.load(Ordering::Relaxed), 9);
+}
+
+#[test]
+fn a_second_ctrl_c_soon_after_the_first_forces_quitting() {
+    let start = Instant::now();
+    let presses = InterruptCounter::default();
+    assert_eq!(presses.press(start), Interrupt::Graceful);
+    assert_eq!(
+        presses.press(start + Duration::from_secs(1)),
+        Interrupt::ForceQuit
+    );
+
+    // presses further apart are each a graceful stop
+    let presses = InterruptCounter::default();
+    assert_eq!(presses.press(start), Interrupt::Graceful);
+    let later = start + FORCE_QUIT_WINDOW + Duration::from_millis(1);
+    assert_eq!(presses.press(later), Interrupt::Graceful);
+    assert_eq!(
+        presses.press(later + FORCE_QUIT_WINDOW),
+        Interrupt::ForceQuit
+    );
+}
+
+#[test]
+fn keeping_a_match_resumes_with_the_same_counter_and_difficulty() {
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    let pause = Arc::new(MiningPause::default());
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let iterations = Arc::new(AtomicU64::new(0));
+    let best = Arc::new(AtomicU8::new(10));
+    // stand-ins for the workers, counting in batches like they do
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            let (pause, shutdown, iterations) =
+                (pause.clone(), shutdown.clone(), iterations.clone());
+            thread::spawn(move || loop {
+                pause.wait(&shutdown);
+                if shutdown.load(Ordering::Relaxed) {
+                    break;
+                }
+                iterations.fetch_add(64, Ordering::Relaxed);
+                thread::sleep(Duration::from_millis(1));
+            })
+        })
+        .collect();
+    // a single long wait reports whether the counter moved at all
+    let moved = |during: Duration| {
+        let before = iterations.load(Ordering::Relaxed);
+        thread::sleep(during);
+        iterations.load(Ordering::Relaxed) - before
+    };
+
+    assert!(moved(Duration::from_millis(50)) > 0);
+    let prompt = pause.pause();
+    thread::sleep(Duration::from_millis(20));
+    let paused_at = iterations.load(Ordering::Relaxed);
+    assert_eq!(moved(Duration::from_millis(100)), 0);
+    assert!(raise_best(&best, 12));
+    drop(prompt);
+
+    // the search goes on from the same counter and the raised bar
+    assert!(moved(Duration::from_millis(50)) > 0);
+    assert!(iterations.load(Ordering::Relaxed) > paused_at);
+    assert_eq!(best.load(Ordering::Relaxed), 12);
+
+    // a shutdown while paused still stops the workers
+    let _prompt = pause.pause();
+    shutdown.store(true, Ordering::Relaxed);
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+#[test]
+fn best_difficulty_only_grows() {
+    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    let best = Arc::new(AtomicU8::new(10));
+    assert!(!raise_best(&best, 10));
+    assert!(raise_best(&best, 11));
+    assert!(!raise_best(&best, 3));
+
+    let best = Arc::new(AtomicU8::new(1));
+    let threads: Vec<_> = (0..16_u32)
+        .map(|seed| {
+            let best = best.clone();
+            thread::spawn(move || {
+                let mut raised = Vec::new();
+                let mut state = seed.wrapping_mul(2654435761).wrapping_add(1);
+                for _ in 0..10_000 {
+                    // cheap xorshift so threads race with different candidates
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    let candidate = (state % 256) as u8;
+                    if raise_best(&best, candidate) {
+                        raised.push(candidate);
+                    }
+                }
+                raised
+            })
+        })
+        .collect();
+
+    let mut all_raised = Vec::new();
+    for raised in threads.into_iter().map(|t| t.join().unwrap()) {
+        assert!(raised.windows(2).all(|pair| pair[0] < pair[1]));
+        all_raised.extend(raised);
+    }
+    let count = all_raised.len();
+    all_raised.sort();
+    all_raised.dedup();
+    // every improvement was reported by exactly one thread
+    assert_eq!(all_raised.len(), count);
+    assert_eq!(
+        all_raised.last().copied(),
+        Some(best.load(Ordering::Relaxed))
+    );
+}
+
+#[test]
+fn compact_qr_codes_are_half_as_wide() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let nsec = keys.secret_key().unwrap().to_bech32().unwrap();
+    let full = render_qr(&nsec).unwrap();
+    let compact = render_qr_compact(&nsec).unwrap();
+    let width = |qr: &str| qr.lines().map(|line| line.chars().count()).max().unwrap();
+
+    assert_eq!(compact.lines().count(), full.lines().count());
+    assert_eq!(width(&compact), width(&full).div_ceil(2));
+
+    // reading the quarters back gives every module of the code
+    let code = qrcode::QrCode::new(&nsec).unwrap();
+    let rows: Vec<Vec<char>> = compact.lines().map(|l| l.chars().collect()).collect();
+    for (i, color) in code.to_colors().iter().enumerate() {
+        let (x, y) = (i % code.width() + 4, i / code.width() + 4);
+        let block = rows[y / 2][x / 2];
+        let index = QUADRANT_BLOCKS.iter().position(|&b| b == block).unwrap();
+        let drawn = index & 1 << ((y % 2) * 2 + x % 2) != 0;
+        assert_eq!(drawn, *color == qrcode::Color::Light);
+    }
+}
+
+#[test]
+fn ascii_qr_codes_hold_every_module() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let nsec = keys.secret_key().unwrap().to_bech32().unwrap();
+    let ascii = render_qr_ascii(&nsec).unwrap();
+    let code = qrcode::QrCode::new(&nsec).unwrap();
+    let size = code.width() + 8;
+
+    let rows: Vec<&str> = ascii.lines().collect();
+    assert_eq!(rows.len(), size);
+    assert!(rows.iter().all(|row| row.len() == 2 * size));
+    assert!(ascii.chars().all(|c| matches!(c, '#' | ' ' | '\n')));
+    for (i, color) in code.to_colors().iter().enumerate() {
+        let (x, y) = (i % code.width() + 4, i / code.width() + 4);
+        let module = &rows[y][2 * x..2 * x + 2];
+        let expected = if *color == qrcode::Color::Light {
+            "##"
+        } else {
+            "  "
+        };
+        assert_eq!(module, expected);
+    }
+}
+
+#[test]
+fn qr_style_follows_the_flags() {
+    use clap::Parser;
+
+    let style = |args: &[&str]| CLIArgs::parse_from([&["rana", "-q"], args].concat()).qr_style();
+    assert_eq!(style(&["--qr-compact"]), QrStyle::Compact);
+    assert_eq!(style(&["--qr-ascii"]), QrStyle::Ascii);
+    assert_eq!(style(&["--qr-ascii=false"]), QrStyle::Blocks);
+    let default = if cfg!(windows) {
+        QrStyle::Ascii
+    } else {
+        QrStyle::Blocks
+    };
+    assert_eq!(style(&[]), default);
+}
+
+#[test]
+fn difficulty_bar_follows_the_flags() {
+    use clap::Parser;
+
+    let bar = |args: &[&str]| CLIArgs::parse_from([&["rana"], args].concat()).difficulty_bar();
+    assert_eq!(bar(&[]), DifficultyBar::OpenEnded);
+    assert_eq!(bar(&["--open-ended"]), DifficultyBar::OpenEnded);
+    assert_eq!(bar(&["--fixed"]), DifficultyBar::Fixed);
+    assert_eq!(bar(&["--disable-bar-raising"]), DifficultyBar::Fixed);
+}
+
+#[test]
+fn qr_rendering_failures_are_errors() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let nsec = keys.secret_key().unwrap().to_bech32().unwrap();
+    assert!(render_qr(&nsec).is_ok());
+    // more than a version 40 QR code can hold
+    assert!(render_qr(&"a".repeat(8000)).is_err());
+    assert!(render_qr_compact(&"a".repeat(8000)).is_err());
+    assert!(render_qr_ascii(&"a".repeat(8000)).is_err());
+}
+
+#[test]
+fn narrow_terminals_get_a_warning_instead_of_the_qr_code() {
+    let nsec = Keys::from_sk_str(TEST_SECRET_KEY)
+        .unwrap()
+        .secret_key()
+        .unwrap()
+        .to_bech32()
+        .unwrap();
+    let qr = render_qr(&nsec).unwrap();
+    let width = qr.lines().map(|line| line.chars().count()).max().unwrap();
+
+    let warning = narrow_terminal_warning(&qr, Some(width - 1), QrStyle::Blocks).unwrap();
+    assert!(warning.contains(&format!("{width} columns wide")));
+    assert!(warning.contains("--qr-compact"));
+    assert!(warning.contains("--backup-card"));
+    let compact = render_qr_compact(&nsec).unwrap();
+    let warning = narrow_terminal_warning(&compact, Some(10), QrStyle::Compact).unwrap();
+    assert!(!warning.contains("--qr-compact"));
+
+    assert_eq!(
+        narrow_terminal_warning(&qr, Some(width), QrStyle::Blocks),
+        None
+    );
+    assert_eq!(
+        narrow_terminal_warning(&qr, Some(200), QrStyle::Blocks),
+        None
+    );
+    assert_eq!(narrow_terminal_warning(&qr, None, QrStyle::Blocks), None);
+}
+
+#[test]
+fn keys_encode_under_each_hrp() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let npub = encode_bech32(&keys, Hrp::Npub).unwrap();
+    let nsec = encode_bech32(&keys, Hrp::Nsec).unwrap();
+    assert_eq!(
+        npub,
+        "npub18pwr5mkqh82h5sesm0tzsjvfhedaqrjpc56lnj3ek6h8c5sms8xsn5yj00"
+    );
+    assert_eq!(nsec, keys.secret_key().unwrap().to_bech32().unwrap());
+    assert!(nsec.starts_with("nsec1"));
+
+    let npub_prefix = vec![npub[5..9].to_string()];
+    let nsec_prefix = vec![nsec[5..9].to_string()];
+    assert_eq!(
+        match_npub(&npub, &npub_prefix, &[], false),
+        Some(npub_prefix[0].clone())
+    );
+    assert_eq!(
+        match_npub(&nsec, &nsec_prefix, &[], false),
+        Some(nsec_prefix[0].clone())
+    );
+    assert_eq!(match_npub(&nsec, &npub_prefix, &[], false), None);
+}
+
+#[test]
+fn keys_match_under_any_or_all_of_several_hrps() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let nsec = encode_bech32(&keys, Hrp::Nsec).unwrap();
+    // "8pwr" starts the npub, the other target starts the nsec
+    let targets = vec!["8pwr".to_string(), nsec[5..9].to_string()];
+    let matches = |bech_key: &str| match_npub(bech_key, &targets, &[], false);
+    let both = [Hrp::Npub, Hrp::Nsec];
+
+    assert_eq!(
+        match_hrps(&keys, &[Hrp::Npub], false, matches),
+        Some("8pwr".to_string())
+    );
+    assert_eq!(
+        match_hrps(&keys, &both, false, matches),
+        Some("npub:8pwr".to_string())
+    );
+    assert_eq!(
+        match_hrps(&keys, &both, true, matches),
+        Some(format!("npub:8pwr, nsec:{}", &nsec[5..9]))
+    );
+
+    // only the npub matches now
+    let npub_only = |bech_key: &str| match_npub(bech_key, &targets[..1], &[], false);
+    assert_eq!(
+        match_hrps(&keys, &[Hrp::Nsec, Hrp::Npub], false, npub_only),
+        Some("npub:8pwr".to_string())
+    );
+    assert_eq!(match_hrps(&keys, &both, true, npub_only), None);
+    assert_eq!(match_hrps(&keys, &[Hrp::Nsec], false, npub_only), None);
+}
+
+#[test]
+fn jsonl_matches_are_independent_lines() {
+    let first = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let second = Keys::from_sk_str(MAX_SECRET_KEY).unwrap();
+    let stream = [
+        format_key_match(
+            &KeyMatch::new(&first, "", 12, None, false).unwrap(),
+            OutputFormat::Jsonl,
+        )
+        .unwrap(),
+        format_key_match(
+            &KeyMatch::new(&second, "rana", 0, None, false).unwrap(),
+            OutputFormat::Jsonl,
+        )
+        .unwrap(),
+    ]
+    .join("\n");
+
+    let lines: Vec<serde_json::Value> = stream
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["secret_key"], TEST_SECRET_KEY);
+    assert_eq!(lines[0]["leading_zero_bits"], 12);
+    assert_eq!(
+        lines[0]["npub"],
+        "npub18pwr5mkqh82h5sesm0tzsjvfhedaqrjpc56lnj3ek6h8c5sms8xsn5yj00"
+    );
+    assert!(lines[0].get("vanity").is_none());
+    assert_eq!(lines[1]["secret_key"], MAX_SECRET_KEY);
+    assert_eq!(lines[1]["vanity"], "rana");
+}
+
+#[test]
+fn pubkey_only_output_has_no_secret() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let nsec = keys.secret_key().unwrap().to_bech32().unwrap();
+    let public_only = Keys::from_public_key(keys.public_key());
+
+    for format in [
+        OutputFormat::Plain,
+        OutputFormat::Nak,
+        OutputFormat::Jsonl,
+        OutputFormat::Env,
+    ] {
+        let output = format_key_match(
+            &KeyMatch::new(&public_only, "", 12, None, false).unwrap(),
+            format,
+        )
+        .unwrap();
+        assert!(!output.contains(TEST_SECRET_KEY));
+        assert!(!output.contains(&nsec));
+        assert!(!output.contains("private key"));
+        assert!(!output.contains("secret"));
+        assert!(output.contains(&keys.public_key().to_string()));
+    }
+
+    let plain = format_key_match(
+        &KeyMatch::new(&public_only, "", 12, None, false).unwrap(),
+        OutputFormat::Plain,
+    )
+    .unwrap();
+    assert!(plain.ends_with("npub18pwr5mkqh82h5sesm0tzsjvfhedaqrjpc56lnj3ek6h8c5sms8xsn5yj00"));
+}
+
+#[test]
+fn compact_output_is_a_single_line_per_match() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let nsec = keys.secret_key().unwrap().to_bech32().unwrap();
+    let mut key_match = KeyMatch::new(&keys, "", 12, None, false)
+        .unwrap()
+        .with_encodings(SecretFormat::Nsec, false);
+    key_match.found_at_iteration = 4096;
+    assert_eq!(
+        format_key_match(&key_match, OutputFormat::Compact).unwrap(),
+        format!("npub=npub18pwr5mkqh82h5sesm0tzsjvfhedaqrjpc56lnj3ek6h8c5sms8xsn5yj00 nsec={nsec} diff=12 iter=4096 origin=random")
+    );
+
+    let mnemonic = Mnemonic::parse(
+        "congress evoke onion donate fantasy soccer project fiction envelope body faith mean",
+    )
+    .unwrap();
+    let output = format_key_match(
+        &KeyMatch::new(&keys, "rana", 0, Some(&mnemonic), false).unwrap(),
+        OutputFormat::Compact,
+    )
+    .unwrap();
+    assert_eq!(output.lines().count(), 1);
+    assert!(output.contains(&format!(" secret={TEST_SECRET_KEY} ")));
+    assert!(output.contains(" vanity=rana "));
+    assert!(output.contains(" mnemonic='congress evoke onion donate fantasy soccer project fiction envelope body faith mean' "));
+    assert!(output.ends_with(" path=m/44'/1237'/0'/0/0"));
+
+    let public_only = Keys::from_public_key(keys.public_key());
+    let output = format_key_match(
+        &KeyMatch::new(&public_only, "", 12, None, false).unwrap(),
+        OutputFormat::Compact,
+    )
+    .unwrap();
+    assert_eq!(
+        output,
+        "npub=npub18pwr5mkqh82h5sesm0tzsjvfhedaqrjpc56lnj3ek6h8c5sms8xsn5yj00 diff=12 iter=0 origin=random"
+    );
+}
+
+#[test]
+fn difficulty_output_includes_a_valid_npub() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let output = format_key_match(
+        &KeyMatch::new(&keys, "", 10, None, false).unwrap(),
+        OutputFormat::Plain,
+    )
+    .unwrap();
+    let npub = output
+        .lines()
+        .find_map(|line| line.strip_prefix("Nostr public key (npub):"))
+        .unwrap()
+        .trim();
+    assert_eq!(
+        XOnlyPublicKey::from_bech32(npub).unwrap(),
+        keys.public_key()
+    );
+}
+
+#[test]
+fn redacted_output_never_contains_the_secret() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let nsec = keys.secret_key().unwrap().to_bech32().unwrap();
+    let mnemonic = Mnemonic::parse(
+        "congress evoke onion donate fantasy soccer project fiction envelope body faith mean",
+    )
+    .unwrap();
+
+    for format in [
+        OutputFormat::Plain,
+        OutputFormat::Nak,
+        OutputFormat::Jsonl,
+        OutputFormat::Env,
+        OutputFormat::Compact,
+    ] {
+        let output = format_key_match(
+            &KeyMatch::new(&keys, "rana", 0, Some(&mnemonic), true).unwrap(),
+            format,
+        )
+        .unwrap();
+        assert!(!output.contains(TEST_SECRET_KEY));
+        assert!(!output.contains(&nsec));
+        assert!(!output.contains("congress"));
+        assert!(output.contains(REDACTED));
+    }
+
+    let plain = format_key_match(
+        &KeyMatch::new(&keys, "rana", 0, Some(&mnemonic), true).unwrap(),
+        OutputFormat::Plain,
+    )
+    .unwrap();
+    assert!(plain.contains("Vanity npub found:         rana"));
+    assert!(plain.contains(&keys.public_key().to_bech32().unwrap()));
+}
+
+#[test]
+fn durations() {
+    assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+    assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+    assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(172_800)));
+    assert!(parse_duration("1w").is_err());
+    assert!(parse_duration("999999999999999999d")
+        .unwrap_err()
+        .contains("duration too large"));
+    assert_eq!(
+        parse_duration(&format!("{}s", u64::MAX)),
+        Ok(Duration::from_secs(u64::MAX))
+    );
+    assert!(parse_duration("h").is_err());
+}
+
+#[test]
+fn target_time_picks_difficulty() {
+    // 2^10 hashes at 1024 h/s take exactly one second
+    assert_eq!(difficulty_for_target_time(1024, Duration::from_secs(1)), 10);
+    assert_eq!(difficulty_for_target_time(1023, Duration::from_secs(1)), 9);
+    // 3.6 million hashes in an hour at 1000 h/s, 2^21 < 3.6M < 2^22
+    assert_eq!(
+        difficulty_for_target_time(1000, Duration::from_secs(3600)),
+        21
+    );
+    assert_eq!(difficulty_for_target_time(0, Duration::from_secs(3600)), 1);
+}
+
+#[test]
+fn npub_targets_take_32_times_more_attempts_per_character() {
+    let attempts = |prefix: &str, suffix: &str| {
+        let to_targets = |target: &str| {
+            (!target.is_empty())
+                .then(|| target.to_string())
+                .into_iter()
+                .collect::<Vec<String>>()
+        };
+        expected_attempts(npub_pow_difficulty(
+            &to_targets(prefix),
+            &to_targets(suffix),
+            false,
+        ))
+    };
+    assert_eq!(attempts("r", ""), 32.0);
+    assert_eq!(attempts("rana", ""), 1_048_576.0);
+    assert_eq!(attempts("rana", "x"), 33_554_432.0);
+    assert_eq!(attempts("", "h0dl"), attempts("rana", ""));
+    assert_eq!(expected_attempts(0), 1.0);
+}
+
+#[test]
+fn estimates_are_written_in_a_readable_unit() {
+    assert_eq!(format_eta(5.0), "5.0 seconds");
+    assert_eq!(format_eta(90.0), "1.5 minutes");
+    assert_eq!(format_eta(7200.0), "2.0 hours");
+    assert_eq!(format_eta(3.0 * 86400.0), "3.0 days");
+    assert_eq!(format_eta(365.25 * 86400.0 * 1e7), "1.0e7 years");
+
+    assert_eq!(
+        format_estimate(20, Some(1024)),
+        "Difficulty: 20 bits\nExpected attempts: 1,048,576 (2^20)\nAt 1,024 keys/second this takes about 17.1 minutes"
+    );
+    assert!(format_estimate(20, None).ends_with("Pass --rate to estimate how long this takes"));
+    assert!(format_estimate(200, None).contains("Expected attempts: 2^200\n"));
+}
+
+#[test]
+fn luck_compares_the_work_to_the_expected_attempts() {
+    assert_eq!(luck_percent(614, 10), 614.0 / 1024.0 * 100.0);
+    assert_eq!(luck_percent(1 << 20, 20), 100.0);
+    assert_eq!(
+        format_luck(614, 10),
+        "Luck: found at 60% of the expected work of 2^10 keys, lucky"
+    );
+    assert_eq!(
+        format_luck(3 << 19, 20),
+        "Luck: found at 150% of the expected work of 2^20 keys, unlucky"
+    );
+    assert_eq!(
+        format_luck(5, 10),
+        "Luck: found at 0.49% of the expected work of 2^10 keys, lucky"
+    );
+}
+
+#[test]
+fn generated_keys_are_well_formed() {
+    let secp = Secp256k1::new();
+    assert!(is_well_formed(&secp, &Keys::generate()));
+    assert!(is_well_formed(
+        &secp,
+        &Keys::from_sk_str(TEST_SECRET_KEY).unwrap()
+    ));
+    // a public key without its secret cannot be checked
+    let public_only = Keys::from_public_key(Keys::generate().public_key());
+    assert!(!is_well_formed(&secp, &public_only));
+}
+
+#[test]
+fn weak_keys_are_retried() {
+    let weak = Keys::from_public_key(Keys::generate().public_key());
+    let mut attempts = vec![Keys::generate(), weak.clone(), weak];
+    let secp = Secp256k1::new();
+    let (keys, retries) = generate_until(
+        || attempts.pop().unwrap(),
+        |keys| is_well_formed(&secp, keys),
+    );
+    assert_eq!(retries, 2);
+    assert!(keys.secret_key().is_ok());
+}
+
+#[test]
+fn low_entropy_secrets_are_refused() {
+    let secret = |hex: &str| SecretKey::from_str(hex).unwrap().secret_bytes();
+    assert!(has_sufficient_entropy(&secret(TEST_SECRET_KEY)));
+    for _ in 0..100 {
+        let keys = Keys::generate();
+        assert!(has_sufficient_entropy(
+            &keys.secret_key().unwrap().secret_bytes()
+        ));
+    }
+
+    // a small counter of a sequential search
+    let mut counter = [0_u8; 32];
+    counter[31] = 1;
+    assert!(!has_sufficient_entropy(&counter));
+    // a repeated pattern has no long run, but few distinct bytes
+    let pattern = secret(&"deadbeef".repeat(8));
+    assert!(!has_sufficient_entropy(&pattern));
+    // and a run of 5 bytes in an otherwise random secret
+    let mut run = secret(TEST_SECRET_KEY);
+    run[10..15].fill(0x42);
+    assert!(!has_sufficient_entropy(&run));
+    run[14] = 0x43;
+    assert!(has_sufficient_entropy(&run));
+}
+
+#[test]
+fn later_backup_cards_are_numbered() {
+    assert_eq!(backup_card_path("card.png", 1), "card.png");
+    assert_eq!(backup_card_path("card.png", 2), "card-2.png");
+    assert_eq!(
+        backup_card_path("cards/frog.card.png", 12),
+        "cards/frog.card-12.png"
+    );
+    assert_eq!(backup_card_path("card", 3), "card-3");
+}
+
+#[test]
+fn backup_card_is_a_png_of_the_expected_size() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let qr_width = qrcode::QrCode::new(keys.public_key().to_bech32().unwrap())
+        .unwrap()
+        .width() as u32;
+    let panel = (qr_width + 2 * QUIET_ZONE) * MODULE_PX;
+    let height = panel + 7 * GLYPH_PX + 2 * PADDING;
+
+    for (include_secret, panels) in [(true, 2), (false, 1)] {
+        let path = std::env::temp_dir().join(format!("rana-card-{include_secret}.png"));
+        write_backup_card(&keys, include_secret, path.to_str().unwrap()).unwrap();
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!(info.width, PADDING + panels * (panel + PADDING));
+        assert_eq!(info.height, height);
+        assert_eq!(info.color_type, png::ColorType::Grayscale);
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn hex_prefixes_match_the_start_of_the_public_key() {
+    let public_key = Keys::from_sk_str(TEST_SECRET_KEY).unwrap().public_key();
+    let hex = public_key.to_string();
+    assert!(matches_hex_prefix(&public_key, ""));
+    assert!(matches_hex_prefix(&public_key, &hex[..4]));
+    assert!(matches_hex_prefix(&public_key, &hex));
+    assert!(!matches_hex_prefix(&public_key, &hex[1..5]));
+    assert!(!matches_hex_prefix(&public_key, &format!("{hex}0")));
+}
+
+#[test]
+fn difficulty_is_met_by_beating_the_best_key() {
+    let mut key = [0xff_u8; 32];
+    key[0] = 0x00;
+    key[1] = 0x1f; // 8 + 3 leading zero bits
+    assert_eq!(meets_difficulty(&key, 0, 0), Some(11));
+    assert_eq!(meets_difficulty(&key, 0, 10), Some(11));
+    assert_eq!(meets_difficulty(&key, 0, 11), None);
+    assert_eq!(meets_difficulty(&key, 1, 0), Some(11));
+    // the whole zero bytes are required even if the bits beat the best
+    assert_eq!(meets_difficulty(&key, 2, 0), None);
+    // no difficulty set: any leading zero bit, but not none
+    assert_eq!(meets_difficulty(&[0x40; 32], 0, 0), Some(1));
+    assert_eq!(meets_difficulty(&[0x80; 32], 0, 0), None);
+}
+
+#[test]
+fn leading_zero_bytes() {
+    let mut bytes = [0xff_u8; 32];
+    assert!(has_leading_zero_bytes(&bytes, 0));
+    assert!(!has_leading_zero_bytes(&bytes, 1));
+
+    bytes[0] = 0;
+    bytes[1] = 0;
+    assert!(has_leading_zero_bytes(&bytes, 1));
+    assert!(has_leading_zero_bytes(&bytes, 2));
+    assert!(!has_leading_zero_bytes(&bytes, 3));
+    assert_eq!(get_leading_zero_bits(&bytes), 16);
+
+    // a partially zero byte does not count as a whole zero byte
+    bytes[2] = 0x01;
+    assert!(!has_leading_zero_bytes(&bytes, 3));
+    assert_eq!(get_leading_zero_bits(&bytes), 23);
+
+    assert!(has_leading_zero_bytes(&[0; 32], 32));
+    assert!(!has_leading_zero_bytes(&[0; 2], 3));
+}
+
+#[test]
+fn npub_pow_uses_five_bits_per_character() {
+    let prefixes = vec![String::from("rana"), String::from("h0dl0")];
+    let suffixes = vec![String::from("end")];
+    assert_eq!(npub_pow_difficulty(&prefixes, &[], false), 20);
+    assert_eq!(npub_pow_difficulty(&[], &suffixes, false), 15);
+    assert_eq!(npub_pow_difficulty(&prefixes, &suffixes, false), 35);
+    assert_eq!(npub_pow_difficulty(&prefixes, &suffixes, true), 15);
+
+    let long = vec!["q".repeat(59)];
+    assert_eq!(npub_pow_difficulty(&long, &long, false), u8::MAX);
+}
+
+/// Curve order minus one, the largest valid secret key
+const MAX_SECRET_KEY: &str = "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140";
+
+#[test]
+fn scalar_walk_steps_through_keys() {
+    let start = SecretKey::from_str(TEST_SECRET_KEY).unwrap();
+    let walk: Vec<(SecretKey, u128)> = ScalarWalk::new(start, 2, 3).take(3).collect();
+    let offsets: Vec<u128> = walk.iter().map(|(_, offset)| *offset).collect();
+    assert_eq!(offsets, vec![2, 5, 8]);
+    assert_eq!(walk[0].0, start.add_tweak(&scalar_from_u64(2)).unwrap());
+    assert_eq!(walk[2].0, start.add_tweak(&scalar_from_u64(8)).unwrap());
+}
+
+#[test]
+fn scalar_walk_wraps_around_the_curve_order() {
+    let max = SecretKey::from_str(MAX_SECRET_KEY).unwrap();
+    let one = SecretKey::from_slice(&scalar_from_u64(1).to_be_bytes()).unwrap();
+    let two = SecretKey::from_slice(&scalar_from_u64(2).to_be_bytes()).unwrap();
+
+    // max + 1 is the curve order itself, an invalid key that gets skipped
+    let walk: Vec<(SecretKey, u128)> = ScalarWalk::new(max, 0, 1).take(3).collect();
+    assert_eq!(walk, vec![(max, 0), (one, 2), (two, 3)]);
+
+    // max + 2 wraps around to 1
+    let walk: Vec<(SecretKey, u128)> = ScalarWalk::new(max, 0, 2).take(2).collect();
+    assert_eq!(walk, vec![(max, 0), (one, 2)]);
+
+    // starting from an offset that lands on the curve order
+    let walk: Vec<(SecretKey, u128)> = ScalarWalk::new(max, 1, 2).take(1).collect();
+    assert_eq!(walk, vec![(two, 3)]);
+}
+
+#[test]
+fn scalar_walks_with_a_stride_are_disjoint() {
+    let start = SecretKey::from_str(TEST_SECRET_KEY).unwrap();
+    let first: Vec<SecretKey> = ScalarWalk::new(start, 0, 2)
+        .take(50)
+        .map(|(k, _)| k)
+        .collect();
+    let second: Vec<SecretKey> = ScalarWalk::new(start, 1, 2)
+        .take(50)
+        .map(|(k, _)| k)
+        .collect();
+    assert!(first.iter().all(|key| !second.contains(key)));
+}
+
+#[cfg(feature = "distributed")]
+#[test]
+fn distributed_workers_search_disjoint_ranges() {
+    use crate::distributed::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    let start = SecretKey::from_str(TEST_SECRET_KEY).unwrap();
+    let coordinator = Arc::new(Coordinator::bind("127.0.0.1:0", start, 10).unwrap());
+    let addr = coordinator.local_addr().unwrap().to_string();
+    {
+        let coordinator = coordinator.clone();
+        thread::spawn(move || {
+            let _ = coordinator.serve(Arc::new(|_, _| {}));
+        });
+    }
+
+    let workers: Vec<_> = (1..=2)
+        .map(|worker_id| {
+            let addr = addr.clone();
+            thread::spawn(move || {
+                let searched: Vec<SecretKey> = DistributedWalk::connect(&addr, worker_id)
+                    .unwrap()
+                    .take(25)
+                    .map(|(k, _)| k)
+                    .collect();
+                // any key whose pubkey starts with a zero bit counts as found
+                let found = searched
+                    .iter()
+                    .find(|k| Keys::new(**k).public_key().serialize()[0] < 128)
+                    .unwrap();
+                let mut client = WorkerClient::connect(&addr, worker_id).unwrap();
+                client.report_found(found).unwrap();
+                searched
+            })
+        })
+        .collect();
+    let searched: Vec<Vec<SecretKey>> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+
+    assert!(searched[0].iter().all(|key| !searched[1].contains(key)));
+    let mut finders: Vec<u32> = coordinator.found().iter().map(|(id, _)| *id).collect();
+    finders.sort();
+    assert_eq!(finders, vec![1, 2]);
+}
+
+#[test]
+fn run_outcomes_map_to_exit_codes() {
+    assert_eq!(Outcome::of_run(true, false), Outcome::Found);
+    assert_eq!(Outcome::of_run(true, true), Outcome::Found);
+    assert_eq!(Outcome::of_run(false, true), Outcome::Interrupted);
+    assert_eq!(Outcome::of_run(false, false), Outcome::NoMatch);
+    assert_eq!(Outcome::NoMatch as u8, 3);
+}
+
+#[test]
+fn confusable_prefixes_are_rejected() {
+    let confusables = parse_confusables(DEFAULT_CONFUSABLES).unwrap();
+    // nothing confusable in the first eight characters
+    assert_eq!(find_confusable("npub1acdefhjk0q", "", &confusables), None);
+    assert_eq!(
+        find_confusable("npub1acdefhjq", "", &confusables),
+        Some('q')
+    );
+    // the target itself may hold confusables, the rest of the prefix may not
+    assert_eq!(find_confusable("npub1qqacdefhq", "qq", &confusables), None);
+    assert_eq!(
+        find_confusable("npub1qgacdefh", "qq", &confusables),
+        Some('g')
+    );
+    // targets longer than the displayed prefix are checked in full
+    assert_eq!(
+        find_confusable("npub1acdefhjkmn", "acdefhjkm", &confusables),
+        None
+    );
+    assert_eq!(
+        find_confusable("npub1acdefhjkmn", "acdefhjk", &confusables),
+        None
+    );
+    assert_eq!(
+        find_confusable("npub1acdefhqkmq", "acdefhqkm", &confusables),
+        None
+    );
+    // a custom set
+    assert_eq!(find_confusable("npub1acdefhjq", "", "x"), None);
+    assert_eq!(find_confusable("npub1axdefhj", "", "x"), Some('x'));
+
+    assert!(parse_confusables("qg").is_ok());
+    assert!(parse_confusables("ob").is_err());
+    assert!(parse_confusables("").is_err());
+}
+
+#[test]
+fn progress_is_printed_once_per_step() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    let iterations = Arc::new(AtomicU64::new(0));
+    let events = Arc::new(AtomicU64::new(0));
+    let workers: Vec<_> = (0..8)
+        .map(|_| {
+            let iterations = iterations.clone();
+            let events = events.clone();
+            std::thread::spawn(move || {
+                for _ in 0..12_500 {
+                    let previous = iterations.fetch_add(1, Ordering::Relaxed);
+                    let crossed = crossed_progress_steps(previous, previous + 1, 1_000).count();
+                    events.fetch_add(crossed as u64, Ordering::Relaxed);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    assert_eq!(iterations.load(Ordering::Relaxed), 100_000);
+    assert_eq!(events.load(Ordering::Relaxed), 100);
+}
+
+#[test]
+fn batched_progress_is_printed_once_per_step() {
+    let mut events = 0;
+    let mut iteration = 0;
+    for batch in [1, 7, 64, 999, 1_000].iter().cycle().take(1_000) {
+        events += crossed_progress_steps(iteration, iteration + batch, 1_000).count();
+        iteration += batch;
+    }
+    assert_eq!(iteration, 414_200);
+    assert_eq!(events, 414);
+
+    // a batch larger than the step crosses several of them at once
+    let steps: Vec<u64> = crossed_progress_steps(0, 64, 10).collect();
+    assert_eq!(steps, [10, 20, 30, 40, 50, 60]);
+    let steps: Vec<u64> = crossed_progress_steps(60, 128, 10).collect();
+    assert_eq!(steps, [70, 80, 90, 100, 110, 120]);
+    let events: usize = (0..10)
+        .map(|flush| crossed_progress_steps(flush * 64, (flush + 1) * 64, 10).count())
+        .sum();
+    assert_eq!(events, 64);
+    assert_eq!(crossed_progress_steps(11, 19, 10).count(), 0);
+}
+
+#[test]
+fn target_rates_pick_just_enough_cores() {
+    assert_eq!(cores_for_target_rate(10_000, 25_000, 8), 3);
+    assert_eq!(cores_for_target_rate(10_000, 30_000, 8), 3);
+    assert_eq!(cores_for_target_rate(10_000, 30_001, 8), 4);
+    assert_eq!(cores_for_target_rate(10_000, 1, 8), 1);
+    // more than the machine can do uses all of it
+    assert_eq!(cores_for_target_rate(10_000, 1_000_000, 8), 8);
+    // a core too slow to measure
+    assert_eq!(cores_for_target_rate(0, 5, 8), 5);
+}
+
+#[test]
+fn more_cores_than_the_machine_has_only_warn() {
+    let warning = oversubscription_warning(8, 4).unwrap();
+    assert!(warning.contains("mining on 8 threads"), "{warning}");
+    assert!(warning.contains("has 4 cores"), "{warning}");
+    assert!(oversubscription_warning(2, 1)
+        .unwrap()
+        .contains("has 1 core."));
+    assert_eq!(oversubscription_warning(4, 4), None);
+    assert_eq!(oversubscription_warning(1, 4), None);
+
+    // the cores of this machine pass, and more are allowed too
+    let available = num_cpus::get();
+    assert_eq!(
+        check_args(10, "", &Vec::new(), &Vec::new(), available),
+        Ok(None)
+    );
+    assert!(check_args(10, "", &Vec::new(), &Vec::new(), available + 1)
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn faster_cores_get_larger_batches() {
+    let batches = batch_sizes(&[1_000, 3_000, 2_000, 2_000], 64);
+    assert_eq!(batches, vec![32, 96, 64, 64]);
+    assert!(batches[1] > batches[0]);
+
+    // a core too slow to measure still counts its keys
+    assert_eq!(batch_sizes(&[0, 10_000], 64), vec![1, 128]);
+    // nothing measured at all, every core is average
+    assert_eq!(batch_sizes(&[0, 0], 64), vec![64, 64]);
+    assert_eq!(batch_sizes(&[5_000; 3], 64), vec![64; 3]);
+}
+
+#[test]
+fn profile_flags_assemble_kind_0_metadata() {
+    use clap::Parser;
+
+    let args = CLIArgs::try_parse_from([
+        "rana",
+        "--preview",
+        "--name",
+        "frog",
+        "--about",
+        "Ribbit",
+        "--picture",
+        "https://example.com/frog.png",
+        "--nip05",
+        "frog@example.com",
+        "--lud16",
+        "frog@wallet.example.com",
+    ])
+    .unwrap();
+    let content: serde_json::Value =
+        serde_json::from_str(&args.profile_metadata().as_json().unwrap()).unwrap();
+    assert_eq!(
+        content,
+        serde_json::json!({
+            "name": "frog",
+            "about": "Ribbit",
+            "picture": "https://example.com/frog.png",
+            "nip05": "frog@example.com",
+            "lud16": "frog@wallet.example.com",
+        })
+    );
+
+    // only the given fields are set
+    let args = CLIArgs::try_parse_from(["rana", "--preview", "--name", "frog"]).unwrap();
+    assert_eq!(
+        args.profile_metadata().as_json().unwrap(),
+        r#"{"name":"frog"}"#
+    );
+
+    assert!(CLIArgs::try_parse_from(["rana", "--preview", "--picture", "frog.png"]).is_err());
+    assert!(CLIArgs::try_parse_from(["rana", "--preview", "--picture", "ftp://a.com/f"]).is_err());
+    assert!(CLIArgs::try_parse_from(["rana", "--preview", "--nip05", "example.com"]).is_err());
+    assert!(CLIArgs::try_parse_from(["rana", "--name", "frog"]).is_err());
+}
+
+#[test]
+fn every_matching_path_is_benchmarked() {
+    let rates = benchmark_paths(Duration::from_millis(50));
+    let paths: Vec<&str> = rates.iter().map(|rate| rate.path).collect();
+    assert_eq!(paths, ["difficulty", "hex", "npub", "mnemonic"]);
+    assert!(rates.iter().all(|rate| rate.keys_per_second > 0));
+
+    let table = format_path_rates(&rates);
+    assert_eq!(table.lines().count(), 5);
+    assert!(table.lines().skip(1).any(|line| line.ends_with("1.000x")));
+}
+
+#[test]
+fn suffixes_are_flagged_as_checksum_dependent() {
+    assert_eq!(
+        suffix_checksum_note("rana", "npub"),
+        "Note: the last 6 characters of an npub are a checksum, so only 0 of the 4 characters of the suffix '...rana' are part of the key itself (requested pow: 20, pow on the key data: 0). Matching a suffix of the key data needs a different and harder search."
+    );
+    assert!(suffix_checksum_note("ranafrog", "npub")
+        .contains("only 2 of the 8 characters of the suffix '...ranafrog'"));
+    assert!(suffix_checksum_note("ranafrog", "npub")
+        .contains("(requested pow: 40, pow on the key data: 10)"));
+}
+
+#[test]
+fn unpadded_last_npub_key_chars_are_found() {
+    // the top 5 bits of a key take every value, so any character can come first
+    for c in BECH32_CHARSET.chars() {
+        assert_eq!(unpadded_last_npub_key_char(&c.to_string()), None, "{c}");
+    }
+    // the last key character only holds one bit, followed by 4 zero bits
+    let npub = Keys::from_sk_str(TEST_SECRET_KEY)
+        .unwrap()
+        .public_key()
+        .to_bech32()
+        .unwrap();
+    let full = &npub["npub1".len()..];
+    assert_eq!(unpadded_last_npub_key_char(full), None);
+    assert_eq!(
+        unpadded_last_npub_key_char(&format!("{}q", &full[..51])),
+        None
+    );
+    assert_eq!(
+        unpadded_last_npub_key_char(&format!("{}p", &full[..51])),
+        Some('p')
+    );
+    assert!(check_args(0, "", &vec![full[..52].to_string()], &Vec::new(), 1).is_ok());
+}
+
+#[test]
+fn impossible_npub_prefix_is_rejected() {
+    let error =
+        check_args(0, "", &vec![format!("{}z", "q".repeat(51))], &Vec::new(), 1).unwrap_err();
+    assert!(error.contains("no public key has 'z' as character 52 of its npub"));
+}
+
+#[test]
+fn non_bech32_first_char_is_rejected() {
+    let error = check_args(0, "", &vec![String::from("bad")], &Vec::new(), 1).unwrap_err();
+    assert!(error.contains("can only contain characters supported by Bech32"));
+}
+
+#[test]
+fn brand_words_become_bech32_prefixes() {
+    let prefixes = brand_prefixes("Bitcoin");
+    assert_eq!(prefixes, vec!["8ltc0ln", "6ltc0ln"]);
+    for prefix in &prefixes {
+        assert!(
+            prefix.chars().all(|c| BECH32_CHARSET.contains(c)),
+            "{prefix}"
+        );
+    }
+    assert!(check_args(0, "", &prefixes, &Vec::new(), 1).is_ok());
+
+    assert_eq!(brand_prefixes("rana"), vec!["rana"]);
+    assert_eq!(brand_prefixes("my frog!"), vec!["myfr0g"]);
+    assert_eq!(brand_prefixes("bob").len(), 4);
+    assert_eq!(brand_prefixes(&"b".repeat(10)).len(), BRAND_PREFIX_LIMIT);
+    assert!(brand_prefixes("!!!").is_empty());
+}
+
+#[test]
+fn the_schema_describes_every_key_match_field() {
+    let schema: serde_json::Value =
+        serde_json::from_str(&serde_json::to_string(&key_match_schema()).unwrap()).unwrap();
+    let properties = schema["properties"].as_object().unwrap();
+    for field in [
+        "pubkey",
+        "npub",
+        "nsec",
+        "leading_zero_bits",
+        "found_at_iteration",
+    ] {
+        assert!(properties.contains_key(field), "{field}");
+    }
+    assert_eq!(properties["found_at_secs"]["type"], "number");
+    assert_eq!(properties["found_at_iteration"]["type"], "integer");
+
+    // a field added to KeyMatch lands in the schema, it only needs describing
+    let serialized = serde_json::to_value(full_key_match()).unwrap();
+    let fields = serialized.as_object().unwrap();
+    assert_eq!(fields.len(), properties.len());
+    for field in fields.keys() {
+        assert!(properties.contains_key(field), "{field}");
+        assert!(key_match_field_description(field).is_some(), "{field}");
+    }
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field.as_str().unwrap())
+        .collect();
+    assert!(!required.contains(&"nsec") && required.contains(&"npub"));
+    for field in &required {
+        assert!(properties.contains_key(*field), "{field}");
+    }
+    for origin in KeyOrigin::ALL {
+        assert_eq!(serde_json::to_value(origin).unwrap(), origin.as_str());
+    }
+}
+
+#[test]
+fn ncryptsec_matches_the_nip49_test_vector() {
+    let secret_key = decrypt_secret_key(
+        "ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dgjtcxfqtd67p9m0w57lspw8gsq6yphnm8623nsl8xn9j4jdzz84zm3frztj3z7s35vpzmqf6ksu8r89qk5z2zxfmu5gv8th8wclt0h4p",
+        "nostr",
+    )
+    .unwrap();
+    assert_eq!(
+        secret_key.display_secret().to_string(),
+        "3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683"
+    );
+}
+
+#[test]
+fn ncryptsec_round_trips() {
+    let keys = Keys::generate();
+    let ncryptsec = encrypt_secret_key(&keys.secret_key().unwrap(), "frog", 4).unwrap();
+    assert!(ncryptsec.starts_with("ncryptsec1"));
+    assert_eq!(
+        decrypt_secret_key(&ncryptsec, "frog").unwrap(),
+        keys.secret_key().unwrap()
+    );
+    assert!(decrypt_secret_key(&ncryptsec, "toad").is_err());
+}
+
+#[test]
+fn keystore_entries_round_trip() {
+    let path = std::env::temp_dir().join(format!("rana-keystore-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let first = Keys::generate();
+    let second = Keys::generate();
+
+    let mut keystore = Keystore::load(&path).unwrap();
+    assert!(keystore.entries.is_empty());
+    keystore.add(&first, "rana", "frog", 4).unwrap();
+    keystore.save(&path).unwrap();
+    let mut keystore = Keystore::load(&path).unwrap();
+    keystore
+        .add(&second, "20 leading zero bits", "frog", 4)
+        .unwrap();
+    keystore.save(&path).unwrap();
+
+    let keystore = Keystore::load(&path).unwrap();
+    let list = keystore.format_list();
+    assert_eq!(list.lines().count(), 2);
+    assert!(list
+        .lines()
+        .next()
+        .unwrap()
+        .contains(&first.public_key().to_bech32().unwrap()));
+    assert!(list
+        .lines()
+        .nth(1)
+        .unwrap()
+        .ends_with("20 leading zero bits"));
+
+    // an entry is found by its npub or by its number in the list
+    let npub = second.public_key().to_bech32().unwrap();
+    assert_eq!(keystore.find(&npub), keystore.find("2"));
+    assert!(keystore.find("0").is_none() && keystore.find("3").is_none());
+    let exported = decrypt_secret_key(&keystore.find("1").unwrap().ncryptsec, "frog").unwrap();
+    assert_eq!(exported, first.secret_key().unwrap());
+
+    // the temporary file of the atomic write is gone
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    assert!(!std::path::Path::new(&tmp).exists());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn leading_zero_bits_follow_a_geometric_distribution() {
+    let mut rng = rand::thread_rng();
+    let samples = 1 << 16;
+    let histogram =
+        leading_zero_histogram((0..samples).map(|_| rand::Rng::gen::<[u8; 32]>(&mut rng)));
+    assert_eq!(histogram.iter().sum::<u64>(), samples);
+    // each extra bit halves the count, while there are enough keys to tell
+    for bits in 0..5 {
+        let ratio = histogram[bits] as f64 / histogram[bits + 1] as f64;
+        assert!((1.6..2.5).contains(&ratio), "{bits} bits: ratio {ratio}");
+    }
+
+    let bins = histogram_bins(&histogram);
+    assert_eq!(bins[0].expected, samples as f64 / 2.0);
+    assert_eq!(bins[3].expected, samples as f64 / 16.0);
+    assert_eq!(format_histogram(&bins).lines().count(), histogram.len() + 1);
+
+    // mined keys are spread over the cores without losing any
+    let mined = mine_difficulty_histogram(1_001, 3);
+    assert_eq!(mined.iter().sum::<u64>(), 1_001);
+    let mut rarest = [0_u8; 32];
+    rarest[31] = 1;
+    assert_eq!(
+        leading_zero_histogram([[0xff; 32], rarest].into_iter()).len(),
+        256
+    );
+}
+
+#[test]
+fn first_npub_chars_are_bech32_and_evenly_spread() {
+    let distribution = mine_first_char_distribution(3_200, 3);
+    assert_eq!(distribution.values().sum::<u64>(), 3_200);
+    assert!(distribution
+        .keys()
+        .all(|first| BECH32_CHARSET.contains(*first)));
+    // the top five bits of the key pick the character, so all turn up
+    assert_eq!(distribution.len(), 32, "{distribution:?}");
+
+    let bins = first_char_bins(&distribution);
+    assert_eq!(bins.len(), 32);
+    assert!(bins.iter().all(|bin| bin.expected == 100.0));
+    let table = format_first_char_distribution(&bins);
+    assert_eq!(table.lines().count(), 33);
+    assert!(!table.contains("Never seen"));
+
+    let unseen = format_first_char_distribution(&first_char_bins(&BTreeMap::from([('q', 5)])));
+    assert!(unseen.ends_with("Never seen in this sample: pzry9x8gf2tvdw0s3jn54khce6mua7l"));
+}
+
+#[test]
+fn secret_format_selects_the_secret_encodings() {
+    let keys =
+        Keys::from_sk_str("3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683")
+            .unwrap();
+    let hex_line = "Nostr private key:         3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683";
+    let nsec = keys.secret_key().unwrap().to_bech32().unwrap();
+    let nsec_line = format!("Nostr private key (nsec):  {nsec}");
+    let key_match = KeyMatch::new(&keys, "", 0, None, false).unwrap();
+    let render = |secret_format, format| {
+        format_key_match(&key_match.clone().with_secret_format(secret_format), format).unwrap()
+    };
+
+    let both = render(SecretFormat::Both, OutputFormat::Plain);
+    assert!(both.contains(hex_line) && both.contains(&nsec_line));
+    let hex = render(SecretFormat::Hex, OutputFormat::Plain);
+    assert!(hex.contains(hex_line) && !hex.contains(&nsec));
+    assert!(hex.ends_with(&keys.public_key().to_bech32().unwrap()));
+    let nsec_only = render(SecretFormat::Nsec, OutputFormat::Plain);
+    assert!(!nsec_only.contains("Nostr private key: ") && nsec_only.ends_with(&nsec_line));
+
+    assert_eq!(
+        render(SecretFormat::Nsec, OutputFormat::Nak),
+        nsec,
+        "nak reads nsec keys too"
+    );
+    assert!(!render(SecretFormat::Hex, OutputFormat::Jsonl).contains("nsec"));
+}
+
+#[test]
+fn matches_are_counted_per_target() {
+    let prefixes = vec!["rana".to_string(), "h0dl".to_string()];
+    let suffixes = vec!["end".to_string()];
+    assert_eq!(npub_targets(&prefixes, &[], false), prefixes);
+    assert_eq!(npub_targets(&[], &suffixes, false), suffixes);
+    assert_eq!(
+        npub_targets(&prefixes, &suffixes, false),
+        ["rana...end", "h0dl...end"]
+    );
+    assert_eq!(
+        npub_targets(&prefixes, &suffixes, true),
+        ["rana...", "h0dl...", "...end"]
+    );
+
+    // the reported target of a match is one of the counted ones
+    let counts = TargetCounts::new(&npub_targets(&prefixes, &[], false));
+    let npub = "npub1ranaqqqqqqqq";
+    counts.record(&match_npub(npub, &prefixes, &[], false).unwrap());
+    counts.record("rana");
+    // a prefix read from stdin later on
+    counts.record("fr0g");
+    assert_eq!(
+        counts.snapshot(),
+        [
+            ("rana".to_string(), 2),
+            ("h0dl".to_string(), 0),
+            ("fr0g".to_string(), 1)
+        ]
+    );
+    assert_eq!(
+        format_target_counts(&counts.snapshot()),
+        "Matches per target:\n  rana  2\n  h0dl  0 (outstanding)\n  fr0g  1"
+    );
+
+    // counted from several threads at once
+    let counts = std::sync::Arc::new(TargetCounts::new(&prefixes));
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let counts = counts.clone();
+            std::thread::spawn(move || (0..250).for_each(|_| counts.record("h0dl")))
+        })
+        .collect();
+    workers.into_iter().for_each(|w| w.join().unwrap());
+    assert_eq!(counts.snapshot()[1], ("h0dl".to_string(), 1_000));
+}
+
+#[test]
+fn resumed_sequential_searches_skip_tested_keys() {
+    let start =
+        SecretKey::from_str("0000000000000000000000000000000000000000000000000000000000000001")
+            .unwrap();
+    let key_at = |offset| ScalarWalk::new(start, offset, 1).next().unwrap().0;
+
+    // two threads with a stride of 1: thread 0 tests 0, 2, 4, ... and thread 1 tests 1, 3, 5, ...
+    let progress = WalkProgress::new(2, 1, 2);
+    assert_eq!(progress.resume_scalar(start), Some(start));
+    for (_, offset) in ScalarWalk::new(start, 0, 2).take(5) {
+        progress.record(0, offset);
+    }
+    for (_, offset) in ScalarWalk::new(start, 1, 2).take(3) {
+        progress.record(1, offset);
+    }
+    // every offset below 6 was tested, the slower thread decides
+    assert_eq!(progress.resume_offset(), 6);
+    assert_eq!(progress.resume_scalar(start), Some(key_at(6)));
+
+    // a search stopped halfway resumes to the same match
+    let is_match = |key: &SecretKey| {
+        let keys = Keys::new(*key);
+        get_leading_zero_bits(&keys.public_key().serialize()) >= 6
+    };
+    let (expected, match_offset) = ScalarWalk::new(start, 0, 1)
+        .find(|(key, _)| is_match(key))
+        .unwrap();
+    let progress = WalkProgress::new(1, 1, 1);
+    for (_, offset) in ScalarWalk::new(start, 0, 1).take(match_offset as usize / 2) {
+        progress.record(0, offset);
     }
+    let resume_from = progress.resume_scalar(start).unwrap();
+    let path = std::env::temp_dir().join(format!("rana-checkpoint-{}", std::process::id()));
+    write_checkpoint(&path, &resume_from).unwrap();
+    let saved = SecretKey::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(saved, resume_from);
+
+    let (found, resumed_offset) = ScalarWalk::new(saved, 0, 1)
+        .find(|(key, _)| is_match(key))
+        .unwrap();
+    assert_eq!(found, expected);
+    assert_eq!(
+        resumed_offset,
+        match_offset - progress.resume_offset() as u128
+    );
+}
+
+#[test]
+fn env_output_is_valid_shell_assignments() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let mnemonic = Mnemonic::parse(
+        "congress evoke onion donate fantasy soccer project fiction envelope body faith mean",
+    )
+    .unwrap();
+    let output = format_key_match(
+        &KeyMatch::new(&keys, "", 10, Some(&mnemonic), false).unwrap(),
+        OutputFormat::Env,
+    )
+    .unwrap();
+    let names: Vec<&str> = output
+        .lines()
+        .map(|line| line.split_once('=').unwrap().0)
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "NOSTR_SECRET_KEY",
+            "NOSTR_NSEC",
+            "NOSTR_PUBLIC_KEY",
+            "NOSTR_NPUB",
+            "NOSTR_MNEMONIC"
+        ]
+    );
+
+    // a real shell reads back the exact values
+    let script = format!(
+        "{output}\nprintf '%s\\n' \"$NOSTR_SECRET_KEY\" \"$NOSTR_NPUB\" \"$NOSTR_MNEMONIC\""
+    );
+    let shell = std::process::Command::new("sh")
+        .args(["-c", &script])
+        .output()
+        .unwrap();
+    assert!(shell.status.success());
+    assert_eq!(
+        String::from_utf8(shell.stdout).unwrap(),
+        format!(
+            "{TEST_SECRET_KEY}\n{}\n{mnemonic}\n",
+            keys.public_key().to_bech32().unwrap()
+        )
+    );
+
+    assert_eq!(shell_quote("it's $HOME"), r"'it'\''s $HOME'");
+}
+
+#[test]
+fn interactive_answers_pick_what_to_do_with_a_match() {
+    use std::io::Cursor;
+
+    assert_eq!(
+        parse_match_choice("\n", false),
+        Some(MatchChoice::KeepMining)
+    );
+    assert_eq!(
+        parse_match_choice(" K ", false),
+        Some(MatchChoice::KeepMining)
+    );
+    assert_eq!(parse_match_choice("save", true), Some(MatchChoice::Save));
+    assert_eq!(parse_match_choice("s", false), None);
+    assert_eq!(parse_match_choice("q", false), Some(MatchChoice::Stop));
+
+    // invalid answers ask again, the end of the input stops
+    let mut prompt = Vec::new();
+    let choice = prompt_match_choice(&mut Cursor::new("x\ns\n"), &mut prompt, true).unwrap();
+    assert_eq!(choice, MatchChoice::Save);
+    assert_eq!(
+        String::from_utf8(prompt).unwrap().matches("[s]ave").count(),
+        2
+    );
+    let choice = prompt_match_choice(&mut Cursor::new(""), &mut Vec::new(), false).unwrap();
+    assert_eq!(choice, MatchChoice::Stop);
+}
+
+#[test]
+fn addresses_are_truncated_like_clients_do() {
+    let npub = "npub1pszk2fupse037w0pfjh5z65agma2gp3rnzrjhhtdpqkfrplnde2s5a27nf";
+
+    assert_eq!(truncate_address(npub, 8, 4), "npub1psz...27nf");
+    assert_eq!(truncate_address(npub, 12, 6), "npub1pszk2fu...5a27nf");
+    assert_eq!(truncate_address(npub, 16, 8), "npub1pszk2fupse0...2s5a27nf");
+    assert_eq!(truncate_address("npub1short", 8, 4), "npub1short");
+    assert_eq!(
+        format_address_examples(npub),
+        "Shown truncated in clients as:\n  \
+         first  8 + last 4:  npub1psz...27nf\n  \
+         first 12 + last 6:  npub1pszk2fu...5a27nf\n  \
+         first 16 + last 8:  npub1pszk2fupse0...2s5a27nf"
+    );
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn metrics_endpoint_serves_prometheus_lines() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use crate::metrics::*;
+
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let best = KeyMatch::new(&keys, "", 12, None, false).unwrap();
+    let metrics = Metrics::new(
+        Arc::new(AtomicU64::new(1234)),
+        Arc::new(AtomicU64::new(2)),
+        Arc::new(Mutex::new(Some(best))),
+        Instant::now(),
+    );
+    let addr = serve_metrics("127.0.0.1:0", Arc::new(metrics)).unwrap();
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK"));
+
+    let samples: Vec<(&str, f64)> = body
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| {
+            let (name, value) = line.split_once(' ').unwrap();
+            assert!(name.chars().all(|c| c.is_ascii_lowercase() || c == '_'));
+            (name, value.parse().unwrap())
+        })
+        .collect();
+    let names: Vec<&str> = samples.iter().map(|(name, _)| *name).collect();
+    assert_eq!(
+        names,
+        [
+            "rana_iterations_total",
+            "rana_hashrate",
+            "rana_best_leading_zero_bits",
+            "rana_matches_total",
+            "rana_uptime_seconds"
+        ]
+    );
+    assert_eq!(samples[0].1, 1234.0);
+    assert_eq!(samples[2].1, 12.0);
+    assert_eq!(samples[3].1, 2.0);
+    assert!(body.contains("# TYPE rana_iterations_total counter\n"));
+}
+
+#[test]
+fn nprofile_matches_the_nip19_test_vector() {
+    use clap::Parser;
+
+    let public_key = XOnlyPublicKey::from_str(
+        "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d",
+    )
+    .unwrap();
+    let relays = [
+        String::from("wss://r.x.com"),
+        String::from("wss://djbas.sadkb.com"),
+    ];
+    assert_eq!(
+        encode_nprofile(&public_key, &relays).unwrap(),
+        "nprofile1qqsrhuxx8l9ex335q7he0f09aej04zpazpl0ne2cgukyawd24mayt8gpp4mhxue69uhhytnc9e3k7mgpz4mhxue69uhkg6nzv9ejuumpv34kytnrdaksjlyr9p"
+    );
+
+    let args = CLIArgs::parse_from([
+        "rana",
+        "--nprofile-relays",
+        "wss://r.x.com,wss://djbas.sadkb.com",
+    ]);
+    assert_eq!(args.nprofile_relays, relays);
+    assert!(parse_relay_url("https://r.x.com").is_err());
+    assert!(parse_relay_url(&format!("wss://{}.com", "r".repeat(250))).is_err());
+}
+
+#[test]
+fn mnemonic_accounts_follow_nip06() {
+    use crate::mnemonic::*;
+
+    let phrase = "equal dragon fabric refuse stable cherry smoke allow alley easy never medal attend together lumber movie what sad siege weather matrix buffalo state shoot";
+    let mnemonic = Mnemonic::from_str(phrase).unwrap();
+    let accounts = sweep_accounts(&mnemonic, "", 3, |_| Some(String::new())).unwrap();
+    assert_eq!(accounts.len(), 3);
+    // account 0 is the key every NIP-06 client restores
+    assert_eq!(
+        accounts[0]
+            .1
+            .secret_key()
+            .unwrap()
+            .display_secret()
+            .to_string(),
+        "06992419a8fe821dd8de03d4c300614e8feefb5ea936b76f89976dcace8aebee"
+    );
+    assert_ne!(accounts[1].1.public_key(), accounts[2].1.public_key());
+    assert_eq!(nip06_path(2), "m/44'/1237'/2'/0/0");
+
+    let (valid, rejected) = read_mnemonics(std::io::Cursor::new(format!(
+        "# comment\n\n{phrase}\nnot a mnemonic\n"
+    )));
+    assert_eq!(valid.len(), 1);
+    assert_eq!(valid[0].0, 3);
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(rejected[0].0, 4);
+}
+
+#[test]
+fn validate_mnemonic_diagnoses_typed_phrases() {
+    use crate::mnemonic::*;
+
+    let valid =
+        "congress evoke onion donate fantasy soccer project fiction envelope body faith mean";
+    assert_eq!(validate_mnemonic(valid), Ok((12, bip39::Language::English)));
+    // extra spaces are how phrases get typed
+    assert!(validate_mnemonic(&format!("  {}  ", valid.replace(' ', "   "))).is_ok());
+    // words shared with the French list are told apart by the checksum
+    let shared = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+    assert_eq!(
+        validate_mnemonic(shared),
+        Ok((12, bip39::Language::English))
+    );
+    let spanish = Mnemonic::from_entropy_in(bip39::Language::Spanish, &[7; 16]).unwrap();
+    assert_eq!(
+        validate_mnemonic(&spanish.to_string()),
+        Ok((12, bip39::Language::Spanish))
+    );
+
+    assert_eq!(
+        validate_mnemonic(&valid.replace("fantasy", "fantazy")).unwrap_err(),
+        "Word 5 of 12, 'fantazy', is not in any BIP-39 word list"
+    );
+    let checksum = validate_mnemonic(&valid.replace("mean", "abandon")).unwrap_err();
+    assert!(checksum.contains("checksum does not match"), "{checksum}");
+    let swapped =
+        validate_mnemonic(&valid.replace("congress evoke", "evoke congress")).unwrap_err();
+    assert!(swapped.contains("checksum does not match"), "{swapped}");
+    assert_eq!(
+        validate_mnemonic("congress evoke onion").unwrap_err(),
+        "A mnemonic has 12, 18 or 24 words, not 3"
+    );
+}
+
+#[test]
+fn mnemonic_errors_name_what_is_wrong() {
+    use crate::mnemonic::*;
+
+    let valid =
+        "congress evoke onion donate fantasy soccer project fiction envelope body faith mean";
+    assert!(parse_mnemonic(valid).is_ok());
+    assert_eq!(
+        parse_mnemonic("congress evoke onion").unwrap_err(),
+        MnemonicError::InvalidWordCount(3)
+    );
+    assert_eq!(
+        parse_mnemonic("").unwrap_err(),
+        MnemonicError::InvalidWordCount(0)
+    );
+    assert_eq!(
+        parse_mnemonic(&valid.replace("onion", "oniion")).unwrap_err(),
+        MnemonicError::InvalidWords("unknown word 'oniion'".to_string())
+    );
+    // every word is known, but the last one does not fit the checksum
+    assert!(matches!(
+        parse_mnemonic(&valid.replace("mean", "abandon")),
+        Err(MnemonicError::InvalidWords(_))
+    ));
+
+    assert_eq!(
+        generate_mnemonic(13).unwrap_err(),
+        MnemonicError::InvalidWordCount(13)
+    );
+    assert_eq!(
+        generate_mnemonic(30).unwrap_err().to_string(),
+        "A mnemonic has 12, 18 or 24 words, not 30"
+    );
+    for word_count in WORD_COUNTS {
+        let mnemonic = generate_mnemonic(word_count).unwrap();
+        assert_eq!(mnemonic.word_count(), word_count);
+        assert!(keys_from_mnemonic(&mnemonic, "").is_ok());
+    }
+}
+
+#[test]
+fn matches_tell_their_scheme_and_origin() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let random = KeyMatch::new(&keys, "", 3, None, false).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&format_key_match(&random, OutputFormat::Jsonl).unwrap()).unwrap();
+    assert_eq!(json["scheme"], "secp256k1-xonly");
+    assert_eq!(json["origin"], "random");
+    assert!(json.get("derivation_path").is_none());
+    assert!(format_key_match(&random, OutputFormat::Plain)
+        .unwrap()
+        .contains("Key type:                  secp256k1-xonly (BIP-340 Schnorr), random\n"));
+
+    let mnemonic = Mnemonic::parse(
+        "congress evoke onion donate fantasy soccer project fiction envelope body faith mean",
+    )
+    .unwrap();
+    let derived = Keys::from_mnemonic(mnemonic.to_string(), None).unwrap();
+    let from_mnemonic = KeyMatch::new(&derived, "", 3, Some(&mnemonic), false).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&format_key_match(&from_mnemonic, OutputFormat::Jsonl).unwrap())
+            .unwrap();
+    assert_eq!(json["scheme"], "secp256k1-xonly");
+    assert_eq!(json["origin"], "mnemonic");
+    assert_eq!(json["derivation_path"], "m/44'/1237'/0'/0/0");
+}
+
+#[test]
+fn every_rng_draws_valid_keys_and_chacha_replays_its_seed() {
+    let secp = Secp256k1::new();
+    for kind in [RngKind::Thread, RngKind::Os, RngKind::Chacha] {
+        let mut rng = new_rng(kind, None, 0);
+        for _ in 0..10 {
+            let (secret_key, _) = secp.generate_keypair(&mut rng);
+            assert!(is_well_formed(&secp, &Keys::new(secret_key)), "{kind:?}");
+        }
+        let mnemonic = generate_mnemonic_with(&mut rng, 12).unwrap();
+        assert_eq!(mnemonic.word_count(), 12);
+    }
+
+    let draw = |seed: Option<u64>, stream: u64| {
+        let mut rng = new_rng(RngKind::Chacha, seed, stream);
+        (0..3)
+            .map(|_| secp.generate_keypair(&mut rng).0)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(draw(Some(42), 0), draw(Some(42), 0));
+    assert_ne!(draw(Some(42), 0), draw(Some(43), 0));
+    // seeded cores walk their own streams
+    assert_ne!(draw(Some(42), 0), draw(Some(42), 1));
+    assert_ne!(draw(None, 0), draw(None, 0));
+    assert_eq!(
+        generate_mnemonic_with(&mut new_rng(RngKind::Chacha, Some(7), 0), 24).unwrap(),
+        generate_mnemonic_with(&mut new_rng(RngKind::Chacha, Some(7), 0), 24).unwrap()
+    );
+}
+
+#[test]
+fn pubkey_bytes_are_shown_with_their_leading_zero_bits() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let bytes = keys.public_key().serialize();
+    let shown = format_pubkey_bytes(&bytes);
+    let mut lines = shown.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        format!(
+            "Public key bytes (32, {} leading zero bits):",
+            get_leading_zero_bits(&bytes)
+        )
+    );
+    let shown_bytes: Vec<&str> = lines
+        .flat_map(|row| row.split_once(':').unwrap().1.split_whitespace())
+        .collect();
+    assert_eq!(shown_bytes.len(), 32);
+    assert_eq!(shown_bytes.concat(), keys.public_key().to_string());
+
+    let mut rare = [0xff; 32];
+    rare[..2].copy_from_slice(&[0x00, 0x0f]);
+    assert!(format_pubkey_bytes(&rare).starts_with(
+        "Public key bytes (32, 12 leading zero bits):\n  00: 00 0f ff ff ff ff ff ff\n  08: ff"
+    ));
+}
+
+#[test]
+fn csv_rows_quote_only_what_needs_it() {
+    assert_eq!(csv_field("npub1abc"), "npub1abc");
+    assert_eq!(csv_field("frog, toad"), "\"frog, toad\"");
+    assert_eq!(csv_field("say \"ribbit\""), r#""say ""ribbit""""#);
+    assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    assert_eq!(csv_field("crlf\r\nline"), "\"crlf\r\nline\"");
+    assert_eq!(csv_field("\""), r#""""""#);
+    assert_eq!(csv_field(""), "");
+    // all of them at once, as RFC 4180 has it
+    assert_eq!(
+        csv_field("a \"frog\", a toad\nand a newt"),
+        "\"a \"\"frog\"\", a toad\nand a newt\""
+    );
+
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let key_match = KeyMatch {
+        found_at_secs: 1.5,
+        ..KeyMatch::new(&keys, "", 12, None, false).unwrap()
+    };
+    let nsec = key_match.nsec.clone().unwrap();
+    let row = |index: u64| {
+        format!(
+            "{index},{},{nsec},{},12,1.5",
+            key_match.npub, key_match.pubkey
+        )
+    };
+    let rows = CsvRows::new();
+    assert_eq!(
+        rows.with_next_row(&key_match, str::to_string),
+        format!("{CSV_HEADER}\n{}", row(1))
+    );
+    assert_eq!(rows.with_next_row(&key_match, str::to_string), row(2));
+    // the formatter shares the header of the process, it never repeats it
+    let formatted: Vec<String> = (0..3)
+        .map(|_| format_key_match(&key_match, OutputFormat::Csv).unwrap())
+        .collect();
+    assert_eq!(formatted.concat().matches(CSV_HEADER).count(), 1);
+    assert!(formatted[2].starts_with("3,"), "{formatted:?}");
+    let public_only = KeyMatch::new(
+        &Keys::from_public_key(keys.public_key()),
+        "",
+        12,
+        None,
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        format_csv_row(&public_only, 7),
+        format!("7,{},,{},12,0", public_only.npub, public_only.pubkey)
+    );
+}
+
+#[test]
+fn tag_is_part_of_every_format() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let key_match = KeyMatch {
+        tag: Some(String::from("support bot")),
+        ..KeyMatch::new(&keys, "", 3, None, false).unwrap()
+    };
+
+    let json: serde_json::Value =
+        serde_json::from_str(&format_key_match(&key_match, OutputFormat::Jsonl).unwrap()).unwrap();
+    assert_eq!(json["tag"], "support bot");
+    assert!(format_key_match(&key_match, OutputFormat::Plain)
+        .unwrap()
+        .contains("Tag:                       support bot\n"));
+    assert!(format_key_match(&key_match, OutputFormat::Env)
+        .unwrap()
+        .ends_with("RANA_TAG='support bot'"));
+
+    // untagged runs do not get an empty field
+    let untagged = KeyMatch::new(&keys, "", 3, None, false).unwrap();
+    assert!(!format_key_match(&untagged, OutputFormat::Jsonl)
+        .unwrap()
+        .contains("tag"));
+}
+
+#[test]
+fn multibyte_labels_survive_every_format() {
+    // an emoji, a flag of two code points and an e with a combining accent
+    let label = "🐸 frog 🇩🇰 cafe\u{301}";
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let key_match = KeyMatch {
+        tag: Some(label.to_string()),
+        tier: Some("🥇".to_string()),
+        ..KeyMatch::new(&keys, "", 3, None, false).unwrap()
+    };
+
+    let json: serde_json::Value =
+        serde_json::from_str(&format_key_match(&key_match, OutputFormat::Jsonl).unwrap()).unwrap();
+    assert_eq!(json["tag"], label);
+    assert_eq!(json["tier"], "🥇");
+    assert!(format_key_match(&key_match, OutputFormat::Plain)
+        .unwrap()
+        .contains(&format!("Tag:                       {label}\n")));
+    assert!(format_key_match(&key_match, OutputFormat::Env)
+        .unwrap()
+        .ends_with(&format!("RANA_TAG='{label}'")));
+    let compact = format_key_match(&key_match, OutputFormat::Compact).unwrap();
+    assert!(
+        compact.contains(&format!("tier=🥇 tag='{label}'")),
+        "{compact}"
+    );
+
+    let mut keystore = Keystore::default();
+    keystore.add(&keys, label, "password", 1).unwrap();
+    assert!(keystore.format_list().ends_with(label));
+}
+
+#[test]
+fn found_table_pads_multibyte_vanity_by_characters() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let matches = vec![
+        KeyMatch::new(&keys, "é".repeat(8).as_str(), 3, None, false).unwrap(),
+        KeyMatch::new(&keys, "", 3, None, false).unwrap(),
+    ];
+    let table = format_found_table(&matches, true);
+    // the column is as wide as its widest entry, in characters
+    let lines: Vec<&str> = table.lines().collect();
+    assert!(
+        lines[1].contains(&format!("{}  nsec1", "é".repeat(8))),
+        "{table}"
+    );
+    assert!(
+        lines[2].contains(&format!("-{}  nsec1", " ".repeat(7))),
+        "{table}"
+    );
+}
+
+#[test]
+fn vanity_and_extra_zeros_are_both_required() {
+    let prefixes = vec![String::from("rana")];
+    let mut rare = [0xff_u8; 32];
+    rare[0] = 0x00;
+    rare[1] = 0x0f;
+    let common = [0x80_u8; 32];
+    let combined = |bech_key: &str, public_key: &[u8], extra_zeros: u8| {
+        let matched = match_npub(bech_key, &prefixes, &[], false)?;
+        Some((matched, extra_zero_bits(public_key, extra_zeros)?))
+    };
+
+    assert_eq!(
+        combined("npub1rana", &rare, 12),
+        Some((String::from("rana"), 12))
+    );
+    assert_eq!(combined("npub1rana", &rare, 13), None);
+    assert_eq!(combined("npub1rana", &common, 1), None);
+    assert_eq!(combined("npub1frog", &rare, 12), None);
+
+    // both aspects are shown with the match
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let plain = format_key_match(
+        &KeyMatch::new(&keys, "rana", 12, None, false).unwrap(),
+        OutputFormat::Plain,
+    )
+    .unwrap();
+    assert!(plain.starts_with("Leading zero bits:         12\nVanity npub found:         rana\n"));
+}
+
+#[test]
+fn cpu_topology_is_detected() {
+    let topology = CpuTopology::detect(3);
+    assert!(topology.logical >= 1);
+    assert!(topology.physical >= 1);
+    assert_eq!(
+        topology.available,
+        std::thread::available_parallelism().unwrap().get()
+    );
+    assert_eq!(topology.used, 3);
+
+    let hyperthreaded = CpuTopology {
+        logical: 8,
+        physical: 4,
+        available: 8,
+        used: 8,
+    };
+    assert_eq!(
+        format_cpu_topology(&hyperthreaded),
+        "CPU: 8 logical cores on 4 physical cores, 8 available, mining with 8\n\
+         Note: 8 threads share 4 physical cores, so each thread mines slower than a lone one"
+    );
+    assert!(!format_cpu_topology(&CpuTopology {
+        used: 4,
+        ..hyperthreaded
+    })
+    .contains("Note"));
+}
+
+#[test]
+fn report_limit_hides_small_finds() {
+    assert!(is_reported(12, 0));
+    assert!(is_reported(12, 12));
+    assert!(!is_reported(11, 12));
+}
+
+#[test]
+fn key_pairs_are_verified() {
+    let nsec = "nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5";
+    let npub = "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg";
+    let hex_secret = "67dea2ed018072d675f5415ecfaed7d2597555e202d85b3d65ea4e58d2d92ffa";
+    let hex_public = "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e";
+
+    assert!(verify_key_pair(nsec, npub).unwrap());
+    assert!(verify_key_pair(hex_secret, hex_public).unwrap());
+    assert!(verify_key_pair(&format!(" {nsec}\n"), npub).unwrap());
+    let other = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    assert!(!verify_key_pair(nsec, &other.public_key().to_bech32().unwrap()).unwrap());
+
+    assert_eq!(
+        verify_key_pair("nsec1oops", npub).unwrap_err().to_string(),
+        "The nsec is not a valid secret key"
+    );
+    assert_eq!(
+        verify_key_pair(nsec, &npub[..40]).unwrap_err().to_string(),
+        "The npub is not a valid public key"
+    );
+}
+
+#[test]
+fn base64_encodings_decode_to_the_raw_key_bytes() {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let key_match = KeyMatch::new(&keys, "", 0, None, false)
+        .unwrap()
+        .with_encodings(SecretFormat::Both, true);
+
+    let public = STANDARD
+        .decode(key_match.pubkey_base64.as_ref().unwrap())
+        .unwrap();
+    assert_eq!(public, keys.public_key().serialize());
+    let secret = STANDARD
+        .decode(key_match.secret_key_base64.as_ref().unwrap())
+        .unwrap();
+    assert_eq!(secret, keys.secret_key().unwrap().secret_bytes());
+
+    // secrets that are not shown are not encoded either
+    let nsec_only = KeyMatch::new(&keys, "", 0, None, false)
+        .unwrap()
+        .with_encodings(SecretFormat::Nsec, true);
+    assert!(nsec_only.pubkey_base64.is_some());
+    assert_eq!(nsec_only.secret_key_base64, None);
+    let redacted = KeyMatch::new(&keys, "", 0, None, true)
+        .unwrap()
+        .with_base64();
+    assert_eq!(redacted.secret_key_base64.as_deref(), Some(REDACTED));
+    assert_eq!(hex_bytes("0aff"), Some(vec![0x0a, 0xff]));
+    assert_eq!(hex_bytes("0af"), None);
+}
+
+#[test]
+fn nip13_difficulty_is_a_difficulty_in_bits() {
+    use clap::Parser;
+
+    let bits = |args: &[&str]| {
+        CLIArgs::try_parse_from([&["rana"], args].concat())
+            .map(|args| args.difficulty_bits())
+            .ok()
+    };
+    assert_eq!(bits(&["--nip13-difficulty", "21"]), Some(21));
+    assert_eq!(bits(&["--difficulty", "21"]), Some(21));
+    assert_eq!(bits(&["--min-zero-bytes", "2"]), Some(16));
+    // 32 bytes would be 256 bits, past what a difficulty can hold
+    assert_eq!(bits(&["--min-zero-bytes", "31"]), Some(248));
+    assert_eq!(bits(&["--min-zero-bytes", "32"]), None);
+    assert_eq!(bits(&[]), Some(0));
+    // only one way to give the difficulty
+    assert_eq!(bits(&["--nip13-difficulty", "21", "-d", "20"]), None);
+    assert_eq!(bits(&["--nip13-difficulty", "21", "-n", "rana"]), None);
+}
+
+#[test]
+fn bloom_filter_remembers_the_keys_it_was_given() {
+    let filter = BloomFilter::with_capacity(1000, DEDUP_FALSE_POSITIVE_RATE);
+    // about 43 bits per key for a one in a billion false positive rate
+    assert_eq!(filter.size_bytes(), 5392);
+
+    let keys: Vec<[u8; 32]> = (0..1000)
+        .map(|_| Keys::generate().public_key().serialize())
+        .collect();
+    for key in &keys {
+        assert!(filter.insert(key));
+    }
+    for key in &keys {
+        assert!(filter.contains(key));
+        assert!(!filter.insert(key));
+    }
+    let unseen = Keys::generate().public_key().serialize();
+    assert!(!filter.contains(&unseen));
+}
+
+#[test]
+fn anchored_suffixes_match_the_key_data_not_the_checksum() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let npub = keys.public_key().to_bech32().unwrap();
+    let anchored = anchored_key_data(&npub).unwrap();
+    // one character for the first bit, then 51 for the other 255
+    assert_eq!(anchored.len(), 52);
+    assert!(anchored.starts_with(['q', 'p']));
+
+    // the last character holds the last 5 bits of the key
+    let last_byte = keys.public_key().serialize()[31];
+    let last_char = BECH32_CHARSET.as_bytes()[(last_byte & 0x1f) as usize] as char;
+    assert!(anchored.ends_with(last_char));
+
+    let data_suffix = vec![anchored[anchored.len() - 3..].to_string()];
+    assert_eq!(
+        match_npub_anchored(&npub, &[], &data_suffix, false),
+        Some(data_suffix[0].clone())
+    );
+
+    // the last characters of the npub are its checksum
+    let checksum_suffix = vec![npub[npub.len() - 3..].to_string()];
+    assert_ne!(checksum_suffix, data_suffix);
+    assert!(match_npub(&npub, &[], &checksum_suffix, false).is_some());
+    assert_eq!(
+        match_npub_anchored(&npub, &[], &checksum_suffix, false),
+        None
+    );
+
+    // prefixes are matched as before
+    let prefix = vec![npub[5..9].to_string()];
+    assert_eq!(
+        match_npub_anchored(&npub, &prefix, &data_suffix, false),
+        Some(format!("{}...{}", prefix[0], data_suffix[0]))
+    );
+}
+
+#[test]
+fn known_pubkeys_are_read_in_hex_and_as_npubs() {
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let other = Keys::generate();
+    let file = format!(
+        "# owned\n{}\n\n{}\ndead\nnpub1abc\n",
+        keys.public_key(),
+        other.public_key().to_bech32().unwrap()
+    );
+    let (known, rejected) = read_known_pubkeys(file.as_bytes());
+    assert_eq!(known.len(), 2);
+    assert!(known.contains(&keys.public_key()));
+    assert!(known.contains(&other.public_key()));
+    assert!(!known.contains(&Keys::generate().public_key()));
+    let lines: Vec<usize> = rejected.iter().map(|(line, _)| *line).collect();
+    assert_eq!(lines, vec![5, 6]);
+}
+
+#[test]
+fn pattern_lines_are_parsed_by_kind() {
+    let file = "# targets\nhex:DEAD\n\nnpub:cafe\nnpub:b1o\nhex:xyz\ncafe\nTXT:abc\n";
+    let (patterns, rejected) = read_patterns(file.as_bytes());
+    assert_eq!(patterns.hex, vec!["dead"]);
+    assert_eq!(patterns.npub, vec!["cafe"]);
+    let lines: Vec<usize> = rejected.iter().map(|(line, _)| *line).collect();
+    assert_eq!(lines, vec![5, 6, 7, 8]);
+    assert!(rejected[3].1.contains("unknown pattern kind 'TXT'"));
+
+    // 16 bits and 20 bits: a match in about 2^16 / (1 + 1/16) keys
+    assert_eq!(patterns.pow_difficulty(), 16);
+
+    let keys = Keys::from_sk_str(TEST_SECRET_KEY).unwrap();
+    let npub = keys.public_key().to_bech32().unwrap();
+    let hex = keys.public_key().to_string();
+    let mut own = Patterns::default();
+    own.add(&format!("npub:{}", &npub[5..8])).unwrap();
+    assert_eq!(
+        own.find_match(&keys.public_key()),
+        Some(format!("npub:{}", &npub[5..8]))
+    );
+    own.add(&format!("hex:{}", &hex[..2])).unwrap();
+    assert_eq!(
+        own.find_match(&keys.public_key()),
+        Some(format!("hex:{}", &hex[..2]))
+    );
 }
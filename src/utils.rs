@@ -1,95 +1,2118 @@
-use std::time::Instant;
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::{BufRead, Write as _};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bech32::FromBase32;
 use bip39::Mnemonic;
 use nostr::prelude::*;
 use qrcode::render::unicode;
 use qrcode::QrCode;
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use regex::Regex;
+use serde::Serialize;
 
-/// Benchmark the cores capabilities for key generation
-pub fn benchmark_cores(cores: usize, pow_difficulty: u8) {
-    let mut hashes_per_second_per_core = 0;
+use crate::cli::{Hrp, OutputFormat, QrStyle, RngKind, SecretFormat};
+use crate::mnemonic::{nip06_path, NumberedLines};
 
-    println!("Benchmarking a single core for 5 seconds...");
+/// Placeholder printed instead of secret material when redaction is enabled
+pub const REDACTED: &str = "***redacted***";
+
+/// Print a status line to stdout, or to stderr when stdout is reserved for
+/// machine readable key output
+#[macro_export]
+macro_rules! status {
+    ($format:expr, $($arg:tt)*) => {
+        if $format.is_machine_readable() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// How long the single core benchmark runs for by default
+pub const BENCHMARK_SECONDS: u64 = 5;
+
+/// The generator of `kind` for a core. The ChaCha stream of every core is its
+/// `stream`, so seeded cores don't draw the same keys.
+pub fn new_rng(kind: RngKind, seed: Option<u64>, stream: u64) -> Box<dyn RngCore> {
+    match kind {
+        RngKind::Thread => Box::new(rand::thread_rng()),
+        RngKind::Os => Box::new(rand::rngs::OsRng),
+        RngKind::Chacha => {
+            let mut rng = match seed {
+                Some(seed) => ChaCha20Rng::seed_from_u64(seed),
+                None => ChaCha20Rng::from_entropy(),
+            };
+            rng.set_stream(stream);
+            Box::new(rng)
+        }
+    }
+}
+
+/// A random mnemonic of `word_count` words, from the entropy of `rng`
+pub fn generate_mnemonic_with(rng: &mut dyn RngCore, word_count: usize) -> Result<Mnemonic> {
+    let mut entropy = [0_u8; 64];
+    rng.fill_bytes(&mut entropy);
+    // every 3 words hold 32 bits of entropy, the rest is checksum
+    let length = min(word_count / 3 * 4, entropy.len());
+    Ok(Mnemonic::from_entropy(&entropy[..length])?)
+}
+
+/// How long a core runs the hot loop unmeasured by default before benchmarking
+pub const WARMUP_SECONDS: u64 = 1;
+
+/// Generate key pairs the way the miner does until `duration` elapsed and
+/// return how many were generated
+fn mine_keys_for(use_nostr_keys: bool, duration: Duration) -> u64 {
+    let mut hashes = 0_u64;
     let now = Instant::now();
     let secp = Secp256k1::new();
     let mut rng = rand::thread_rng();
-    loop {
-        let (_secret_key, public_key) = secp.generate_keypair(&mut rng);
-        let (xonly_public_key, _) = public_key.x_only_public_key();
+    while now.elapsed() < duration {
+        let xonly_public_key = if use_nostr_keys {
+            Keys::generate().public_key()
+        } else {
+            let (_secret_key, public_key) = secp.generate_keypair(&mut rng);
+            public_key.x_only_public_key().0
+        };
         get_leading_zero_bits(&xonly_public_key.serialize());
-        hashes_per_second_per_core += 1;
-        if now.elapsed().as_secs() > 5 {
+        hashes += 1;
+    }
+    hashes
+}
+
+/// Measure how many keys per second a single core can mine, with raw
+/// secp256k1 key pairs or, if `use_nostr_keys` is set, with `Keys::generate`
+/// as used by the miner, for the given duration. The hot loop first runs
+/// unmeasured for `warmup`, so the CPU can ramp up its frequency; no warmup
+/// runs when the benchmark itself is skipped with a zero duration.
+pub fn benchmark_core_rate(
+    format: OutputFormat,
+    use_nostr_keys: bool,
+    warmup: Duration,
+    duration: Duration,
+) -> u64 {
+    if !warmup.is_zero() && !duration.is_zero() {
+        status!(
+            format,
+            "Warming up a single core for {} seconds...",
+            warmup.as_secs_f64()
+        );
+        mine_keys_for(use_nostr_keys, warmup);
+    }
+
+    status!(
+        format,
+        "Benchmarking a single core for {} seconds...",
+        duration.as_secs_f64()
+    );
+    let now = Instant::now();
+    let hashes = mine_keys_for(use_nostr_keys, duration);
+    let hashes_per_second_per_core = (hashes as f64 / now.elapsed().as_secs_f64()) as u64;
+    status!(
+        format,
+        "A single core can mine roughly {hashes_per_second_per_core} h/s!"
+    );
+    hashes_per_second_per_core
+}
+
+/// Keys expected to be tried before one meets a pow difficulty: each bit
+/// halves the keys that match
+pub fn expected_attempts(pow_difficulty: u8) -> f64 {
+    2_f64.powi(pow_difficulty as i32)
+}
+
+/// Keys tried before a key was found, as a percentage of the keys expected
+/// for its leading zero bits. Below 100 it was found with less work than
+/// expected.
+pub fn luck_percent(found_at_iteration: u64, leading_zero_bits: u8) -> f64 {
+    found_at_iteration as f64 / expected_attempts(leading_zero_bits) * 100.0
+}
+
+/// Line of the shutdown summary telling how lucky finding a key was
+pub fn format_luck(found_at_iteration: u64, leading_zero_bits: u8) -> String {
+    let percent = luck_percent(found_at_iteration, leading_zero_bits);
+    let precision = if percent < 1.0 { 2 } else { 0 };
+    let verdict = if percent <= 100.0 { "lucky" } else { "unlucky" };
+    format!(
+        "Luck: found at {percent:.precision$}% of the expected work of 2^{leading_zero_bits} keys, {verdict}"
+    )
+}
+
+/// Seconds expected to find a key of the given pow difficulty at a rate
+pub fn estimate_seconds(pow_difficulty: u8, hashes_per_second: f64) -> f64 {
+    expected_attempts(pow_difficulty) / hashes_per_second
+}
+
+/// Write a number of seconds in the largest unit it has at least one of
+pub fn format_eta(seconds: f64) -> String {
+    const UNITS: [(&str, f64); 4] = [
+        ("years", 365.25 * 86400.0),
+        ("days", 86400.0),
+        ("hours", 3600.0),
+        ("minutes", 60.0),
+    ];
+    match UNITS.iter().find(|(_, unit)| seconds >= *unit) {
+        Some(("years", unit)) if seconds / unit >= 1e6 => format!("{:.1e} years", seconds / unit),
+        Some((name, unit)) => format!("{:.1} {name}", seconds / unit),
+        None => format!("{seconds:.1} seconds"),
+    }
+}
+
+/// Describe how hard a pow difficulty is: the expected attempts and, given
+/// the total rate in keys per second, how long they take
+pub fn format_estimate(pow_difficulty: u8, hashes_per_second: Option<u64>) -> String {
+    let attempts = if pow_difficulty < 64 {
+        format!(
+            "{} (2^{pow_difficulty})",
+            group_thousands(expected_attempts(pow_difficulty) as u64)
+        )
+    } else {
+        format!("2^{pow_difficulty}")
+    };
+    let mut out = format!("Difficulty: {pow_difficulty} bits\nExpected attempts: {attempts}");
+    match hashes_per_second {
+        Some(rate) => write!(
+            out,
+            "\nAt {} keys/second this takes about {}",
+            group_thousands(rate),
+            format_eta(estimate_seconds(pow_difficulty, rate as f64))
+        )
+        .unwrap(),
+        None => out.push_str("\nPass --rate to estimate how long this takes"),
+    }
+    out
+}
+
+/// Print the estimated time to find a key of the given pow difficulty, or
+/// that it is unknown when the rate of a core was not benchmarked
+pub fn print_estimate(
+    hashes_per_second_per_core: Option<u64>,
+    cores: usize,
+    pow_difficulty: u8,
+    format: OutputFormat,
+) {
+    status!(
+        format,
+        "Searching for prefix of {pow_difficulty} specific bits"
+    );
+    let Some(hashes_per_second_per_core) = hashes_per_second_per_core else {
+        status!(
+            format,
+            "The hashrate was not benchmarked, so how long this takes is unknown"
+        );
+        return;
+    };
+    let estimate = estimate_seconds(
+        pow_difficulty,
+        hashes_per_second_per_core as f64 * cores as f64,
+    ) as f32;
+    status!(format, "This is estimated to take about {estimate} seconds");
+}
+
+/// Result of the startup benchmark, as printed with --benchmark-json
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BenchmarkResult {
+    pub cores: usize,
+    /// Keys per second of all the cores together
+    pub rate_hps: u64,
+    pub duration_secs: f64,
+}
+
+impl BenchmarkResult {
+    pub fn new(hashes_per_second_per_core: u64, cores: usize, duration: Duration) -> Self {
+        Self {
+            cores,
+            rate_hps: hashes_per_second_per_core.saturating_mul(cores as u64),
+            duration_secs: duration.as_secs_f64(),
+        }
+    }
+}
+
+/// Benchmark the cores capabilities for key generation and return the rate
+/// of a single core
+pub fn benchmark_cores(
+    cores: usize,
+    pow_difficulty: u8,
+    format: OutputFormat,
+    use_nostr_keys: bool,
+    warmup: Duration,
+    duration: Duration,
+) -> u64 {
+    let hashes_per_second_per_core = benchmark_core_rate(format, use_nostr_keys, warmup, duration);
+    print_estimate(
+        Some(hashes_per_second_per_core),
+        cores,
+        pow_difficulty,
+        format,
+    );
+    hashes_per_second_per_core
+}
+
+/// How long all cores mine at once to measure each of them for --shuffle-cores
+pub const SHUFFLE_BENCHMARK: Duration = Duration::from_millis(500);
+
+/// Mine on `cores` threads at the same time for `duration`, so they compete
+/// for the machine like the workers do, and return the rate of each
+pub fn benchmark_threads(cores: usize, use_nostr_keys: bool, duration: Duration) -> Vec<u64> {
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..cores)
+            .map(|_| {
+                scope.spawn(move || {
+                    let now = Instant::now();
+                    let hashes = mine_keys_for(use_nostr_keys, duration);
+                    (hashes as f64 / now.elapsed().as_secs_f64()) as u64
+                })
+            })
+            .collect();
+        threads.into_iter().map(|t| t.join().unwrap()).collect()
+    })
+}
+
+/// Batch sizes proportional to the measured rate of each core: a core of
+/// average speed gets `base`, a core twice as fast twice as much, and every
+/// core at least 1
+pub fn batch_sizes(rates: &[u64], base: u64) -> Vec<u64> {
+    let total: u128 = rates.iter().map(|&rate| rate as u128).sum();
+    if total == 0 {
+        return vec![base; rates.len()];
+    }
+    rates
+        .iter()
+        .map(|&rate| {
+            max(
+                1,
+                (base as u128 * rate as u128 * rates.len() as u128 / total) as u64,
+            )
+        })
+        .collect()
+}
+
+/// Fewest cores that reach `target_rate` together at `core_rate` keys per
+/// second each, between 1 and `max_cores`
+pub fn cores_for_target_rate(core_rate: u64, target_rate: u64, max_cores: usize) -> usize {
+    let needed = target_rate.div_ceil(core_rate.max(1));
+    needed.clamp(1, max_cores.max(1) as u64) as usize
+}
+
+/// Cores detected on this machine, and how many of them rana mines with
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CpuTopology {
+    pub logical: usize,
+    pub physical: usize,
+    /// Cores this process may run on, e.g. limited by a container or affinity
+    pub available: usize,
+    pub used: usize,
+}
+
+impl CpuTopology {
+    pub fn detect(used: usize) -> Self {
+        Self {
+            logical: num_cpus::get(),
+            physical: num_cpus::get_physical(),
+            available: std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+            used,
+        }
+    }
+}
+
+/// Describe the CPU topology, noting when the mining threads share physical
+/// cores through hyperthreading
+pub fn format_cpu_topology(topology: &CpuTopology) -> String {
+    let mut report = format!(
+        "CPU: {} logical cores on {} physical cores, {} available, mining with {}",
+        topology.logical, topology.physical, topology.available, topology.used
+    );
+    if topology.used > topology.physical {
+        let _ = write!(
+            report,
+            "\nNote: {} threads share {} physical cores, so each thread mines slower than a lone one",
+            topology.used, topology.physical
+        );
+    }
+    report
+}
+
+/// Single core rate of one matching path, as measured by `benchmark_paths`
+#[derive(Clone, Debug, Serialize)]
+pub struct PathRate {
+    pub path: &'static str,
+    pub keys_per_second: u64,
+}
+
+/// Run `step` for at least `duration`, and at least once, returning the rate
+fn measure_rate(duration: Duration, mut step: impl FnMut()) -> u64 {
+    let now = Instant::now();
+    let mut steps = 0_u64;
+    loop {
+        step();
+        steps += 1;
+        if now.elapsed() >= duration {
             break;
         }
     }
-    hashes_per_second_per_core /= 10;
-    println!("A single core can mine roughly {hashes_per_second_per_core} h/s!");
+    max(1, (steps as f64 / now.elapsed().as_secs_f64()) as u64)
+}
 
-    let estimated_hashes = 2_u128.pow(pow_difficulty as u32);
-    println!("Searching for prefix of {pow_difficulty} specific bits");
-    let estimate = estimated_hashes as f32 / hashes_per_second_per_core as f32 / cores as f32;
-    println!("This is estimated to take about {estimate} seconds");
+/// Measure the single core rate of each matching path of the miner for
+/// `duration` each: leading zero bits, hex prefix, npub prefix and keys
+/// derived from a 12 word mnemonic
+pub fn benchmark_paths(duration: Duration) -> Vec<PathRate> {
+    let prefixes = vec!["rana".to_string()];
+    vec![
+        PathRate {
+            path: "difficulty",
+            keys_per_second: measure_rate(duration, || {
+                get_leading_zero_bits(&Keys::generate().public_key().serialize());
+            }),
+        },
+        PathRate {
+            path: "hex",
+            keys_per_second: measure_rate(duration, || {
+                let _ = Keys::generate()
+                    .public_key()
+                    .to_string()
+                    .starts_with("0000");
+            }),
+        },
+        PathRate {
+            path: "npub",
+            keys_per_second: measure_rate(duration, || {
+                let bech_key = encode_bech32(&Keys::generate(), Hrp::Npub).unwrap();
+                match_npub(&bech_key, &prefixes, &[], false);
+            }),
+        },
+        PathRate {
+            path: "mnemonic",
+            keys_per_second: measure_rate(duration, || {
+                let mnemonic = Keys::generate_mnemonic(12).unwrap();
+                let keys = Keys::from_mnemonic(mnemonic.to_string(), None).unwrap();
+                get_leading_zero_bits(&keys.public_key().serialize());
+            }),
+        },
+    ]
 }
 
-/// Print private and public keys to the output
-pub fn print_keys(
-    keys: &Keys,
-    vanity_npub: String,
-    leading_zeroes: u8,
-    mnemonic: Option<Mnemonic>,
-) -> Result<()> {
-    if leading_zeroes != 0 {
-        println!("Leading zero bits:         {leading_zeroes}");
-    } else if !vanity_npub.is_empty() {
-        println!("Vanity npub found:         {vanity_npub}")
+/// Table of the path rates, each relative to the fastest one
+pub fn format_path_rates(rates: &[PathRate]) -> String {
+    let fastest = rates
+        .iter()
+        .map(|rate| rate.keys_per_second)
+        .max()
+        .unwrap_or(1);
+    let mut out = format!("{:<12}{:>14}{:>12}", "path", "keys/s", "relative");
+    for rate in rates {
+        let relative = rate.keys_per_second as f64 / fastest as f64;
+        let _ = write!(
+            out,
+            "\n{:<12}{:>14}{:>11.3}x",
+            rate.path,
+            group_thousands(rate.keys_per_second),
+            relative
+        );
     }
+    out
+}
 
-    println!("Found matching public key: {}", keys.public_key());
+/// Count how many of the serialized keys have 0, 1, 2, ... leading zero bits
+pub fn leading_zero_histogram(keys: impl Iterator<Item = [u8; 32]>) -> Vec<u64> {
+    let mut histogram = vec![0_u64; u8::MAX as usize + 1];
+    for key in keys {
+        histogram[get_leading_zero_bits(&key) as usize] += 1;
+    }
+    while histogram.len() > 1 && histogram.last() == Some(&0) {
+        histogram.pop();
+    }
+    histogram
+}
 
-    println!(
-        "Nostr private key: {:>72}",
-        keys.secret_key()?.display_secret()
+/// Generate `iterations` key pairs over `cores` threads and count their
+/// leading zero bits
+pub fn mine_difficulty_histogram(iterations: u64, cores: usize) -> Vec<u64> {
+    let cores = max(1, cores) as u64;
+    let workers: Vec<_> = (0..cores)
+        .map(|core| {
+            // the first cores take the remainder
+            let share = iterations / cores + u64::from(core < iterations % cores);
+            std::thread::spawn(move || {
+                let secp = Secp256k1::new();
+                let mut rng = rand::thread_rng();
+                leading_zero_histogram((0..share).map(|_| {
+                    let (_secret_key, public_key) = secp.generate_keypair(&mut rng);
+                    public_key.x_only_public_key().0.serialize()
+                }))
+            })
+        })
+        .collect();
+
+    let mut histogram: Vec<u64> = Vec::new();
+    for worker in workers {
+        let counts = worker.join().unwrap();
+        if histogram.len() < counts.len() {
+            histogram.resize(counts.len(), 0);
+        }
+        for (total, count) in histogram.iter_mut().zip(counts) {
+            *total += count;
+        }
+    }
+    histogram
+}
+
+/// Observed and expected number of keys with exactly `bits` leading zero bits
+#[derive(Clone, Debug, Serialize)]
+pub struct HistogramBin {
+    pub bits: u8,
+    pub count: u64,
+    pub expected: f64,
+}
+
+/// Pair each count with the geometric expectation: a key has exactly `k`
+/// leading zero bits with a probability of 1 / 2^(k+1)
+pub fn histogram_bins(histogram: &[u64]) -> Vec<HistogramBin> {
+    let total: u64 = histogram.iter().sum();
+    histogram
+        .iter()
+        .enumerate()
+        .map(|(bits, &count)| HistogramBin {
+            bits: bits as u8,
+            count,
+            expected: total as f64 / 2_f64.powi(bits as i32 + 1),
+        })
+        .collect()
+}
+
+/// Table of the histogram bins, with the ratio of observed to expected keys
+pub fn format_histogram(bins: &[HistogramBin]) -> String {
+    let mut out = format!(
+        "{:>4}{:>14}{:>16}{:>8}",
+        "bits", "keys", "expected", "ratio"
     );
+    for bin in bins {
+        let _ = write!(
+            out,
+            "\n{:>4}{:>14}{:>16.1}{:>8.2}",
+            bin.bits,
+            group_thousands(bin.count),
+            bin.expected,
+            bin.count as f64 / bin.expected
+        );
+    }
+    out
+}
+
+/// Generate `iterations` key pairs over `cores` threads and count the first
+/// data character of their npubs, the one right after `npub1`
+pub fn mine_first_char_distribution(iterations: u64, cores: usize) -> BTreeMap<char, u64> {
+    let cores = max(1, cores) as u64;
+    let workers: Vec<_> = (0..cores)
+        .map(|core| {
+            // the first cores take the remainder
+            let share = iterations / cores + u64::from(core < iterations % cores);
+            std::thread::spawn(move || {
+                let secp = Secp256k1::new();
+                let mut rng = rand::thread_rng();
+                let mut counts = BTreeMap::new();
+                for _ in 0..share {
+                    let (_secret_key, public_key) = secp.generate_keypair(&mut rng);
+                    let npub = public_key.x_only_public_key().0.to_bech32().unwrap();
+                    if let Some(first) = npub["npub1".len()..].chars().next() {
+                        *counts.entry(first).or_insert(0) += 1;
+                    }
+                }
+                counts
+            })
+        })
+        .collect();
 
-    println!(
-        "Nostr public key (npub): {:>65}",
-        keys.public_key().to_bech32()?
+    let mut distribution = BTreeMap::new();
+    for worker in workers {
+        for (first, count) in worker.join().unwrap() {
+            *distribution.entry(first).or_insert(0) += count;
+        }
+    }
+    distribution
+}
+
+/// Observed and expected number of npubs starting with `first_char`
+#[derive(Clone, Debug, Serialize)]
+pub struct FirstCharBin {
+    pub first_char: char,
+    pub count: u64,
+    pub expected: f64,
+}
+
+/// A bin for each of the 32 bech32 characters, in charset order. The first
+/// character holds the top 5 bits of the public key, so each is expected in
+/// 1 of 32 npubs.
+pub fn first_char_bins(distribution: &BTreeMap<char, u64>) -> Vec<FirstCharBin> {
+    let total: u64 = distribution.values().sum();
+    BECH32_CHARSET
+        .chars()
+        .map(|first_char| FirstCharBin {
+            first_char,
+            count: distribution.get(&first_char).copied().unwrap_or(0),
+            expected: total as f64 / BECH32_CHARSET.len() as f64,
+        })
+        .collect()
+}
+
+/// Table of the first character bins, with the ratio of observed to expected
+/// npubs and the characters never seen
+pub fn format_first_char_distribution(bins: &[FirstCharBin]) -> String {
+    let mut out = format!(
+        "{:>10}{:>14}{:>16}{:>8}",
+        "first char", "npubs", "expected", "ratio"
     );
+    for bin in bins {
+        let _ = write!(
+            out,
+            "\n{:>10}{:>14}{:>16.1}{:>8.2}",
+            format!("npub1{}", bin.first_char),
+            group_thousands(bin.count),
+            bin.expected,
+            bin.count as f64 / bin.expected
+        );
+    }
+    let unseen: String = bins
+        .iter()
+        .filter(|bin| bin.count == 0)
+        .map(|bin| bin.first_char)
+        .collect();
+    if !unseen.is_empty() {
+        let _ = write!(out, "\nNever seen in this sample: {unseen}");
+    }
+    out
+}
+
+/// Pick the largest difficulty whose expected time (2^pow / rate) fits in the
+/// target time
+pub fn difficulty_for_target_time(hashes_per_second: u64, target_time: Duration) -> u8 {
+    let budget = hashes_per_second as f64 * target_time.as_secs_f64();
+    let mut pow_difficulty = 1_u8;
+    while pow_difficulty < 255 && 2_f64.powi(pow_difficulty as i32 + 1) <= budget {
+        pow_difficulty += 1;
+    }
+    pow_difficulty
+}
+
+/// Signature scheme of every key rana finds: a BIP-340 Schnorr key, whose
+/// public key is an x-only point on secp256k1
+pub const KEY_SCHEME: &str = "secp256k1-xonly";
+
+/// How the secret of a found key was produced
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyOrigin {
+    /// Drawn from the system's random number generator
+    Random,
+    /// Derived from a mnemonic, along its `derivation_path`
+    Mnemonic,
+    /// A step of a sequential search, from `--start-from` or a coordinator
+    Sequential,
+}
+
+impl KeyOrigin {
+    pub const ALL: [KeyOrigin; 3] = [
+        KeyOrigin::Random,
+        KeyOrigin::Mnemonic,
+        KeyOrigin::Sequential,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyOrigin::Random => "random",
+            KeyOrigin::Mnemonic => "mnemonic",
+            KeyOrigin::Sequential => "sequential",
+        }
+    }
+}
+
+/// A found key, as serialized by the machine readable output formats. The
+/// secret fields are left out for public-only keys.
+#[derive(Clone, Debug, Serialize)]
+pub struct KeyMatch {
+    pub pubkey: String,
+    pub npub: String,
+    pub scheme: &'static str,
+    pub origin: KeyOrigin,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubkey_base64: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_key_base64: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nsec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mnemonic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub derivation_path: Option<String>,
+    pub leading_zero_bits: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vanity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    /// Label given to the run with `--tag`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nprofile: Option<String>,
+    /// Seconds into the run and iteration count at which the key was found
+    pub found_at_secs: f64,
+    pub found_at_iteration: u64,
+}
 
-    println!(
-        "Nostr private key (nsec): {:>64}",
-        keys.secret_key()?.to_bech32()?
+impl KeyMatch {
+    pub fn new(
+        keys: &Keys,
+        vanity_npub: &str,
+        leading_zeroes: u8,
+        mnemonic: Option<&Mnemonic>,
+        redact_secret: bool,
+    ) -> Result<Self> {
+        let secret = |value: String| {
+            if redact_secret {
+                REDACTED.to_string()
+            } else {
+                value
+            }
+        };
+
+        let secret_key = keys.secret_key().ok();
+
+        Ok(Self {
+            pubkey: keys.public_key().to_string(),
+            npub: keys.public_key().to_bech32()?,
+            scheme: KEY_SCHEME,
+            origin: match mnemonic {
+                Some(_) => KeyOrigin::Mnemonic,
+                None => KeyOrigin::Random,
+            },
+            pubkey_base64: None,
+            secret_key: secret_key.map(|sk| secret(sk.display_secret().to_string())),
+            secret_key_base64: None,
+            nsec: secret_key
+                .map(|sk| sk.to_bech32().map(secret))
+                .transpose()?,
+            mnemonic: mnemonic.map(|mnemonic| secret(mnemonic.to_string())),
+            // the first account, as in `Keys::from_mnemonic`
+            derivation_path: mnemonic.map(|_| nip06_path(0)),
+            leading_zero_bits: leading_zeroes,
+            vanity: (!vanity_npub.is_empty()).then(|| vanity_npub.to_string()),
+            tier: None,
+            tag: None,
+            nprofile: None,
+            found_at_secs: 0.0,
+            found_at_iteration: 0,
+        })
+    }
+
+    /// Add the base64 encodings of the raw 32 bytes of the public key and, if
+    /// kept, of the hex secret key. A redacted secret stays redacted.
+    pub fn with_base64(self) -> Self {
+        let encode = |hex: &str| match hex_bytes(hex) {
+            Some(bytes) => BASE64.encode(bytes),
+            None => hex.to_string(),
+        };
+        Self {
+            pubkey_base64: Some(encode(&self.pubkey)),
+            secret_key_base64: self.secret_key.as_deref().map(encode),
+            ..self
+        }
+    }
+
+    /// Apply the encoding options of the run: the secret key encodings asked
+    /// for and, with `--base64`, the base64 ones
+    pub fn with_encodings(self, secret_format: SecretFormat, base64: bool) -> Self {
+        let key_match = self.with_secret_format(secret_format);
+        if base64 {
+            key_match.with_base64()
+        } else {
+            key_match
+        }
+    }
+
+    /// Only keep the encodings of the secret key asked for
+    pub fn with_secret_format(self, secret_format: SecretFormat) -> Self {
+        match secret_format {
+            SecretFormat::Hex => Self { nsec: None, ..self },
+            SecretFormat::Nsec => Self {
+                secret_key: None,
+                ..self
+            },
+            SecretFormat::Both => self,
+        }
+    }
+}
+
+/// Whether a match is printed, or only counted for having fewer leading zero
+/// bits than `--report-limit`
+pub fn is_reported(leading_zero_bits: u8, report_limit: u8) -> bool {
+    leading_zero_bits >= report_limit
+}
+
+/// Keep `candidate` as the best match if it has more leading zero bits than
+/// the current one, or if there is none yet. Returns whether it was kept.
+pub fn retain_best(best: &Mutex<Option<KeyMatch>>, candidate: KeyMatch) -> bool {
+    let mut best = best.lock().unwrap();
+    match best.as_ref() {
+        Some(current) if current.leading_zero_bits >= candidate.leading_zero_bits => false,
+        _ => {
+            *best = Some(candidate);
+            true
+        }
+    }
+}
+
+/// Keep `candidate` if its public key is numerically smaller than the one of
+/// the current lowest match, or if there is none yet. Returns whether it was
+/// kept.
+pub fn retain_lowest(lowest: &Mutex<Option<KeyMatch>>, candidate: KeyMatch) -> bool {
+    let mut lowest = lowest.lock().unwrap();
+    match lowest.as_ref() {
+        // hex of the same length sorts like the numbers it writes
+        Some(current) if current.pubkey <= candidate.pubkey => false,
+        _ => {
+            *lowest = Some(candidate);
+            true
+        }
+    }
+}
+
+/// Format the report of the best key printed when mining stops
+pub fn format_summary(best: Option<&KeyMatch>) -> String {
+    let Some(best) = best else {
+        return String::from("No key found during this run");
+    };
+
+    let mut summary = String::from("Rarest key found during this run:\n");
+    let _ = writeln!(
+        summary,
+        "Leading zero bits:         {}",
+        best.leading_zero_bits
     );
+    if let Some(vanity) = &best.vanity {
+        let _ = writeln!(summary, "Vanity npub found:         {vanity}");
+    }
+    let _ = write!(summary, "Nostr public key (npub): {:>65}", best.npub);
+    if let Some(nsec) = &best.nsec {
+        let _ = write!(summary, "\nNostr private key (nsec): {nsec:>64}");
+    }
+    summary
+}
+
+/// Caps the rate of a loop by sleeping whenever it runs ahead of schedule.
+/// The schedule is measured from the start, so short sleeps overshooting
+/// are made up for and the average rate stays close to the target.
+pub struct Throttle {
+    per_second: f64,
+    start: Instant,
+    ticks: u64,
+}
+
+impl Throttle {
+    pub fn new(per_second: f64) -> Self {
+        Self {
+            per_second,
+            start: Instant::now(),
+            ticks: 0,
+        }
+    }
+
+    /// Count one iteration, sleeping until it is due
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+        let due = Duration::from_secs_f64(self.ticks as f64 / self.per_second);
+        if let Some(ahead) = due.checked_sub(self.start.elapsed()) {
+            std::thread::sleep(ahead);
+        }
+    }
+}
+
+/// Format the table of all matches printed by `--list-found`, one row per
+/// match. The nsec column is only added with `show_secrets`.
+pub fn format_found_table(matches: &[KeyMatch], show_secrets: bool) -> String {
+    let vanity_width = matches
+        .iter()
+        // padding counts chars, not bytes
+        .filter_map(|m| m.vanity.as_ref().map(|v| v.chars().count()))
+        .max()
+        .unwrap_or(0)
+        .max("vanity".len());
+    let row = |index: &str, npub: &str, bits: &str, vanity: &str, nsec: &str| {
+        let mut row = format!("{index:>5}  {npub:<63}  {bits:>4}  {vanity:<vanity_width$}");
+        if show_secrets {
+            row.push_str("  ");
+            row.push_str(nsec);
+        }
+        row.trim_end().to_string()
+    };
+
+    let mut table = row("#", "npub", "bits", "vanity", "nsec");
+    for (i, m) in matches.iter().enumerate() {
+        table.push('\n');
+        table.push_str(&row(
+            &(i + 1).to_string(),
+            &m.npub,
+            &m.leading_zero_bits.to_string(),
+            m.vanity.as_deref().unwrap_or("-"),
+            m.nsec.as_deref().unwrap_or("-"),
+        ));
+    }
+    table
+}
+
+/// Render a found key in the requested output format. Keys without a
+/// secret, as mined with `--pubkey-only`, only show the public key.
+pub fn format_key_match(key_match: &KeyMatch, format: OutputFormat) -> Result<String> {
+    let mut out = String::new();
+
+    match format {
+        OutputFormat::Plain => {
+            if key_match.leading_zero_bits != 0 {
+                writeln!(
+                    out,
+                    "Leading zero bits:         {}",
+                    key_match.leading_zero_bits
+                )?;
+            }
+            if let Some(vanity) = &key_match.vanity {
+                writeln!(out, "Vanity npub found:         {vanity}")?;
+            }
+            if let Some(tier) = &key_match.tier {
+                writeln!(out, "Difficulty tier:           {tier}")?;
+            }
+            if let Some(tag) = &key_match.tag {
+                writeln!(out, "Tag:                       {tag}")?;
+            }
+            if key_match.found_at_iteration != 0 {
+                writeln!(
+                    out,
+                    "Found after:               {:.2} seconds, {} iterations",
+                    key_match.found_at_secs, key_match.found_at_iteration
+                )?;
+            }
+            writeln!(
+                out,
+                "Key type:                  {} (BIP-340 Schnorr), {}",
+                key_match.scheme,
+                key_match.origin.as_str()
+            )?;
+
+            writeln!(out, "Found matching public key: {}", key_match.pubkey)?;
+            if let Some(pubkey_base64) = &key_match.pubkey_base64 {
+                writeln!(out, "Public key (base64):       {pubkey_base64}")?;
+            }
+
+            if let Some(secret_hex) = &key_match.secret_key {
+                writeln!(out, "Nostr private key: {secret_hex:>72}")?;
+            }
+            if let Some(secret_base64) = &key_match.secret_key_base64 {
+                writeln!(out, "Private key (base64):      {secret_base64}")?;
+            }
+
+            write!(out, "Nostr public key (npub): {:>65}", key_match.npub)?;
+
+            if let Some(nprofile) = &key_match.nprofile {
+                write!(out, "\nNostr profile (nprofile): {nprofile}")?;
+            }
 
-    if let Some(mnemonic) = mnemonic {
-        println!("Mnemonic: {mnemonic}");
+            if let Some(nsec) = &key_match.nsec {
+                write!(out, "\nNostr private key (nsec): {nsec:>64}")?;
+            }
+
+            if let Some(mnemonic) = &key_match.mnemonic {
+                write!(out, "\nMnemonic: {mnemonic}")?;
+            }
+
+            if let Some(path) = &key_match.derivation_path {
+                write!(out, "\nDerivation path: {path}")?;
+            }
+        }
+        OutputFormat::Nak => match (&key_match.secret_key, &key_match.nsec) {
+            (Some(secret_hex), _) => write!(out, "{secret_hex}")?,
+            (None, Some(nsec)) => write!(out, "{nsec}")?,
+            (None, None) => write!(out, "{}", key_match.pubkey)?,
+        },
+        OutputFormat::Jsonl => {
+            write!(out, "{}", serde_json::to_string(key_match)?)?;
+        }
+        OutputFormat::Env => {
+            let variables = [
+                ("NOSTR_SECRET_KEY", key_match.secret_key.as_ref()),
+                (
+                    "NOSTR_SECRET_KEY_BASE64",
+                    key_match.secret_key_base64.as_ref(),
+                ),
+                ("NOSTR_NSEC", key_match.nsec.as_ref()),
+                ("NOSTR_PUBLIC_KEY", Some(&key_match.pubkey)),
+                ("NOSTR_PUBLIC_KEY_BASE64", key_match.pubkey_base64.as_ref()),
+                ("NOSTR_NPUB", Some(&key_match.npub)),
+                ("NOSTR_NPROFILE", key_match.nprofile.as_ref()),
+                ("NOSTR_MNEMONIC", key_match.mnemonic.as_ref()),
+                ("RANA_TAG", key_match.tag.as_ref()),
+            ];
+            let lines: Vec<String> = variables
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some(format!("{name}={}", shell_quote(value.as_ref()?)))
+                })
+                .collect();
+            write!(out, "{}", lines.join("\n"))?;
+        }
+        // the next row of the process, the header only comes with the first
+        OutputFormat::Csv => {
+            CSV_ROWS.with_next_row(key_match, |lines| out.push_str(lines));
+        }
+        OutputFormat::Compact => {
+            let diff = key_match.leading_zero_bits.to_string();
+            let iter = key_match.found_at_iteration.to_string();
+            let origin = key_match.origin.as_str().to_string();
+            let fields = [
+                ("npub", Some(&key_match.npub)),
+                ("nsec", key_match.nsec.as_ref()),
+                ("secret", key_match.secret_key.as_ref()),
+                ("diff", Some(&diff)),
+                ("iter", Some(&iter)),
+                ("origin", Some(&origin)),
+                ("vanity", key_match.vanity.as_ref()),
+                ("tier", key_match.tier.as_ref()),
+                ("tag", key_match.tag.as_ref()),
+                ("mnemonic", key_match.mnemonic.as_ref()),
+                ("path", key_match.derivation_path.as_ref()),
+            ];
+            let pairs: Vec<String> = fields
+                .iter()
+                .filter_map(|(name, value)| {
+                    let value = (*value)?;
+                    // values with spaces, like a mnemonic, stay a single field
+                    Some(if value.contains(char::is_whitespace) {
+                        format!("{name}={}", shell_quote(value))
+                    } else {
+                        format!("{name}={value}")
+                    })
+                })
+                .collect();
+            write!(out, "{}", pairs.join(" "))?;
+        }
     }
 
+    Ok(out)
+}
+
+/// Whether the public key of a secret key, given as an nsec or hex, is the
+/// given npub or hex public key. Malformed keys are errors naming which one.
+pub fn verify_key_pair(nsec: &str, npub: &str) -> Result<bool> {
+    let keys = Keys::from_sk_str(nsec.trim()).map_err(|_| "The nsec is not a valid secret key")?;
+    let expected =
+        Keys::from_pk_str(npub.trim()).map_err(|_| "The npub is not a valid public key")?;
+    Ok(keys.public_key() == expected.public_key())
+}
+
+/// Columns of `--format csv`, printed once before the first match
+pub const CSV_HEADER: &str = "index,npub,nsec,pubkey_hex,difficulty,found_at_secs";
+
+/// Quote a CSV field when it holds a comma, a quote or a line break, doubling
+/// the quotes inside it
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// The CSV row of the `index`th match of the run, counted from 1
+pub fn format_csv_row(key_match: &KeyMatch, index: u64) -> String {
+    [
+        index.to_string(),
+        key_match.npub.clone(),
+        key_match.nsec.clone().unwrap_or_default(),
+        key_match.pubkey.clone(),
+        key_match.leading_zero_bits.to_string(),
+        key_match.found_at_secs.to_string(),
+    ]
+    .iter()
+    .map(|value| csv_field(value))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Numbers the `--format csv` rows and puts the header before the first one.
+/// The lock keeps the header first and the indexes in order when several
+/// threads find a key at once.
+pub struct CsvRows {
+    written: Mutex<u64>,
+}
+
+impl CsvRows {
+    pub const fn new() -> Self {
+        Self {
+            written: Mutex::new(0),
+        }
+    }
+
+    /// Hand the lines of the next match to `write` while holding the lock:
+    /// its row, after the header for the first one
+    pub fn with_next_row<T>(&self, key_match: &KeyMatch, write: impl FnOnce(&str) -> T) -> T {
+        let mut written = self.written.lock().unwrap();
+        *written += 1;
+        let row = format_csv_row(key_match, *written);
+        if *written == 1 {
+            write(&format!("{CSV_HEADER}\n{row}"))
+        } else {
+            write(&row)
+        }
+    }
+}
+
+impl Default for CsvRows {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The CSV rows of this process, one header for all of them
+static CSV_ROWS: CsvRows = CsvRows::new();
+
+/// Quote a value for a POSIX shell: single quotes keep everything literal,
+/// and a single quote itself is closed, escaped and reopened
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Print a found key to the output and append the `unredacted` version of it
+/// to the output file, if any, or to the fallback file when that fails
+pub fn print_key_match(
+    key_match: &KeyMatch,
+    unredacted: &KeyMatch,
+    format: OutputFormat,
+    output: &str,
+) -> Result<()> {
+    if format == OutputFormat::Csv {
+        // printed within the lock, so no row can come before the header
+        CSV_ROWS.with_next_row(key_match, |lines| println!("{lines}"));
+    } else {
+        println!("{}", format_key_match(key_match, format)?);
+    }
+    // streamed formats are read line by line while mining goes on
+    std::io::stdout().flush()?;
+
+    if !output.is_empty() {
+        let report = format_key_match(unredacted, OutputFormat::Plain)?;
+        // the key is already printed, a full disk must not stop the run
+        if let Err(e) = append_to_file(output, &report) {
+            let fallback = fallback_output_path();
+            let fallback_name = fallback.display();
+            match append_to_file(&fallback.to_string_lossy(), &report) {
+                Ok(()) => status!(
+                    format,
+                    "WARNING: could not write the key to {output} ({e}), appended it to {fallback_name} instead"
+                ),
+                Err(fallback_error) => status!(
+                    format,
+                    "WARNING: could not write the key to {output} ({e}) nor to {fallback_name} ({fallback_error}), it is only in the output above"
+                ),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Where found keys are appended when the `--output` file can't be written.
+/// Every match tries the output file first again.
+pub fn fallback_output_path() -> PathBuf {
+    std::env::temp_dir().join("rana-unsaved-keys.txt")
+}
+
+/// Open a file to append to, creating it if needed
+fn open_append(path: impl AsRef<std::path::Path>) -> std::io::Result<std::fs::File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Append a found key report to the output file, creating it if needed
+pub fn append_to_file(path: &str, report: &str) -> Result<()> {
+    let mut file = open_append(path)?;
+    writeln!(file, "==============================================")?;
+    writeln!(file, "{report}")?;
     Ok(())
 }
 
+/// Append the npub of a found key to the `--npub-log`, one per line and
+/// without anything secret, so the file can be shared as a watch-list
+pub fn append_npub(path: &std::path::Path, npub: &str) -> Result<()> {
+    writeln!(open_append(path)?, "{npub}")?;
+    Ok(())
+}
+
+/// Check that generated keys are well formed: a non-zero secret whose x-only
+/// public key is a valid curve point derived from that secret
+pub fn is_well_formed<C: Signing>(secp: &Secp256k1<C>, keys: &Keys) -> bool {
+    let secret_key = match keys.secret_key() {
+        Ok(secret_key) => secret_key,
+        Err(_) => return false,
+    };
+    let public_key = keys.public_key();
+
+    secret_key.secret_bytes() != [0; 32]
+        && XOnlyPublicKey::from_slice(&public_key.serialize()).is_ok()
+        && PublicKey::from_secret_key(secp, &secret_key)
+            .x_only_public_key()
+            .0
+            == public_key
+}
+
+/// Longest run of one repeated byte a secret may have for `--require-entropy`.
+/// A run of 5 in 32 random bytes has a chance of about 1 in 150 million.
+pub const ENTROPY_MAX_BYTE_RUN: usize = 4;
+
+/// Fewest distinct byte values a secret must have for `--require-entropy`.
+/// 32 random bytes have about 30, and fewer than 16 practically never.
+pub const ENTROPY_MIN_DISTINCT_BYTES: usize = 16;
+
+/// Whether secret bytes look random enough to be used as a key: no long run
+/// of a repeated byte and enough distinct bytes. Catches degenerate secrets
+/// like small counters or repeated patterns, not a weak random source.
+pub fn has_sufficient_entropy(secret: &[u8; 32]) -> bool {
+    let longest_run = secret
+        .chunk_by(|a, b| a == b)
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0);
+    let mut seen = [false; 256];
+    for byte in secret {
+        seen[*byte as usize] = true;
+    }
+    let distinct = seen.iter().filter(|seen| **seen).count();
+    longest_run <= ENTROPY_MAX_BYTE_RUN && distinct >= ENTROPY_MIN_DISTINCT_BYTES
+}
+
+/// Generate until `is_valid` accepts the result, returning it along with the
+/// number of rejected attempts
+pub fn generate_until<T>(
+    mut generate: impl FnMut() -> T,
+    is_valid: impl Fn(&T) -> bool,
+) -> (T, u64) {
+    let mut retries = 0;
+    loop {
+        let generated = generate();
+        if is_valid(&generated) {
+            return (generated, retries);
+        }
+        retries += 1;
+    }
+}
+
+/// Group the digits of a number by thousands, e.g. 1234567 -> "1,234,567"
+pub fn group_thousands(value: u64) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Format the iteration summary printed after a match, with the iterations
+/// in comma grouped decimal or, if `scientific` is set, as a power of ten.
+///
+/// The counter is a `u64`, so even at 100 million hashes per second it would
+/// take over 5,000 years to reach `u64::MAX`; all arithmetic here saturates
+/// anyway so the line stays sane at the ceiling.
+pub fn format_stats(iterations: u64, elapsed_secs: u64, scientific: bool) -> String {
+    let iterations_string = if scientific {
+        let iter_string = format!("{iterations}");
+        let exponent = iter_string.len().saturating_sub(1);
+        let first_digit = iter_string.chars().next().unwrap_or('0');
+        format!("{iterations} iterations (about {first_digit}x10^{exponent} hashes)")
+    } else {
+        format!("{} iterations", group_thousands(iterations))
+    };
+    format!(
+        "{} in {} seconds. Avg rate {} hashes/second",
+        iterations_string,
+        elapsed_secs,
+        iterations.saturating_div(max(1, elapsed_secs))
+    )
+}
+
+/// The multiples of `every` crossed when counting from `previous` up to
+/// `iteration`, several when a batch is larger than `every`. Each range of
+/// the shared counter is returned by a single `fetch_add`, so exactly one
+/// thread sees every crossing and prints its progress line.
+pub fn crossed_progress_steps(
+    previous: u64,
+    iteration: u64,
+    every: u64,
+) -> impl Iterator<Item = u64> {
+    (previous / every + 1..=iteration / every).map(move |step| step * every)
+}
+
+/// Raise `best` to `candidate` if it is strictly better, in a single
+/// compare-and-swap loop. Returns whether this call raised it, so of several
+/// threads racing with the same or worse keys only one reports an improvement.
+///
+/// `Relaxed` is enough: `best` is the only value shared, and every atomic has
+/// a single total modification order that the CAS respects, so it can only
+/// ever grow.
+pub fn raise_best(best: &AtomicU8, candidate: u8) -> bool {
+    best.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+        (candidate > current).then_some(candidate)
+    })
+    .is_ok()
+}
+
+/// Difficulty after a match found `since_last` after the previous change,
+/// for a match about every `interval`: one bit harder when it came sooner,
+/// one bit easier when it came later
+pub fn adapt_difficulty(difficulty: u8, since_last: Duration, interval: Duration) -> u8 {
+    match since_last.cmp(&interval) {
+        std::cmp::Ordering::Less => difficulty.saturating_add(1),
+        std::cmp::Ordering::Equal => difficulty,
+        std::cmp::Ordering::Greater => difficulty.saturating_sub(1),
+    }
+}
+
+/// Tunes the difficulty of `--target-interval` so a match is found about
+/// every interval, starting from the difficulty it was given
+pub struct AdaptiveDifficulty {
+    interval: Duration,
+    /// Milliseconds into the run of the last find or easing
+    last_change_ms: AtomicU64,
+}
+
+impl AdaptiveDifficulty {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_change_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether a key of `bits` leading zero bits beats the `difficulty`,
+    /// then adapt it to how long the find took. `elapsed` is the time into
+    /// the run.
+    pub fn record(&self, difficulty: &AtomicU8, bits: u8, elapsed: Duration) -> bool {
+        let current = difficulty.load(Ordering::Relaxed);
+        if bits <= current {
+            return false;
+        }
+        let elapsed_ms = elapsed.as_millis() as u64;
+        let previous = self.last_change_ms.swap(elapsed_ms, Ordering::Relaxed);
+        let since_last = Duration::from_millis(elapsed_ms.saturating_sub(previous));
+        let adapted = adapt_difficulty(current, since_last, self.interval);
+        // another thread may have adapted it meanwhile, its change stands
+        let _ = difficulty.compare_exchange(current, adapted, Ordering::Relaxed, Ordering::Relaxed);
+        true
+    }
+
+    /// Ease the `difficulty` by a bit when nothing was found for an
+    /// interval, returning the new one
+    pub fn ease(&self, difficulty: &AtomicU8, elapsed: Duration) -> Option<u8> {
+        let elapsed_ms = elapsed.as_millis() as u64;
+        let previous = self.last_change_ms.load(Ordering::Relaxed);
+        if elapsed_ms.saturating_sub(previous) < self.interval.as_millis() as u64 {
+            return None;
+        }
+        self.last_change_ms
+            .compare_exchange(previous, elapsed_ms, Ordering::Relaxed, Ordering::Relaxed)
+            .ok()?;
+        let eased = difficulty
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |d| d.checked_sub(1))
+            .ok()?;
+        Some(eased - 1)
+    }
+}
+
+/// Bits of the key encoded by each bech32 data character
+pub const BECH32_BITS_PER_CHAR: usize = 5;
+
+/// Estimate the pow difficulty of the npub targets from the first prefix
+/// and/or suffix. With `match_any` the easier of the two counts.
+pub fn npub_pow_difficulty(prefixes: &[String], suffixes: &[String], match_any: bool) -> u8 {
+    let prefix_len = prefixes.first().map_or(0, |p| p.len());
+    let suffix_len = suffixes.first().map_or(0, |s| s.len());
+    let chars = if match_any && prefix_len > 0 && suffix_len > 0 {
+        min(prefix_len, suffix_len)
+    } else {
+        prefix_len + suffix_len
+    };
+    min(chars * BECH32_BITS_PER_CHAR, u8::MAX as usize) as u8
+}
+
+/// Number of trailing bech32 characters that are a checksum, not key data
+pub const BECH32_CHECKSUM_CHARS: usize = 6;
+
+/// Explain that a vanity suffix mostly lands in the bech32 checksum: compare
+/// the requested difficulty of the suffix with the bits it fixes in the key
+/// data itself
+pub fn suffix_checksum_note(suffix: &str, hrp_name: &str) -> String {
+    let requested = suffix.len() * BECH32_BITS_PER_CHAR;
+    let data_chars = suffix.len().saturating_sub(BECH32_CHECKSUM_CHARS);
+    let effective = data_chars * BECH32_BITS_PER_CHAR;
+    format!(
+        "Note: the last {BECH32_CHECKSUM_CHARS} characters of an {hrp_name} are a checksum, so only {data_chars} of the {} characters of the suffix '...{suffix}' are part of the key itself (requested pow: {requested}, pow on the key data: {effective}). Matching a suffix of the key data needs a different and harder search.",
+        suffix.len()
+    )
+}
+
+/// Characters of the bech32 data alphabet, in their order of value
+pub const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Bech32 characters that look like the letters and digits bech32 leaves out
+const BRAND_LOOKALIKES: [(char, &str); 4] = [('b', "86"), ('i', "l"), ('o', "0"), ('1', "l")];
+
+/// Most prefixes derived from a single brand word
+pub const BRAND_PREFIX_LIMIT: usize = 32;
+
+/// Derive bech32 prefixes that read like a brand word: letters bech32 has
+/// are kept, each of the ones it lacks is replaced by one of its lookalikes
+/// and anything else, like spaces or punctuation, is dropped. Every prefix
+/// is as long as the others, so none of them is found far more often.
+pub fn brand_prefixes(word: &str) -> Vec<String> {
+    let mut prefixes = vec![String::new()];
+    for c in word.to_lowercase().chars() {
+        let options = if BECH32_CHARSET.contains(c) {
+            c.to_string()
+        } else if let Some((_, lookalikes)) = BRAND_LOOKALIKES.iter().find(|(l, _)| *l == c) {
+            lookalikes.to_string()
+        } else {
+            continue;
+        };
+        prefixes = prefixes
+            .iter()
+            .flat_map(|prefix| {
+                options
+                    .chars()
+                    .map(move |option| format!("{prefix}{option}"))
+            })
+            .take(BRAND_PREFIX_LIMIT)
+            .collect();
+    }
+    prefixes.retain(|prefix| !prefix.is_empty());
+    prefixes
+}
+
+/// Encode the public or secret key of the keys under the given HRP
+pub fn encode_bech32(keys: &Keys, hrp: Hrp) -> Result<String> {
+    Ok(match hrp {
+        Hrp::Npub => keys.public_key().to_bech32()?,
+        Hrp::Nsec => keys.secret_key()?.to_bech32()?,
+    })
+}
+
+/// Match the bech32 encodings of the keys under each HRP. With `all` every
+/// one of them has to match, otherwise the first that does is enough. A
+/// single HRP reports what matched as it is, several put the HRP in front.
+pub fn match_hrps(
+    keys: &Keys,
+    hrps: &[Hrp],
+    all: bool,
+    matches: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let matched = hrps
+        .iter()
+        .map(|&hrp| matches(&encode_bech32(keys, hrp).ok()?).map(|target| (hrp, target)));
+    let found: Vec<(Hrp, String)> = if all {
+        matched.collect::<Option<_>>()?
+    } else {
+        vec![matched.flatten().next()?]
+    };
+    if let ([(_, target)], 1) = (found.as_slice(), hrps.len()) {
+        return Some(target.clone());
+    }
+    Some(
+        found
+            .iter()
+            .map(|(hrp, target)| format!("{}:{target}", hrp.as_str()))
+            .collect::<Vec<String>>()
+            .join(", "),
+    )
+}
+
+/// Encode a public key with relay hints as a NIP-19 `nprofile`
+pub fn encode_nprofile(public_key: &XOnlyPublicKey, relays: &[String]) -> Result<String> {
+    Ok(Profile::new(*public_key, relays.to_vec()).to_bech32()?)
+}
+
+/// Leading zero bits of a public key that also has to carry a proof of work
+/// on top of its vanity npub, if it has at least `extra_zeros` of them
+pub fn extra_zero_bits(public_key: &[u8], extra_zeros: u8) -> Option<u8> {
+    let bits = get_leading_zero_bits(public_key);
+    (bits >= extra_zeros).then_some(bits)
+}
+
+/// Check a bech32 key against the vanity npub prefixes and suffixes.
+///
+/// When both lists are given a key must match a prefix and a suffix, or
+/// either of them if `match_any` is set. Returns the satisfied target.
+pub fn match_npub(
+    bech_key: &str,
+    prefixes: &[String],
+    suffixes: &[String],
+    match_any: bool,
+) -> Option<String> {
+    // the data part never contains the `1` separating it from the HRP
+    let data = bech_key.rsplit_once('1').map_or(bech_key, |(_, data)| data);
+    match_data(data, data, prefixes, suffixes, match_any)
+}
+
+/// Like `match_npub`, but the suffixes are matched against the end of the key
+/// data, before the checksum, as rendered by `anchored_key_data`
+pub fn match_npub_anchored(
+    bech_key: &str,
+    prefixes: &[String],
+    suffixes: &[String],
+    match_any: bool,
+) -> Option<String> {
+    let data = bech_key.rsplit_once('1').map_or(bech_key, |(_, data)| data);
+    let anchored = anchored_key_data(bech_key)?;
+    match_data(data, &anchored, prefixes, suffixes, match_any)
+}
+
+/// How a bech32 key is checked against the vanity targets
+pub type NpubMatcher = fn(&str, &[String], &[String], bool) -> Option<String>;
+
+/// `match_npub_anchored` with --npub-anchored-suffix, `match_npub` otherwise
+pub fn npub_matcher(anchored_suffix: bool) -> NpubMatcher {
+    if anchored_suffix {
+        match_npub_anchored
+    } else {
+        match_npub
+    }
+}
+
+/// The characters of the key data of a bech32 key, without its checksum,
+/// rendered from the end so that the last character holds the last 5 bits of
+/// the key. The 256 bits of a key leave 4 bits of padding in the last data
+/// character of an npub, so this is the rendering a suffix of the key itself
+/// can be matched against.
+pub fn anchored_key_data(bech_key: &str) -> Option<String> {
+    let (_, words, _) = bech32::decode(bech_key).ok()?;
+    let bytes = Vec::<u8>::from_base32(&words).ok()?;
+    let bits = bytes.len() * 8;
+    let bit = |index: usize| (bytes[bytes.len() - 1 - index / 8] >> (index % 8)) & 1;
+    let mut chars: Vec<char> = (0..bits.div_ceil(BECH32_BITS_PER_CHAR))
+        .map(|group| {
+            let value = (0..BECH32_BITS_PER_CHAR)
+                .map(|offset| group * BECH32_BITS_PER_CHAR + offset)
+                .filter(|&index| index < bits)
+                .fold(0, |value, index| {
+                    value | bit(index) << (index % BECH32_BITS_PER_CHAR)
+                });
+            BECH32_CHARSET.as_bytes()[value as usize] as char
+        })
+        .collect();
+    chars.reverse();
+    Some(chars.into_iter().collect())
+}
+
+/// Match the prefixes against `prefix_data` and the suffixes against
+/// `suffix_data`, as `match_npub` describes
+fn match_data(
+    prefix_data: &str,
+    suffix_data: &str,
+    prefixes: &[String],
+    suffixes: &[String],
+    match_any: bool,
+) -> Option<String> {
+    let find_prefix = || {
+        prefixes
+            .iter()
+            .find(|p| prefix_data.starts_with(p.as_str()))
+    };
+    let find_suffix = || suffixes.iter().find(|s| suffix_data.ends_with(s.as_str()));
+
+    match (prefixes.is_empty(), suffixes.is_empty()) {
+        (true, true) => None,
+        (false, true) => find_prefix().cloned(),
+        (true, false) => find_suffix().cloned(),
+        (false, false) if match_any => find_prefix()
+            .map(|p| format!("{p}..."))
+            .or_else(|| find_suffix().map(|s| format!("...{s}"))),
+        (false, false) => {
+            let prefix = find_prefix()?;
+            let suffix = find_suffix()?;
+            Some(format!("{prefix}...{suffix}"))
+        }
+    }
+}
+
+/// Every target `match_npub` can report for the given prefixes and suffixes
+pub fn npub_targets(prefixes: &[String], suffixes: &[String], match_any: bool) -> Vec<String> {
+    match (prefixes.is_empty(), suffixes.is_empty()) {
+        (_, true) => prefixes.to_vec(),
+        (true, false) => suffixes.to_vec(),
+        (false, false) if match_any => prefixes
+            .iter()
+            .map(|p| format!("{p}..."))
+            .chain(suffixes.iter().map(|s| format!("...{s}")))
+            .collect(),
+        (false, false) => prefixes
+            .iter()
+            .flat_map(|p| suffixes.iter().map(move |s| format!("{p}...{s}")))
+            .collect(),
+    }
+}
+
+/// Number of matches of each vanity target, shared by the mining threads.
+/// Targets keep the order they were first seen in.
+#[derive(Debug, Default)]
+pub struct TargetCounts {
+    counts: Mutex<Vec<(String, u64)>>,
+}
+
+impl TargetCounts {
+    /// Start every target at zero, so outstanding ones are listed too
+    pub fn new(targets: &[String]) -> Self {
+        Self {
+            counts: Mutex::new(targets.iter().map(|t| (t.clone(), 0)).collect()),
+        }
+    }
+
+    /// Count a match of `target`, adding it if it was not known yet (e.g. a
+    /// prefix read from stdin)
+    pub fn record(&self, target: &str) {
+        let mut counts = self.counts.lock().unwrap();
+        match counts.iter_mut().find(|(t, _)| t == target) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((target.to_string(), 1)),
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<(String, u64)> {
+        self.counts.lock().unwrap().clone()
+    }
+}
+
+/// One line per target with its number of matches
+pub fn format_target_counts(counts: &[(String, u64)]) -> String {
+    let width = counts.iter().map(|(t, _)| t.len()).max().unwrap_or(0);
+    let lines: Vec<String> = counts
+        .iter()
+        .map(|(target, count)| match count {
+            0 => format!("  {target:<width$}  0 (outstanding)"),
+            _ => format!("  {target:<width$}  {count}"),
+        })
+        .collect();
+    format!("Matches per target:\n{}", lines.join("\n"))
+}
+
+/// Match the start of a bech32 key against a target prefix, allowing up to
+/// `max_distance` differing characters (Hamming distance). Returns the
+/// actual prefix of the key and its distance to the target.
+pub fn match_npub_fuzzy(
+    bech_key: &str,
+    target: &str,
+    max_distance: usize,
+) -> Option<(String, usize)> {
+    let data = bech_key.rsplit_once('1').map_or(bech_key, |(_, data)| data);
+    let prefix = data.get(..target.len())?;
+    let mut distance = 0;
+    for (a, b) in prefix.bytes().zip(target.bytes()) {
+        if a != b {
+            distance += 1;
+            if distance > max_distance {
+                return None;
+            }
+        }
+    }
+    Some((prefix.to_string(), distance))
+}
+
+/// Number of npub data characters clients typically show of a shortened npub
+pub const DISPLAYED_PREFIX_CHARS: usize = 8;
+
+/// Leading and trailing characters kept by common ways clients shorten an npub
+pub const ADDRESS_EXAMPLE_FORMS: [(usize, usize); 3] = [(8, 4), (12, 6), (16, 8)];
+
+/// Shorten a bech32 key to its first `head` and last `tail` characters
+pub fn truncate_address(bech_key: &str, head: usize, tail: usize) -> String {
+    if head + tail >= bech_key.len() {
+        return bech_key.to_string();
+    }
+    format!(
+        "{}...{}",
+        &bech_key[..head],
+        &bech_key[bech_key.len() - tail..]
+    )
+}
+
+/// Show how a found key appears in clients with every `ADDRESS_EXAMPLE_FORMS`
+pub fn format_address_examples(bech_key: &str) -> String {
+    let mut examples = String::from("Shown truncated in clients as:");
+    for (head, tail) in ADDRESS_EXAMPLE_FORMS {
+        let _ = write!(
+            examples,
+            "\n  first {head:>2} + last {tail}:  {}",
+            truncate_address(bech_key, head, tail)
+        );
+    }
+    examples
+}
+
+/// The serialized bytes of a public key in rows of 8, each led by its offset,
+/// and their leading zero bits as `get_leading_zero_bits` counts them
+pub fn format_pubkey_bytes(bytes: &[u8; 32]) -> String {
+    let mut out = format!(
+        "Public key bytes ({}, {} leading zero bits):",
+        bytes.len(),
+        get_leading_zero_bits(bytes)
+    );
+    for (row, chunk) in bytes.chunks(8).enumerate() {
+        let _ = write!(out, "\n  {:02}:", row * 8);
+        for byte in chunk {
+            let _ = write!(out, " {byte:02x}");
+        }
+    }
+    out
+}
+
+/// Find a confusable character in the displayed prefix of a bech32 key: its
+/// first `DISPLAYED_PREFIX_CHARS` data characters, or the whole target if it
+/// is longer. Characters equal to the target at the same position are the
+/// ones asked for and are never held against the key.
+pub fn find_confusable(bech_key: &str, target: &str, confusables: &str) -> Option<char> {
+    let data = bech_key.rsplit_once('1').map_or(bech_key, |(_, data)| data);
+    let span = max(DISPLAYED_PREFIX_CHARS, target.len());
+    let wanted = target.chars().map(Some).chain(std::iter::repeat(None));
+    data.chars()
+        .zip(wanted)
+        .take(span)
+        .find(|&(c, wanted)| Some(c) != wanted && confusables.contains(c))
+        .map(|(c, _)| c)
+}
+
+/// Estimate the pow difficulty of a fuzzy prefix: the bits of the prefix
+/// minus the log2 of the number of strings within `max_distance` of it
+pub fn fuzzy_pow_difficulty(target_len: usize, max_distance: usize) -> u8 {
+    // sum over k <= max_distance of C(n, k) * 31^k
+    let mut neighbours = 0_f64;
+    let mut binomial = 1_f64;
+    for k in 0..=max_distance.min(target_len) {
+        neighbours += binomial * 31_f64.powi(k as i32);
+        binomial = binomial * (target_len - k) as f64 / (k + 1) as f64;
+    }
+    let bits = (target_len * BECH32_BITS_PER_CHAR) as f64 - neighbours.log2();
+    bits.clamp(0.0, u8::MAX as f64) as u8
+}
+
+/// Add the npub prefixes read line by line to the shared search targets until
+/// EOF, skipping blank lines. Returns the lines rejected for not being bech32.
+pub fn read_prefixes(
+    reader: impl BufRead,
+    targets: &RwLock<Vec<String>>,
+    format: OutputFormat,
+) -> Vec<String> {
+    let bech32_re = Regex::new(r"^([02-9ac-hj-np-z]{1,59})$").unwrap();
+    let mut rejected = Vec::new();
+
+    for line in reader.lines().map_while(|line| line.ok()) {
+        let prefix = line.trim();
+        if prefix.is_empty() {
+            continue;
+        }
+        if bech32_re.is_match(prefix) {
+            targets.write().unwrap().push(prefix.to_string());
+            status!(format, "Added npub prefix '{prefix}' to the search");
+        } else {
+            status!(
+                format,
+                "Ignored '{prefix}': npub prefixes can only contain up to 59 Bech32 characters"
+            );
+            rejected.push(prefix.to_string());
+        }
+    }
+
+    rejected
+}
+
+/// Read the public keys of a `--known-pubkeys` file, in hex or as npubs,
+/// one per line and skipping blank lines and `#` comments. Returns them with
+/// the rejected lines and their line numbers.
+pub fn read_known_pubkeys(
+    reader: impl BufRead,
+) -> (HashSet<XOnlyPublicKey>, NumberedLines<String>) {
+    let mut known = HashSet::new();
+    let mut rejected = Vec::new();
+    for (index, line) in reader.lines().map_while(|line| line.ok()).enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let public_key = if line.starts_with("npub1") {
+            XOnlyPublicKey::from_bech32(line).ok()
+        } else if line.len() == 64 {
+            line.parse::<XOnlyPublicKey>().ok()
+        } else {
+            None
+        };
+        match public_key {
+            Some(public_key) => {
+                known.insert(public_key);
+            }
+            None => rejected.push((
+                index + 1,
+                format!("'{line}' is not a public key in hex or an npub"),
+            )),
+        }
+    }
+    (known, rejected)
+}
+
+/// What to do with a match, as answered at the `--interactive` prompt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchChoice {
+    KeepMining,
+    Save,
+    Stop,
+}
+
+/// Parse an answer to the `--interactive` prompt. An empty answer keeps
+/// mining, and saving is only an option with a keystore.
+pub fn parse_match_choice(answer: &str, can_save: bool) -> Option<MatchChoice> {
+    match answer.trim().to_lowercase().as_str() {
+        "" | "k" | "keep" => Some(MatchChoice::KeepMining),
+        "s" | "save" if can_save => Some(MatchChoice::Save),
+        "q" | "quit" | "stop" => Some(MatchChoice::Stop),
+        _ => None,
+    }
+}
+
+/// Ask what to do with a match until `input` gives a valid answer. The end
+/// of the input stops the search, as nobody is left to answer.
+pub fn prompt_match_choice(
+    input: &mut impl BufRead,
+    prompt: &mut impl std::io::Write,
+    can_save: bool,
+) -> std::io::Result<MatchChoice> {
+    let question = if can_save {
+        "[k]eep mining, [s]ave this key to the keystore or [q]uit? [k] "
+    } else {
+        "[k]eep mining or [q]uit? [k] "
+    };
+    loop {
+        write!(prompt, "{question}")?;
+        prompt.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(MatchChoice::Stop);
+        }
+        if let Some(choice) = parse_match_choice(&answer, can_save) {
+            return Ok(choice);
+        }
+    }
+}
+
+/// A second Ctrl+C this soon after the first quits without waiting for the
+/// workers
+pub const FORCE_QUIT_WINDOW: Duration = Duration::from_secs(2);
+
+/// What a Ctrl+C asks for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interrupt {
+    /// Stop the workers and print the summary
+    Graceful,
+    /// Exit right away, e.g. when a worker is stuck in a large batch
+    ForceQuit,
+}
+
+/// Tells a second Ctrl+C within `FORCE_QUIT_WINDOW` from a first one
+#[derive(Default)]
+pub struct InterruptCounter {
+    last: Mutex<Option<Instant>>,
+}
+
+impl InterruptCounter {
+    /// Count a Ctrl+C pressed `at`
+    pub fn press(&self, at: Instant) -> Interrupt {
+        let mut last = self.last.lock().unwrap();
+        let again = last.is_some_and(|last| at.duration_since(last) <= FORCE_QUIT_WINDOW);
+        *last = Some(at);
+        if again {
+            Interrupt::ForceQuit
+        } else {
+            Interrupt::Graceful
+        }
+    }
+}
+
+/// Holds the mining threads while an `--interactive` prompt is answered, so
+/// keeping a match resumes the search where it was, with its counters and
+/// difficulty, instead of restarting it
+#[derive(Default)]
+pub struct MiningPause {
+    /// Prompts open at the moment
+    pauses: Mutex<usize>,
+    resumed: Condvar,
+}
+
+/// Open pause of `MiningPause::pause`, resuming the threads when dropped
+pub struct PauseGuard<'a>(&'a MiningPause);
+
+impl MiningPause {
+    /// Pause the threads that reach `wait` until the guard is dropped
+    pub fn pause(&self) -> PauseGuard<'_> {
+        *self.pauses.lock().unwrap() += 1;
+        PauseGuard(self)
+    }
+
+    /// Block while paused. A shutdown, like Ctrl+C, ends the wait too.
+    pub fn wait(&self, shutdown: &AtomicBool) {
+        let mut pauses = self.pauses.lock().unwrap();
+        while *pauses > 0 && !shutdown.load(Ordering::Relaxed) {
+            pauses = self
+                .resumed
+                .wait_timeout(pauses, Duration::from_millis(50))
+                .unwrap()
+                .0;
+        }
+    }
+}
+
+impl Drop for PauseGuard<'_> {
+    fn drop(&mut self) {
+        *self.0.pauses.lock().unwrap() -= 1;
+        self.0.resumed.notify_all();
+    }
+}
+
+/// Compute the id of the kind-0 profile event the key would publish with the
+/// given metadata. Everything is computed locally, nothing is sent.
+pub fn profile_preview_id(
+    public_key: &XOnlyPublicKey,
+    metadata: &Metadata,
+    created_at: Timestamp,
+) -> Result<EventId> {
+    Ok(EventId::new(
+        public_key,
+        created_at,
+        &Kind::Metadata,
+        &[],
+        &metadata.as_json()?,
+    ))
+}
+
+/// Sign the kind-0 profile event of the key with the given metadata, as it
+/// would be sent to a relay. Nothing is sent.
+pub fn sign_profile_event(keys: &Keys, metadata: &Metadata) -> Result<Event> {
+    Ok(EventBuilder::new(Kind::Metadata, metadata.as_json()?, &[]).to_event(keys)?)
+}
+
+/// Lowercase hex representation of bytes
+pub fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Decode a hex string, if it is one
+pub fn hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Compare a serialized key against a big-endian threshold. Returns how far
+/// below the threshold it is, or `None` if it is not below.
+pub fn below_threshold(key: &[u8; 32], threshold: &[u8; 32]) -> Option<[u8; 32]> {
+    if key >= threshold {
+        return None;
+    }
+    let mut margin = [0_u8; 32];
+    let mut borrow = 0_i16;
+    for i in (0..32).rev() {
+        let mut difference = threshold[i] as i16 - key[i] as i16 - borrow;
+        borrow = 0;
+        if difference < 0 {
+            difference += 256;
+            borrow = 1;
+        }
+        margin[i] = difference as u8;
+    }
+    Some(margin)
+}
+
+/// Check that the first `count` bytes are zero, stopping at the first
+/// non-zero byte. Faster than counting bits for whole byte targets.
+#[inline]
+pub fn has_leading_zero_bytes(bytes: &[u8], count: usize) -> bool {
+    bytes.len() >= count && bytes[..count].iter().all(|b| *b == 0)
+}
+
+/// Leading zero bits of the bytes, saturating at 255 for 32 zero bytes
 #[inline]
 pub fn get_leading_zero_bits(bytes: &[u8]) -> u8 {
-    let mut res = 0_u8;
+    let mut res = 0_u32;
     for b in bytes {
         if *b == 0 {
             res += 8;
         } else {
-            res += b.leading_zeros() as u8;
-            return res;
+            res += b.leading_zeros();
+            break;
         }
     }
-    res
+    min(res, u8::MAX as u32) as u8
 }
 
-pub fn print_qr(secret_key: SecretKey) -> Result<()> {
-    let nsec = secret_key.to_bech32()?;
-    let code = QrCode::new(nsec)?;
-    let qr = code
+/// Whether the hex encoding of a public key starts with the vanity prefix
+#[inline]
+pub fn matches_hex_prefix(public_key: &XOnlyPublicKey, prefix: &str) -> bool {
+    public_key.to_string().starts_with(prefix)
+}
+
+/// The leading zero bits of a public key when they beat `best`, the
+/// difficulty reached so far, and the key starts with `min_zero_bytes`
+/// whole zero bytes. With a `best` of 0 any key with a leading zero bit does.
+#[inline]
+pub fn meets_difficulty(public_key: &[u8], min_zero_bytes: usize, best: u8) -> Option<u8> {
+    // whole zero bytes are checked first as a cheap short-circuit
+    if !has_leading_zero_bytes(public_key, min_zero_bytes) {
+        return None;
+    }
+    let leading_zeroes = get_leading_zero_bits(public_key);
+    (leading_zeroes > best).then_some(leading_zeroes)
+}
+
+/// Render data as a QR code made of unicode blocks for the terminal
+pub fn render_qr(data: &str) -> Result<String> {
+    let code = QrCode::new(data)?;
+    Ok(code
         .render::<unicode::Dense1x2>()
         .dark_color(unicode::Dense1x2::Light)
         .light_color(unicode::Dense1x2::Dark)
-        .build();
-    println!("{qr}");
+        .build())
+}
+
+/// Quadrant blocks, indexed by which of the top left (1), top right (2),
+/// bottom left (4) and bottom right (8) quarters are filled
+pub const QUADRANT_BLOCKS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+/// Modules of blank margin around a QR code, as its standard requires
+const QR_QUIET_ZONE: usize = 4;
+
+/// Render a QR code with a quadrant block for every 2x2 modules, half as
+/// wide as `render_qr`. Like it, dark modules are left blank for terminals
+/// with a dark background.
+pub fn render_qr_compact(data: &str) -> Result<String> {
+    let code = QrCode::new(data)?;
+    let width = code.width();
+    let colors = code.to_colors();
+    let size = width + 2 * QR_QUIET_ZONE;
+    // the quiet zone and the light modules are drawn
+    let is_drawn = |x: usize, y: usize| {
+        let (x, y) = (x.wrapping_sub(QR_QUIET_ZONE), y.wrapping_sub(QR_QUIET_ZONE));
+        x >= width || y >= width || colors[y * width + x] == qrcode::Color::Light
+    };
+    let mut out = String::new();
+    for y in (0..size).step_by(2) {
+        for x in (0..size).step_by(2) {
+            let quarters = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)];
+            let index = quarters
+                .iter()
+                .enumerate()
+                .filter(|(_, &(x, y))| x < size && y < size && is_drawn(x, y))
+                .fold(0, |index, (bit, _)| index | 1 << bit);
+            out.push(QUADRANT_BLOCKS[index]);
+        }
+        out.push('\n');
+    }
+    out.pop();
+    Ok(out)
+}
+
+/// Render a QR code with `#` for every light module and the quiet zone,
+/// two characters wide so the modules are about square
+pub fn render_qr_ascii(data: &str) -> Result<String> {
+    let code = QrCode::new(data)?;
+    Ok(code
+        .render::<char>()
+        .quiet_zone(true)
+        .module_dimensions(2, 1)
+        .dark_color(' ')
+        .light_color('#')
+        .build())
+}
+
+/// Columns of the terminal: `COLUMNS` if set, else the window size of the
+/// terminal on stderr, stdout or the controlling one. None when neither is
+/// known, e.g. everything is redirected.
+pub fn terminal_width() -> Option<usize> {
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+        return Some(columns);
+    }
+    window_columns()
+}
+
+#[cfg(unix)]
+fn window_columns() -> Option<usize> {
+    use rustix::termios::tcgetwinsize;
+    let columns = tcgetwinsize(std::io::stderr())
+        .or_else(|_| tcgetwinsize(std::io::stdout()))
+        .ok()
+        .or_else(|| tcgetwinsize(std::fs::File::open("/dev/tty").ok()?).ok())?
+        .ws_col;
+    // a pseudo terminal nobody sized reports 0
+    (columns > 0).then_some(columns as usize)
+}
+
+#[cfg(not(unix))]
+fn window_columns() -> Option<usize> {
+    None
+}
+
+/// The warning printed instead of a QR code wider than a terminal of
+/// `columns`, as a wrapped QR code cannot be scanned. None when it fits or
+/// the width is unknown.
+pub fn narrow_terminal_warning(qr: &str, columns: Option<usize>, style: QrStyle) -> Option<String> {
+    let columns = columns?;
+    let needed = qr.lines().map(|line| line.chars().count()).max()?;
+    if needed <= columns {
+        return None;
+    }
+    let compact = if style == QrStyle::Compact {
+        ""
+    } else {
+        " or try --qr-compact,"
+    };
+    Some(format!(
+        "WARNING: the QR code is {needed} columns wide and would wrap in this terminal of {columns} columns, so it was not printed. Widen the terminal{compact} or write it to a PNG file with --backup-card <path>"
+    ))
+}
+
+/// Print the QR code of the nsec in the given style, or a warning if the
+/// terminal is too narrow for it
+pub fn print_qr(secret_key: SecretKey, format: OutputFormat, style: QrStyle) -> Result<()> {
+    let nsec = secret_key.to_bech32()?;
+    let qr = match style {
+        QrStyle::Blocks => render_qr(&nsec)?,
+        QrStyle::Compact => render_qr_compact(&nsec)?,
+        QrStyle::Ascii => render_qr_ascii(&nsec)?,
+    };
+    match narrow_terminal_warning(&qr, terminal_width(), style) {
+        Some(warning) => status!(format, "{warning}"),
+        None => status!(format, "{qr}"),
+    }
     Ok(())
 }
@@ -0,0 +1,38 @@
+//! Workers counting their keys in batches with --batch
+
+mod common;
+
+use common::{found_keys, iterations_tried, run};
+
+/// Keys counted by a run stopped by --max-iterations, from its summary line
+fn stopped_after(args: &[&str]) -> u64 {
+    let output = run(&[&["-d", "60"], args].concat(), 3);
+    iterations_tried(&String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn a_core_stops_at_the_end_of_its_batch() {
+    let args = ["--batch", "1000", "--max-iterations"];
+    assert_eq!(stopped_after(&[&args[..], &["1500"]].concat()), 2000);
+    assert_eq!(stopped_after(&[&args[..], &["1000"]].concat()), 1000);
+}
+
+#[test]
+fn matches_within_a_batch_know_their_iteration() {
+    let iterations: Vec<u64> = found_keys(&[
+        "--vanity",
+        "a",
+        "--batch",
+        "1000",
+        "--max-iterations",
+        "500",
+    ])
+    .iter()
+    .map(|key_match| key_match["found_at_iteration"].as_u64().unwrap())
+    .collect();
+    // about one key in 16 starts with an a, all of them before the counter
+    // is first updated
+    assert!(iterations.len() > 10, "{iterations:?}");
+    assert!(iterations.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(iterations.iter().all(|&iteration| iteration <= 1000));
+}
@@ -0,0 +1,63 @@
+//! Running the rana binary, shared by the integration tests
+
+// each test crate uses only some of them
+#![allow(dead_code)]
+
+use std::process::{Child, Output, Stdio};
+
+use assert_cmd::Command;
+
+/// The rana binary with `args` and an empty stdin, so it never waits for input
+pub fn rana(args: &[&str]) -> Command {
+    let mut command = Command::cargo_bin("rana").unwrap();
+    command.args(args).write_stdin("");
+    command
+}
+
+/// Start rana with `args` in the background, with its stdout piped, to
+/// signal it while it runs
+pub fn spawn(args: &[&str]) -> Child {
+    std::process::Command::new(env!("CARGO_BIN_EXE_rana"))
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Could not run rana")
+}
+
+/// Run rana with `args` on a single core without a benchmark, check that it
+/// exits with `code` and return its output
+pub fn run(args: &[&str], code: i32) -> Output {
+    rana(&["-c", "1", "--no-benchmark"])
+        .args(args)
+        .assert()
+        .code(code)
+        .get_output()
+        .clone()
+}
+
+/// The keys a successful `--format jsonl` run with `args` reported
+pub fn found_keys(args: &[&str]) -> Vec<serde_json::Value> {
+    json_lines(&run(&[&["--format", "jsonl"], args].concat(), 0).stdout)
+}
+
+/// The JSON objects of a `--format jsonl` output, one per line
+pub fn json_lines(stdout: &[u8]) -> Vec<serde_json::Value> {
+    std::str::from_utf8(stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Not a JSON line"))
+        .collect()
+}
+
+/// Keys tried by a stopped run, from the "Stopped. N iterations" summary in
+/// its `output`
+pub fn iterations_tried(output: &str) -> u64 {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("Stopped. "))
+        .and_then(|summary| summary.split_once(" iterations"))
+        .map(|(count, _)| count.replace(',', "").parse().unwrap())
+        .unwrap_or_else(|| panic!("no summary in {output}"))
+}
@@ -0,0 +1,40 @@
+//! Measuring the rate of the configured matcher with --count-only
+
+mod common;
+
+use common::rana;
+
+#[test]
+fn count_only_reports_a_rate_and_prints_no_keys() {
+    // one key in 16 matches, so plenty are found and discarded
+    let output = rana(&["-c", "1", "--vanity", "0", "--benchmark-duration", "1"])
+        .arg("--count-only")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(!output.contains("Benchmarking"), "{output}");
+    assert!(!output.contains("Found matching public key"), "{output}");
+    assert!(!output.contains("nsec"), "{output}");
+    assert!(!output.contains("npub"), "{output}");
+    let counted = output
+        .lines()
+        .find(|line| line.starts_with("Counted "))
+        .unwrap_or_else(|| panic!("no rate in {output}"));
+    let rate: u64 = counted
+        .split(" at ")
+        .nth(1)
+        .and_then(|rest| rest.split(" h/s").next())
+        .unwrap()
+        .replace(',', "")
+        .parse()
+        .unwrap();
+    assert!(rate > 0, "{counted}");
+}
+
+#[test]
+fn count_only_runs_for_the_benchmark_duration_not_a_timeout() {
+    rana(&["--count-only", "--timeout", "1"]).assert().code(2);
+}
@@ -0,0 +1,44 @@
+//! The --format csv rows, read back as a spreadsheet would
+
+mod common;
+
+use common::{json_lines, run};
+
+/// Stdout of a sequential run reporting keys starting with a 0 in `format`
+fn output(format: &str) -> Vec<u8> {
+    let start = format!("{:064x}", 1);
+    let args = ["-v", "0", "--max-iterations", "500", "--start-from", &start];
+    run(&[&args[..], &["--format", format]].concat(), 0).stdout
+}
+
+#[test]
+fn csv_has_a_header_and_a_row_per_match() {
+    let found = json_lines(&output("jsonl"));
+    assert!(found.len() > 2);
+
+    let csv = String::from_utf8(output("csv")).unwrap();
+    let mut lines = csv.lines();
+    let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+    assert_eq!(
+        header,
+        [
+            "index",
+            "npub",
+            "nsec",
+            "pubkey_hex",
+            "difficulty",
+            "found_at_secs"
+        ]
+    );
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    assert_eq!(rows.len(), found.len(), "{csv}");
+    for (index, (row, key_match)) in rows.iter().zip(&found).enumerate() {
+        assert_eq!(row.len(), header.len(), "{row:?}");
+        assert_eq!(row[0], (index + 1).to_string());
+        assert_eq!(row[1], key_match["npub"]);
+        assert_eq!(row[2], key_match["nsec"]);
+        assert_eq!(row[3], key_match["pubkey"]);
+        assert_eq!(row[4], key_match["leading_zero_bits"].to_string());
+        assert!(row[5].parse::<f64>().unwrap() >= 0.0);
+    }
+}
@@ -0,0 +1,83 @@
+//! Raising the bar after each find with --open-ended, the default, or
+//! reporting every key of the difficulty with --fixed
+
+mod common;
+
+use common::{found_keys, rana};
+use nostr::prelude::*;
+
+/// Secret and leading zero bits of the keys found with `args` at a
+/// difficulty of 3, walking the secrets from 1
+fn found(args: &[&str]) -> Vec<(u64, u64)> {
+    let start = format!("{:064x}", 1);
+    let base = [
+        "-d",
+        "3",
+        "--max-iterations",
+        "2000",
+        "--start-from",
+        &start,
+    ];
+    found_keys(&[&base[..], args].concat())
+        .iter()
+        .map(|key_match| {
+            (
+                key_match["found_at_iteration"].as_u64().unwrap(),
+                key_match["leading_zero_bits"].as_u64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// Leading zero bits of the key with secret `secret`
+fn leading_zero_bits(secret: u64) -> u64 {
+    let keys = Keys::from_sk_str(&format!("{secret:064x}")).unwrap();
+    let key = keys.public_key().serialize();
+    let zero_bytes = key.iter().take_while(|&&byte| byte == 0).count();
+    zero_bytes as u64 * 8 + key.get(zero_bytes).map_or(0, |b| b.leading_zeros() as u64)
+}
+
+/// The keys of the secrets up to `last` reaching the difficulty
+fn reaching_the_difficulty(last: u64) -> Vec<(u64, u64)> {
+    (1..=last)
+        .map(|secret| (secret, leading_zero_bits(secret)))
+        .filter(|&(_, bits)| bits >= 3)
+        .collect()
+}
+
+#[test]
+fn open_ended_search_only_reports_ever_rarer_keys() {
+    let default = found(&[]);
+    assert!(!default.is_empty());
+    assert_eq!(found(&["--open-ended"]), default);
+
+    // each key beats the best one before it, the first one the difficulty
+    let last = default.last().unwrap().0;
+    let mut best = 3;
+    let expected: Vec<(u64, u64)> = reaching_the_difficulty(last)
+        .into_iter()
+        .filter(|&(_, bits)| {
+            let rarer = bits > best;
+            best = best.max(bits);
+            rarer
+        })
+        .collect();
+    assert_eq!(default, expected);
+}
+
+#[test]
+fn fixed_search_reports_every_key_of_the_difficulty() {
+    for alias in ["--fixed", "--disable-bar-raising"] {
+        let fixed = found(&[alias]);
+        // one key in 8 has 3 leading zero bits
+        assert!(fixed.len() > 50, "{alias}: {fixed:?}");
+        let last = fixed.last().unwrap().0;
+        assert_eq!(fixed, reaching_the_difficulty(last), "{alias}");
+    }
+}
+
+#[test]
+fn fixed_and_open_ended_exclude_each_other() {
+    rana(&["--fixed", "--open-ended"]).assert().code(2);
+    rana(&["--fixed", "--vanity", "dead"]).assert().code(2);
+}
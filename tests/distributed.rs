@@ -0,0 +1,72 @@
+//! Workers of a --coordinator that can't be reached or goes away
+
+#![cfg(feature = "distributed")]
+
+mod common;
+
+use std::net::TcpListener;
+use std::thread;
+
+use common::rana;
+
+#[test]
+fn an_unreachable_coordinator_is_reported_without_a_panic() {
+    // nothing listens on a port that was just freed
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let output = rana(&[
+        "-c",
+        "1",
+        "-d",
+        "5",
+        "--no-benchmark",
+        "--coordinator",
+        &addr,
+    ])
+    .env_remove("RUST_BACKTRACE")
+    .output()
+    .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("Could not connect to the coordinator"),
+        "{stdout}"
+    );
+    assert!(!stderr.contains("panicked"), "{stderr}");
+}
+
+#[test]
+fn a_lost_coordinator_stops_the_search() {
+    // a coordinator that hangs up on every worker
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            drop(stream);
+        }
+    });
+    let output = rana(&[
+        "-c",
+        "1",
+        "-d",
+        "5",
+        "--no-benchmark",
+        "--coordinator",
+        &addr,
+    ])
+    .env_remove("RUST_BACKTRACE")
+    .output()
+    .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("Lost the connection to the coordinator"),
+        "{stdout}"
+    );
+    assert!(!stderr.contains("panicked"), "{stderr}");
+}
@@ -0,0 +1,322 @@
+//! Run the real binary through a whole bounded search in each mode and check
+//! the keys it reports
+
+mod common;
+
+use common::{found_keys, iterations_tried, json_lines, rana, run};
+use nostr::prelude::*;
+
+/// Mine with `args` until the iteration limit and return the reported keys
+fn mine(args: &[&str]) -> Vec<serde_json::Value> {
+    found_keys(&[&["--max-iterations", "2000"], args].concat())
+}
+
+/// Check that every encoding of a reported key is of one and the same key
+fn assert_valid_key(key_match: &serde_json::Value) -> Keys {
+    let field = |name: &str| key_match[name].as_str().unwrap().to_string();
+    let keys = Keys::from_sk_str(&field("nsec")).unwrap();
+    assert_eq!(
+        keys.secret_key().unwrap().display_secret().to_string(),
+        field("secret_key")
+    );
+    assert_eq!(keys.public_key().to_string(), field("pubkey"));
+    assert_eq!(keys.public_key().to_bech32().unwrap(), field("npub"));
+    keys
+}
+
+#[test]
+fn hex_prefix_search_reports_matching_keys() {
+    let found = mine(&["--vanity", "a"]);
+    assert!(!found.is_empty());
+    for key_match in &found {
+        let keys = assert_valid_key(key_match);
+        assert!(keys.public_key().to_string().starts_with('a'));
+    }
+}
+
+#[test]
+fn npub_prefix_search_reports_matching_keys() {
+    let found = mine(&["--vanity-n-prefix", "r"]);
+    assert!(!found.is_empty());
+    for key_match in &found {
+        let keys = assert_valid_key(key_match);
+        assert!(keys.public_key().to_bech32().unwrap().starts_with("npub1r"));
+        assert_eq!(key_match["vanity"], "r");
+    }
+}
+
+#[test]
+fn difficulty_search_reports_ever_rarer_keys() {
+    let found = mine(&["--difficulty", "2"]);
+    assert!(!found.is_empty());
+    let mut best = 2;
+    for key_match in &found {
+        let keys = assert_valid_key(key_match);
+        let bits = key_match["leading_zero_bits"].as_u64().unwrap();
+        assert!(bits > best);
+        assert!(keys.public_key().serialize()[0].leading_zeros() as u64 >= bits.min(8));
+        best = bits;
+    }
+}
+
+#[test]
+fn plain_output_names_every_encoding() {
+    let output = run(&["--max-iterations", "200", "--vanity", "0"], 0);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    for label in [
+        "Found matching public key: ",
+        "Nostr private key:         ",
+        "Nostr public key (npub):   npub1",
+        "Nostr private key (nsec):  nsec1",
+    ] {
+        assert!(stdout.contains(label), "missing '{label}'");
+    }
+}
+
+#[test]
+fn just_generate_prints_exactly_one_key() {
+    let output = rana(&["--just-generate"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let stdout = String::from_utf8(output).unwrap();
+    assert_eq!(stdout.matches("Nostr public key (npub):").count(), 1);
+    assert!(!stdout.contains("Benchmarking"));
+
+    let keys = mine(&["--just-generate"]);
+    assert_eq!(keys.len(), 1);
+    assert_valid_key(&keys[0]);
+}
+
+#[test]
+fn no_stats_leaves_out_the_line_after_every_match() {
+    let stdout = |no_stats: bool| {
+        let args = ["--max-iterations", "200", "--vanity", "0"];
+        let no_stats = if no_stats { &["--no-stats"][..] } else { &[] };
+        String::from_utf8(run(&[&args[..], no_stats].concat(), 0).stdout).unwrap()
+    };
+    // the summary after the run starts with "Stopped." and is kept
+    let stats_lines = |stdout: &str| {
+        stdout
+            .lines()
+            .filter(|line| line.contains("hashes/second") && !line.starts_with("Stopped."))
+            .count()
+    };
+
+    let with_stats = stdout(false);
+    assert!(stats_lines(&with_stats) > 0, "{with_stats}");
+    let without_stats = stdout(true);
+    assert_eq!(stats_lines(&without_stats), 0, "{without_stats}");
+    assert!(without_stats.contains("Found matching public key: "));
+}
+
+#[test]
+fn vanity_matches_report_their_leading_zero_bits() {
+    let leading_zero_bits = |keys: &Keys| {
+        let key = keys.public_key().serialize();
+        let zero_bytes = key.iter().take_while(|&&byte| byte == 0).count();
+        zero_bytes as u64 * 8 + key.get(zero_bytes).map_or(0, |b| b.leading_zeros() as u64)
+    };
+    // a hex prefix of 0 starts every key with at least 4 zero bits
+    let found = mine(&["--vanity", "0"]);
+    assert!(!found.is_empty());
+    for key_match in &found {
+        let keys = assert_valid_key(key_match);
+        let bits = key_match["leading_zero_bits"].as_u64().unwrap();
+        assert!(bits >= 4);
+        assert_eq!(bits, leading_zero_bits(&keys));
+    }
+
+    for key_match in &mine(&["--vanity-n-prefix", "q"]) {
+        let keys = assert_valid_key(key_match);
+        assert_eq!(
+            key_match["leading_zero_bits"].as_u64().unwrap(),
+            leading_zero_bits(&keys)
+        );
+    }
+}
+
+#[test]
+fn relay_publish_dry_run_prints_a_signed_profile_event() {
+    let args = ["--max-iterations", "200", "--vanity", "0", "--no-stats"];
+    let publish = ["--preview", "--name", "frog", "--relay-publish-dry-run"];
+    let stdout = String::from_utf8(run(&[&args[..], &publish].concat(), 0).stdout).unwrap();
+    let events: Vec<Event> = stdout
+        .lines()
+        .filter(|line| line.starts_with('{'))
+        .map(|line| Event::from_json(line).unwrap())
+        .collect();
+    let pubkeys: Vec<&str> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("Found matching public key: "))
+        .collect();
+    assert!(!events.is_empty());
+    assert_eq!(events.len(), pubkeys.len());
+
+    for (event, pubkey) in events.iter().zip(pubkeys) {
+        event.verify().unwrap();
+        assert_eq!(event.kind, Kind::Metadata);
+        assert_eq!(event.pubkey.to_string(), pubkey);
+        let metadata = Metadata::from_json(&event.content).unwrap();
+        assert_eq!(metadata.name.as_deref(), Some("frog"));
+        // the id printed by --preview is the one of the signed event
+        let id_line = format!("): {}", event.id.to_hex());
+        assert!(stdout.contains(&id_line), "{stdout}");
+    }
+}
+
+#[test]
+fn find_lowest_without_a_difficulty_always_reports_a_key() {
+    // mnemonic keys are slow, so the few tried are most likely below the
+    // default difficulty, which must not apply to the lowest key search
+    let found = found_keys(&["-g", "12", "--find-lowest", "--timeout", "1s"]);
+    assert_eq!(found.len(), 1, "{found:?}");
+    assert_valid_key(&found[0]);
+}
+
+#[test]
+fn find_lowest_reports_the_smallest_key_it_tried() {
+    // sequential keys from 1, so the test can walk the same ones
+    let start = format!("{:064x}", 1);
+    let output = run(
+        &[
+            "--find-lowest",
+            "--timeout",
+            "1",
+            "--format",
+            "jsonl",
+            "--start-from",
+            &start,
+        ],
+        0,
+    );
+    let found = json_lines(&output.stdout);
+    assert_eq!(found.len(), 1, "{found:?}");
+    let keys = assert_valid_key(&found[0]);
+
+    let tried = iterations_tried(&String::from_utf8(output.stderr).unwrap());
+    let lowest = (1..=tried)
+        .map(|secret| {
+            let keys = Keys::from_sk_str(&format!("{secret:064x}")).unwrap();
+            keys.public_key().serialize()
+        })
+        .min()
+        .unwrap();
+    assert_eq!(keys.public_key().serialize(), lowest);
+    // the key with secret n is the n-th one tried
+    let secret = keys.secret_key().unwrap().display_secret().to_string();
+    assert_eq!(
+        found[0]["found_at_iteration"].as_u64(),
+        u64::from_str_radix(&secret, 16).ok()
+    );
+}
+
+#[test]
+fn multibyte_tags_reach_every_output_intact() {
+    let tag = "🐸 frog 🇩🇰 cafe\u{301}";
+    let output = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("multibyte_tag.txt");
+    let _ = std::fs::remove_file(&output);
+    let found = mine(&[
+        "--vanity",
+        "a",
+        "--tag",
+        tag,
+        "--output",
+        output.to_str().unwrap(),
+    ]);
+    assert!(!found.is_empty());
+    assert!(found.iter().all(|key_match| key_match["tag"] == tag));
+    let written = std::fs::read_to_string(&output).unwrap();
+    assert!(written.contains(&format!("Tag:                       {tag}\n")));
+}
+
+#[test]
+fn seeded_chacha_runs_find_the_same_keys() {
+    let run = |rng: &[&str]| found_keys(&[&["-v", "0", "--max-iterations", "300"], rng].concat());
+    for rng in ["thread", "os", "chacha"] {
+        let found = run(&["--rng", rng]);
+        assert!(!found.is_empty(), "{rng}");
+        for key_match in &found {
+            assert_valid_key(key_match);
+        }
+    }
+
+    let seeded = run(&["--rng", "chacha", "--seed", "42"]);
+    let npubs = |found: &[serde_json::Value]| -> Vec<String> {
+        found.iter().map(|m| m["npub"].to_string()).collect()
+    };
+    assert!(!seeded.is_empty());
+    assert_eq!(
+        npubs(&seeded),
+        npubs(&run(&["--rng", "chacha", "--seed", "42"]))
+    );
+    assert_ne!(
+        npubs(&seeded),
+        npubs(&run(&["--rng", "chacha", "--seed", "43"]))
+    );
+}
+
+#[test]
+fn debug_bytes_show_each_found_public_key() {
+    let args = ["-d", "4", "--debug-bytes", "--max-iterations", "200"];
+    let output = run(&[&args[..], &["--format", "jsonl"]].concat(), 0);
+    let found = json_lines(&output.stdout);
+    assert!(!found.is_empty());
+
+    // the status lines go to stderr, four rows of 8 bytes after each heading
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<&str> = stderr.lines().collect();
+    let shown: Vec<(String, String)> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.starts_with("Public key bytes"))
+        .map(|(index, heading)| {
+            let bytes: Vec<&str> = lines[index + 1..index + 5]
+                .iter()
+                .flat_map(|row| row.split_once(':').unwrap().1.split_whitespace())
+                .collect();
+            assert_eq!(bytes.len(), 32, "{stderr}");
+            (heading.to_string(), bytes.concat())
+        })
+        .collect();
+    assert_eq!(shown.len(), found.len(), "{stderr}");
+    for ((heading, hex), key_match) in shown.iter().zip(&found) {
+        assert_eq!(*hex, key_match["pubkey"].as_str().unwrap());
+        assert_eq!(
+            *heading,
+            format!(
+                "Public key bytes (32, {} leading zero bits):",
+                key_match["leading_zero_bits"]
+            )
+        );
+    }
+}
+
+#[test]
+fn every_match_of_an_open_ended_run_gets_its_own_backup_card() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("backup_cards");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let card = dir.join("card.png");
+    let found = mine(&[
+        "-d",
+        "1",
+        "--start-from",
+        &format!("{:064x}", 1),
+        "--backup-card",
+        card.to_str().unwrap(),
+    ]);
+    assert!(found.len() > 1, "{found:?}");
+    let mut cards: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    cards.sort();
+    let mut expected: Vec<_> = std::iter::once("card.png".to_string())
+        .chain((2..=found.len()).map(|index| format!("card-{index}.png")))
+        .collect();
+    expected.sort();
+    assert_eq!(cards, expected);
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use common::rana;
+
+fn exit_code(args: &[&str]) -> Option<i32> {
+    rana(args)
+        .output()
+        .expect("Could not run rana")
+        .status
+        .code()
+}
+
+#[test]
+fn timeout_without_a_match_exits_with_3() {
+    let args = [
+        "-c",
+        "1",
+        "-d",
+        "60",
+        "--benchmark-duration",
+        "0",
+        "--timeout",
+        "1",
+    ];
+    assert_eq!(exit_code(&args), Some(3));
+}
+
+#[test]
+fn iteration_limit_without_a_match_exits_with_3() {
+    let args = [
+        "-c",
+        "1",
+        "-d",
+        "60",
+        "--benchmark-duration",
+        "0",
+        "--max-iterations",
+        "100",
+    ];
+    assert_eq!(exit_code(&args), Some(3));
+}
+
+#[test]
+fn a_match_exits_with_0() {
+    let args = [
+        "-c",
+        "1",
+        "-d",
+        "1",
+        "--benchmark-duration",
+        "0",
+        "--max-iterations",
+        "100",
+    ];
+    assert_eq!(exit_code(&args), Some(0));
+}
+
+#[test]
+fn invalid_arguments_exit_with_2() {
+    assert_eq!(exit_code(&["--mode", "hex"]), Some(2));
+    assert_eq!(exit_code(&["--no-such-flag"]), Some(2));
+    assert_eq!(exit_code(&["--just-generate", "--seed", "1"]), Some(2));
+}
+
+#[test]
+fn invalid_arguments_are_reported_once_without_a_panic() {
+    for (args, reason) in [
+        (
+            &["--mode", "npub"][..],
+            "--mode npub requires at least one npub prefix",
+        ),
+        (
+            &["-n", "b"][..],
+            "can only contain characters supported by Bech32",
+        ),
+        (
+            &["-n", "rana", "--start-from", "frog"][..],
+            "Invalid scalar 'frog'",
+        ),
+        (
+            &["-n", "rana", "--resume-scalar", &"0".repeat(64)][..],
+            "use a valid 64 character hex secret key",
+        ),
+        (
+            &[
+                "-n",
+                "rana",
+                "-c",
+                "2",
+                "--start-from",
+                "0000000000000000000000000000000000000000000000000000000000000001",
+                "--stride",
+                "18446744073709551615",
+            ][..],
+            "--stride multiplied by the number of cores must fit in 64 bits",
+        ),
+    ] {
+        let output = rana(args).output().expect("Could not run rana");
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(stderr.matches(reason).count(), 1, "{stderr}");
+        assert!(!stderr.contains("panicked"), "{stderr}");
+        assert!(!stderr.contains("RUST_BACKTRACE"), "{stderr}");
+    }
+}
+
+#[test]
+fn verify_exits_with_0_on_a_pass_and_3_on_a_fail() {
+    // the key pair of the NIP-19 test vectors
+    let nsec = "nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5";
+    let npub = "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg";
+    let other = "npub1pszk2fupse037w0pfjh5z65agma2gp3rnzrjhhtdpqkfrplnde2s5a27nf";
+
+    assert_eq!(
+        exit_code(&["verify", "--nsec", nsec, "--npub", npub]),
+        Some(0)
+    );
+    assert_eq!(
+        exit_code(&["verify", "--nsec", nsec, "--npub", other]),
+        Some(3)
+    );
+    assert_eq!(
+        exit_code(&["verify", "--nsec", "nsec1oops", "--npub", npub]),
+        Some(2)
+    );
+}
+
+#[test]
+fn estimate_exits_with_0_and_2_on_an_invalid_target() {
+    assert_eq!(exit_code(&["estimate", "--prefix", "rana"]), Some(0));
+    assert_eq!(
+        exit_code(&["estimate", "--bits", "30", "--rate", "1000"]),
+        Some(0)
+    );
+    assert_eq!(exit_code(&["estimate", "--prefix", "bad"]), Some(2));
+    assert_eq!(exit_code(&["estimate"]), Some(2));
+}
+
+#[test]
+fn mnemonic_validate_exits_with_0_when_valid_and_3_when_not() {
+    let valid =
+        "congress evoke onion donate fantasy soccer project fiction envelope body faith mean";
+    assert_eq!(exit_code(&["mnemonic", "validate", valid]), Some(0));
+    let words: Vec<&str> = valid.split(' ').collect();
+    let mut args = vec!["mnemonic", "validate"];
+    args.extend(&words);
+    assert_eq!(exit_code(&args), Some(0));
+    assert_eq!(
+        exit_code(&["mnemonic", "validate", &valid.replace("onion", "oniion")]),
+        Some(3)
+    );
+    assert_eq!(exit_code(&["mnemonic", "validate"]), Some(2));
+}
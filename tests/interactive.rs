@@ -0,0 +1,19 @@
+mod common;
+
+use common::rana;
+
+#[test]
+fn interactive_prompt_is_skipped_without_a_terminal() {
+    let output = rana(&["-c", "1", "-v", "0", "--interactive"])
+        .args(["--benchmark-duration", "0", "--max-iterations", "400"])
+        .output()
+        .expect("Could not run rana");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout.contains("stdin is not a terminal, --interactive is disabled"));
+    assert!(!stderr.contains("[k]eep mining"));
+    // without a prompt every match is reported until the iteration limit
+    assert!(stdout.matches("Nostr public key (npub)").count() > 1);
+}
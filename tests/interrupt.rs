@@ -0,0 +1,61 @@
+//! Stopping a run with Ctrl+C, and quitting right away with a second one
+
+#![cfg(unix)]
+
+mod common;
+
+use std::process::{Child, Command};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+fn interrupt(child: &Child) {
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .expect("Could not run kill");
+    assert!(status.success());
+}
+
+#[test]
+fn a_second_ctrl_c_quits_without_waiting_for_a_stuck_worker() {
+    // a batch this large keeps the worker from seeing the first Ctrl+C for hours
+    let mut child = common::spawn(&[
+        "-c",
+        "1",
+        "-d",
+        "200",
+        "--no-benchmark",
+        "--batch",
+        "1000000000",
+    ]);
+    sleep(Duration::from_secs(1));
+
+    interrupt(&child);
+    sleep(Duration::from_millis(300));
+    assert!(
+        child.try_wait().unwrap().is_none(),
+        "the first Ctrl+C waits for the worker"
+    );
+    let second = Instant::now();
+    interrupt(&child);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if second.elapsed() > Duration::from_secs(10) {
+            child.kill().unwrap();
+            panic!("rana did not quit after a second Ctrl+C");
+        }
+        sleep(Duration::from_millis(20));
+    };
+
+    assert_eq!(status.code(), Some(130));
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("press Ctrl+C again"), "{stdout}");
+    assert!(
+        stdout.contains("Quitting without waiting for the workers"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("Stopped."), "{stdout}");
+}
@@ -0,0 +1,37 @@
+//! Skipping the matches listed in a --known-pubkeys file
+
+mod common;
+
+use common::found_keys;
+
+fn found_npubs(extra: &[&str]) -> Vec<String> {
+    let start = format!("{:064x}", 1);
+    found_keys(&[&["--max-iterations", "500", "--start-from", &start], extra].concat())
+        .iter()
+        .map(|key_match| key_match["npub"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn known_pubkeys_are_not_reported_again() {
+    let all = found_npubs(&["-v", "0"]);
+    assert!(all.len() > 2, "{all:?}");
+
+    let known = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("known_pubkeys.txt");
+    std::fs::write(&known, format!("# already owned\n{}\n", all[0])).unwrap();
+    let found = found_npubs(&["-v", "0", "--known-pubkeys", known.to_str().unwrap()]);
+    assert_eq!(found, all[1..]);
+}
+
+#[test]
+fn a_known_pubkey_does_not_raise_the_difficulty_bar() {
+    let all = found_npubs(&["-d", "2"]);
+    assert!(all.len() > 2, "{all:?}");
+
+    let known = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("known_difficulty.txt");
+    std::fs::write(&known, format!("{}\n", all[0])).unwrap();
+    let found = found_npubs(&["-d", "2", "--known-pubkeys", known.to_str().unwrap()]);
+    // the next key as difficult as the refused one is reported in its place
+    assert!(!all.contains(&found[0]), "{found:?}");
+    assert_eq!(found[1..], all[1..]);
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use common::found_keys;
+
+#[test]
+fn matches_record_when_they_were_found() {
+    let matches = found_keys(&["-v", "0", "--max-iterations", "400"]);
+    assert!(matches.len() > 1);
+
+    let found_at = |key_match: &serde_json::Value| {
+        (
+            key_match["found_at_secs"].as_f64().unwrap(),
+            key_match["found_at_iteration"].as_u64().unwrap(),
+        )
+    };
+    for pair in matches.windows(2) {
+        let (secs, iteration) = found_at(&pair[0]);
+        let (next_secs, next_iteration) = found_at(&pair[1]);
+        assert!(iteration > 0 && iteration < next_iteration);
+        assert!(secs <= next_secs);
+    }
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{json_lines, rana, run};
+
+#[test]
+fn mnemonic_file_sweep_finds_a_derived_vanity_key() {
+    let output = run(
+        &[
+            "--seed-from-mnemonic-file",
+            "tests/fixtures/mnemonics.txt",
+            "--accounts",
+            "40",
+            "-n=qy2p",
+            "--format",
+            "jsonl",
+        ],
+        0,
+    );
+    let found = json_lines(&output.stdout);
+    assert_eq!(found.len(), 1);
+    assert_eq!(
+        found[0]["npub"],
+        "npub1qy2ph9frggmu0cwda848vsrwsqgl4t7rgpng606f7xhvev786sssztm0sj"
+    );
+    assert_eq!(found[0]["derivation_path"], "m/44'/1237'/29'/0/0");
+    assert!(found[0]["mnemonic"]
+        .as_str()
+        .unwrap()
+        .starts_with("equal dragon"));
+
+    // the invalid line is reported, not fatal
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("skipped line 3"));
+}
+
+#[test]
+fn dedup_reports_a_key_derived_twice_once() {
+    let fixture = std::fs::read_to_string("tests/fixtures/mnemonics.txt").unwrap();
+    let mnemonic = fixture.lines().nth(1).unwrap();
+    let path = std::env::temp_dir().join(format!("rana-dedup-{}.txt", std::process::id()));
+    std::fs::write(&path, format!("{mnemonic}\n{mnemonic}\n")).unwrap();
+
+    let found = |dedup: bool| {
+        let args = [
+            "--seed-from-mnemonic-file",
+            path.to_str().unwrap(),
+            "--accounts",
+            "40",
+            "-n=qy2p",
+            "--format",
+            "jsonl",
+        ];
+        let dedup = if dedup { &["--dedup"][..] } else { &[] };
+        json_lines(&run(&[&args[..], dedup].concat(), 0).stdout).len()
+    };
+    assert_eq!(found(false), 2);
+    assert_eq!(found(true), 1);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn pubkey_only_is_refused_for_a_mnemonic_file_sweep() {
+    let output = rana(&["--seed-from-mnemonic-file", "tests/fixtures/mnemonics.txt"])
+        .args(["--accounts", "40", "-n=qy2p", "--pubkey-only"])
+        .output()
+        .expect("Could not run rana");
+    assert_eq!(output.status.code(), Some(2));
+    // nothing derived from the mnemonics is printed
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("--pubkey-only"), "{stderr}");
+}
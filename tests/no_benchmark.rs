@@ -0,0 +1,51 @@
+//! Skipping the benchmark with --no-benchmark, and printing it as JSON
+
+mod common;
+
+use common::{rana, run};
+
+#[test]
+fn no_benchmark_starts_mining_without_a_benchmark_line() {
+    let output = run(&["-d", "60", "--max-iterations", "100"], 3);
+    let output = String::from_utf8(output.stdout).unwrap();
+    assert!(!output.contains("Benchmarking"), "{output}");
+    assert!(!output.contains("Warming up"), "{output}");
+    assert!(
+        output.contains("how long this takes is unknown"),
+        "{output}"
+    );
+    assert!(output.contains("Mining using"), "{output}");
+}
+
+#[test]
+fn no_benchmark_cannot_pick_a_difficulty_for_a_target_time() {
+    rana(&["--no-benchmark", "--target-time", "1m"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn benchmark_json_prints_the_benchmark_as_a_json_line() {
+    let output = rana(&["-c", "1", "-d", "60", "--max-iterations", "100"])
+        .args(["--warmup", "0", "--benchmark-duration", "1"])
+        .arg("--benchmark-json")
+        .assert()
+        .code(3)
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    let line = output
+        .lines()
+        .find(|line| line.starts_with('{'))
+        .unwrap_or_else(|| panic!("no JSON line in {output}"));
+    let result: serde_json::Value = serde_json::from_str(line).unwrap();
+    assert_eq!(result["cores"], 1);
+    assert!(result["rate_hps"].as_u64().unwrap() > 0, "{line}");
+    assert_eq!(result["duration_secs"], 1.0);
+    // the human output is still there
+    assert!(
+        output.contains("A single core can mine roughly"),
+        "{output}"
+    );
+}
@@ -0,0 +1,30 @@
+//! Appending the npubs of the found keys to a --npub-log watch-list
+
+mod common;
+
+use common::found_keys;
+
+#[test]
+fn npub_log_gets_one_npub_per_match_and_no_secrets() {
+    let log = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("npub_log.txt");
+    let _ = std::fs::remove_file(&log);
+    let found = found_keys(&[
+        "-v",
+        "0",
+        "--max-iterations",
+        "500",
+        "--npub-log",
+        log.to_str().unwrap(),
+    ]);
+    assert!(found.len() > 1);
+
+    let logged = std::fs::read_to_string(&log).unwrap();
+    let npubs: Vec<&str> = logged.lines().collect();
+    assert_eq!(npubs.len(), found.len(), "{logged}");
+    for (npub, key_match) in npubs.iter().zip(&found) {
+        assert_eq!(key_match["npub"], *npub);
+        assert!(!logged.contains(key_match["nsec"].as_str().unwrap()));
+        assert!(!logged.contains(key_match["secret_key"].as_str().unwrap()));
+    }
+    assert!(!logged.contains("nsec"));
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use std::fs;
+
+use common::rana;
+
+#[test]
+fn unwritable_output_file_does_not_stop_reporting() {
+    let tmp = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("output_failure");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(&tmp).unwrap();
+
+    let output = rana(&["-c", "1", "-v", "0", "--format", "jsonl"])
+        .args(["--benchmark-duration", "0", "--max-iterations", "400"])
+        .args(["--output", "/nonexistent/keys.txt"])
+        .env("TMPDIR", &tmp)
+        .output()
+        .expect("Could not run rana");
+    assert_eq!(output.status.code(), Some(0));
+
+    // every key still reaches stdout
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let matches = stdout.lines().count();
+    assert!(matches > 1);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("WARNING: could not write the key to /nonexistent/keys.txt"));
+
+    // and is kept in the fallback file
+    let fallback = fs::read_to_string(tmp.join("rana-unsaved-keys.txt")).unwrap();
+    assert_eq!(
+        fallback.matches("Nostr private key (nsec)").count(),
+        matches
+    );
+}
@@ -0,0 +1,53 @@
+//! Mining for the mixed hex and npub targets of a --pattern-file
+
+mod common;
+
+use common::{json_lines, rana, run};
+use nostr::prelude::*;
+
+#[test]
+fn pattern_file_matches_both_kinds_of_target() {
+    let output = run(
+        &[
+            "--format",
+            "jsonl",
+            "--pattern-file",
+            "tests/fixtures/patterns.txt",
+            "--max-iterations",
+            "400",
+        ],
+        0,
+    );
+    let found = json_lines(&output.stdout);
+    let mut kinds = Vec::new();
+    for key_match in &found {
+        let keys = Keys::from_sk_str(key_match["nsec"].as_str().unwrap()).unwrap();
+        let matched = key_match["vanity"].as_str().unwrap();
+        match matched.split_once(':').unwrap() {
+            ("hex", prefix) => assert!(keys.public_key().to_string().starts_with(prefix)),
+            ("npub", prefix) => assert!(keys
+                .public_key()
+                .to_bech32()
+                .unwrap()
+                .starts_with(&format!("npub1{prefix}"))),
+            _ => panic!("unexpected target {matched}"),
+        }
+        kinds.push(matched.to_string());
+    }
+    // one key in 16 starts with an a, one in 32 npubs with a q
+    assert!(kinds.iter().any(|kind| kind == "hex:a"), "{kinds:?}");
+    assert!(kinds.iter().any(|kind| kind == "npub:q"), "{kinds:?}");
+
+    // the invalid line is reported, not fatal
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("skipped line 5"), "{stderr}");
+}
+
+#[test]
+fn pattern_file_without_patterns_is_rejected() {
+    let path = std::env::temp_dir().join(format!("rana-patterns-{}.txt", std::process::id()));
+    std::fs::write(&path, "# nothing to mine for\ndead\n").unwrap();
+    let assert = rana(&["--pattern-file", path.to_str().unwrap()]).assert();
+    std::fs::remove_file(&path).unwrap();
+    assert.code(2);
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use common::rana;
+
+#[test]
+fn finds_below_the_report_limit_are_counted_but_not_printed() {
+    let output = rana(&["-c", "1", "-d", "1", "--report-limit", "255"])
+        .args(["--benchmark-duration", "0", "--max-iterations", "200"])
+        .output()
+        .expect("Could not run rana");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    // the best of them is still kept and makes the run successful
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout.contains("Rarest key found during this run:"));
+    assert_eq!(stdout.matches("Found matching public key").count(), 0);
+    let counted: u64 = stdout
+        .lines()
+        .find_map(|line| {
+            line.strip_prefix("Matches below --report-limit 255, counted but not printed: ")
+        })
+        .expect("No count of the unreported matches")
+        .parse()
+        .unwrap();
+    assert!(counted >= 1);
+}